@@ -12,25 +12,28 @@
 //! oracle: the produced gzip stream must round-trip back to the exact input bytes.
 
 use libfuzzer_sys::fuzz_target;
-use std::io::Write;
+use std::io::{Read, Write};
 
 use cargo_deb::compress::{select_compressor, Format};
 
 fuzz_target!(|data: &[u8]| {
     // fast=false => zopfli deep path; use_system=false => fully in-process (no child process).
-    let mut comp = match select_compressor(false, Format::Gzip, false) {
+    let mut comp = match select_compressor(false, Format::Gzip, &Default::default(), false, false, &std::env::temp_dir()) {
         Ok(c) => c,
         Err(_) => return,
     };
     if comp.write_all(data).is_err() {
         return;
     }
-    let _compressed = match comp.finish() {
+    let mut compressed = match comp.finish() {
         Ok(c) => c,
         Err(_) => return,
     };
-    // `Compressed` derefs to the Vec<u8> gzip stream; it must be non-empty even for empty input
-    // (gzip always emits a header+footer) and must decompress back to `data`.
-    let stream: &[u8] = &_compressed;
+    // `Compressed` is read back from its temp file; the gzip stream must be non-empty even for
+    // empty input (gzip always emits a header+footer) and must decompress back to `data`.
+    let mut stream = Vec::new();
+    if compressed.read_to_end(&mut stream).is_err() {
+        return;
+    }
     assert!(!stream.is_empty(), "gzip stream must never be empty");
 });
@@ -12,23 +12,26 @@
 //! stream is non-empty and round-trips back to the exact input bytes.
 
 use libfuzzer_sys::fuzz_target;
-use std::io::Write;
+use std::io::{Read, Write};
 
 use cargo_deb::compress::{select_compressor, Format};
 
 fuzz_target!(|data: &[u8]| {
     // fast=true => xz fast preset; use_system=false => fully in-process (no child process).
-    let mut comp = match select_compressor(true, Format::Xz, false) {
+    let mut comp = match select_compressor(true, Format::Xz, &Default::default(), false, false, &std::env::temp_dir()) {
         Ok(c) => c,
         Err(_) => return,
     };
     if comp.write_all(data).is_err() {
         return;
     }
-    let compressed = match comp.finish() {
+    let mut compressed = match comp.finish() {
         Ok(c) => c,
         Err(_) => return,
     };
-    let stream: &[u8] = &compressed;
+    let mut stream = Vec::new();
+    if compressed.read_to_end(&mut stream).is_err() {
+        return;
+    }
     assert!(!stream.is_empty(), "xz stream must never be empty");
 });
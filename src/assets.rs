@@ -5,9 +5,13 @@ use crate::parse::manifest::CargoDebAssetArrayOrTable;
 use crate::util::compress::gzipped;
 use crate::util::read_file_to_bytes;
 use rayon::prelude::*;
+use sha2::{Digest, Sha256};
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::env::consts::DLL_SUFFIX;
-use std::path::{Path, PathBuf};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
 use std::{fmt, fs};
 
 
@@ -19,6 +23,13 @@ pub enum AssetSource {
     Symlink(SymlinkKind),
     /// Write data to destination as-is.
     Data(Vec<u8>),
+    /// An explicit directory, possibly empty
+    Dir,
+    /// Same contents as an earlier asset at `link_to`, archived as a hardlink to it
+    Hardlink {
+        link_to: PathBuf,
+        source: Box<AssetSource>,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -56,8 +67,9 @@ impl AssetSource {
         match self {
             Self::Symlink(SymlinkKind ::Copied { source_path:p }) 
             | Self::Path(p) => Some(p),
+            Self::Hardlink { source, .. } => source.source_path(),
             Self::Symlink(SymlinkKind::Created { .. })
-            | Self::Data(_) => None,
+            | Self::Data(_) | Self::Dir => None,
         }
     }
 
@@ -66,8 +78,9 @@ impl AssetSource {
         match self {
             Self::Symlink(SymlinkKind ::Copied { source_path:p }) 
             | Self::Path(p) => Some(p),
+            Self::Hardlink { source, .. } => source.into_path(),
             Self::Symlink(SymlinkKind::Created { .. })
-            | Self::Data(_) => None,
+            | Self::Data(_) | Self::Dir => None,
         }
     }
 
@@ -76,12 +89,18 @@ impl AssetSource {
         matches!(self, Self::Symlink(_))
     }
 
+    #[must_use]
+    pub fn is_dir(&self) -> bool {
+        matches!(self, Self::Dir)
+    }
+
     #[must_use]
     pub fn file_size(&self) -> Option<u64> {
         match *self {
             Self::Path(ref p) => fs::metadata(p).ok().map(|m| m.len()),
             Self::Data(ref d) => Some(d.len() as u64),
-            Self::Symlink(_) => None,
+            // the data is stored only once
+            Self::Symlink(_) | Self::Dir | Self::Hardlink { .. } => None,
         }
     }
 
@@ -93,6 +112,8 @@ impl AssetSource {
                 Cow::Owned(data)
             },
             Self::Data(d) => Cow::Borrowed(d),
+            Self::Dir => Cow::Borrowed(&[]),
+            Self::Hardlink { source, .. } => return source.data(),
             Self::Symlink(SymlinkKind::Copied { source_path:p }) => {
                 let data = read_file_to_bytes(p)
                     .map_err(|e| CargoDebError::IoFile("Symlink unexpectedly used to read file data", e, p.clone()))?;
@@ -104,19 +125,38 @@ impl AssetSource {
         })
     }
 
+    /// Opens the asset for streaming into the archive, without loading it into memory.
+    ///
+    /// Returns the size of the data alongside the reader, since tar headers need the size upfront.
+    pub fn reader(&self) -> CDResult<(u64, Box<dyn Read + '_>)> {
+        let (p, context) = match self {
+            Self::Data(d) => return Ok((d.len() as u64, Box::new(&d[..]))),
+            Self::Dir => return Ok((0, Box::new(std::io::empty()))),
+            Self::Hardlink { source, .. } => return source.reader(),
+            Self::Path(p) => (p, "Unable to read asset to add to archive"),
+            Self::Symlink(SymlinkKind::Copied { source_path: p }) => (p, "Symlink unexpectedly used to read file data"),
+            Self::Symlink(SymlinkKind::Created { target_path, .. }) => {
+                return Err(CargoDebError::CannotReadVirtualSymlink(target_path.to_owned()));
+            },
+        };
+        let file = File::open(p).map_err(|e| CargoDebError::IoFile(context, e, p.clone()))?;
+        let len = file.metadata().map_err(|e| CargoDebError::IoFile(context, e, p.clone()))?.len();
+        Ok((len, Box::new(file)))
+    }
+
     pub(crate) fn magic_bytes(&self) -> Option<[u8; 4]> {
         match self {
             Self::Path(p) | Self::Symlink(SymlinkKind::Copied { source_path:p }) => {
                 let mut buf = [0; 4];
-                use std::io::Read;
-                let mut file = std::fs::File::open(p).ok()?;
+                let mut file = File::open(p).ok()?;
                 file.read_exact(&mut buf[..]).ok()?;
                 Some(buf)
             },
             Self::Data(d) => {
                 d.get(..4).and_then(|b| b.try_into().ok())
             },
-            Self::Symlink(SymlinkKind::Created { .. }) => {
+            Self::Hardlink { source, .. } => source.magic_bytes(),
+            Self::Symlink(SymlinkKind::Created { .. }) | Self::Dir => {
                 None
             }
         }
@@ -159,31 +199,38 @@ pub(crate) enum RawAsset {
         target_path: PathBuf,
         chmod: Option<u32>,
         preserve_symlinks: Option<bool>,
+        owner: AssetOwner,
     },
     Symlink {
         target_path: PathBuf,
         link_name: PathBuf
-    }
+    },
+    Dir {
+        target_path: PathBuf,
+        chmod: Option<u32>,
+        owner: AssetOwner,
+    },
 }
 
 impl RawAsset {
     pub(crate) fn source_path(&self) -> Option<&PathBuf> {
         match self {
             RawAsset::Asset { source_path, .. } => Some(source_path),
-            RawAsset::Symlink { .. } => None,
+            RawAsset::Symlink { .. } | RawAsset::Dir { .. } => None,
         }
     }
 
     pub(crate) fn target_path(&self) -> &PathBuf {
         match self {
             RawAsset::Asset { target_path, .. } 
-            | RawAsset::Symlink { target_path,.. } => target_path,
+            | RawAsset::Symlink { target_path,.. }
+            | RawAsset::Dir { target_path, .. } => target_path,
         }
     }
 
     pub(crate) fn chmod(&self) -> Option<u32> {
         match self {
-            RawAsset::Asset {  chmod, .. } => *chmod,
+            RawAsset::Asset {  chmod, .. } | RawAsset::Dir { chmod, .. } => *chmod,
             RawAsset::Symlink { .. } => None /* or should this return 0o777 ? */,
         }
     }
@@ -191,7 +238,7 @@ impl RawAsset {
     #[cfg(test)]
     pub(crate) fn link_name(&self) -> Option<&PathBuf>{
         match self {
-            RawAsset::Asset { .. } => None,
+            RawAsset::Asset { .. } | RawAsset::Dir { .. } => None,
             RawAsset::Symlink { link_name ,..} => Some(link_name),
         }
     }
@@ -263,35 +310,47 @@ pub enum UnresolvedAsset {
     Symlink {
         link_name: PathBuf,
         c: AssetCommon,
-    }
+    },
+    Dir {
+        c: AssetCommon,
+    },
 }
 
 impl UnresolvedAsset {
-    pub(crate) fn new_asset(source_path: PathBuf, target_path: PathBuf, chmod: Option<u32>, is_built: IsBuilt, asset_kind: AssetKind, preserve_symlinks: bool) -> Self {
+    pub(crate) fn new_asset(source_path: PathBuf, target_path: PathBuf, chmod: Option<u32>, owner: AssetOwner, is_built: IsBuilt, asset_kind: AssetKind, preserve_symlinks: bool) -> Self {
         Self::Asset {
             source_path,
             preserve_symlinks,
-            c: AssetCommon { target_path, chmod, asset_kind, is_built },
+            c: AssetCommon { target_path, chmod, owner, asset_kind, is_built },
         }
     }
     pub(crate) fn new_symlink( target_path: PathBuf, link_name: PathBuf) -> Self {
         Self::Symlink {
             link_name,
-            c: AssetCommon { target_path, chmod: None, asset_kind: AssetKind::Any, is_built: IsBuilt::No },
+            c: AssetCommon { target_path, chmod: None, owner: AssetOwner::default(), asset_kind: AssetKind::Any, is_built: IsBuilt::No },
+        }
+    }
+
+    pub(crate) fn new_dir(target_path: PathBuf, chmod: Option<u32>, owner: AssetOwner) -> Self {
+        // the trailing slash would mean "file name of the source" for other assets
+        let target_path = target_path.components().filter(|c| !matches!(c, Component::RootDir | Component::CurDir)).collect();
+        Self::Dir {
+            c: AssetCommon { target_path, chmod, owner, asset_kind: AssetKind::Any, is_built: IsBuilt::No },
         }
     }
 
     pub fn common(&self) ->&AssetCommon {
         match self {
             UnresolvedAsset::Asset {c, .. } 
-            | UnresolvedAsset::Symlink {c, .. } => c,
+            | UnresolvedAsset::Symlink {c, .. }
+            | UnresolvedAsset::Dir { c } => c,
         }
     }
     
 
     /// Convert `source_path` (with glob or dir) to actual path
     pub fn resolve(&self) -> CDResult<Vec<Asset>> {
-        let (source_path, &preserve_symlinks, &AssetCommon { ref target_path, chmod, is_built, asset_kind }) =  match self {
+        let (source_path, &preserve_symlinks, &AssetCommon { ref target_path, chmod, ref owner, is_built, asset_kind }) =  match self {
             UnresolvedAsset::Symlink { link_name, c } => {
                 let target_path = Asset::normalized_target_path(c.target_path.to_owned(), Some(link_name));               
 
@@ -300,6 +359,13 @@ impl UnresolvedAsset {
                     processed_from: Some(ProcessedFrom { original_path: None, action: "symlink" }), c: AssetCommon { target_path, ..c.clone() }
                 }])
             },
+            UnresolvedAsset::Dir { c } => {
+                return Ok(vec![Asset {
+                    source: AssetSource::Dir,
+                    processed_from: Some(ProcessedFrom { original_path: None, action: "dir" }),
+                    c: c.clone(),
+                }])
+            },
             UnresolvedAsset::Asset { source_path, preserve_symlinks, c } =>  {
                 (source_path, preserve_symlinks, c)
             },
@@ -359,7 +425,7 @@ impl UnresolvedAsset {
                         Some(file_chmod),
                         is_built,
                         asset_kind,
-                    );
+                    ).with_owner(owner.clone());
                     if source_prefix_len.is_some() {
                         Ok(asset.processed("glob", None))
                     } else {
@@ -382,15 +448,85 @@ impl UnresolvedAsset {
     pub(crate) fn source_path(&self) -> Option<&Path> {
         match self {
             UnresolvedAsset::Asset { source_path, .. } => Some(source_path),
-            UnresolvedAsset::Symlink { .. } => None,
+            UnresolvedAsset::Symlink { .. } | UnresolvedAsset::Dir { .. } => None,
+        }
+    }
+}
+
+/// `user` and `group` of a file in the package. Unset means root.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct AssetOwner {
+    pub user: Option<OwnerId>,
+    pub group: Option<OwnerId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum OwnerId {
+    Id(u32),
+    Name(String),
+}
+
+impl OwnerId {
+    /// Accepts numeric ids, and names valid for `adduser` (`root` is the same as 0)
+    pub(crate) fn parse(name: &str) -> Option<Self> {
+        if let Ok(id) = name.parse() {
+            return Some(Self::Id(id));
+        }
+        if name == "root" {
+            return Some(Self::Id(0));
+        }
+        let valid = !name.is_empty() && name.len() <= 32 && !name.starts_with(['-', '+', '~'])
+            && name.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.'));
+        valid.then(|| Self::Name(name.into()))
+    }
+
+    pub(crate) fn id(&self) -> u32 {
+        match self {
+            Self::Id(id) => *id,
+            // dpkg uses the name if it exists when unpacking, and root otherwise
+            Self::Name(_) => 0,
+        }
+    }
+
+    pub(crate) fn name(&self) -> Option<&str> {
+        match self {
+            Self::Id(_) => None,
+            Self::Name(name) => Some(name),
+        }
+    }
+}
+
+impl fmt::Display for OwnerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Id(0) => f.write_str("root"),
+            Self::Id(id) => write!(f, "{id}"),
+            Self::Name(name) => f.write_str(name),
         }
     }
 }
 
+impl AssetOwner {
+    /// Names may not exist yet when dpkg unpacks the files (e.g. a system user created in `postinst`),
+    /// so they also need a `chown` in `postinst`
+    pub(crate) fn has_names(&self) -> bool {
+        self.user.as_ref().and_then(OwnerId::name).is_some() || self.group.as_ref().and_then(OwnerId::name).is_some()
+    }
+}
+
+/// `user:group`, as accepted by `chown`
+impl fmt::Display for AssetOwner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let root = OwnerId::Id(0);
+        write!(f, "{}:{}", self.user.as_ref().unwrap_or(&root), self.group.as_ref().unwrap_or(&root))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AssetCommon {
     pub target_path: PathBuf,
     pub chmod: Option<u32>,
+    pub owner: AssetOwner,
     pub(crate) asset_kind: AssetKind,
     is_built: IsBuilt,
 }
@@ -422,7 +558,7 @@ impl<'a> AssetFmt<'a> {
                     cwd,
                 }
             },
-            UnresolvedAsset::Symlink { .. } => {
+            UnresolvedAsset::Symlink { .. } | UnresolvedAsset::Dir { .. } => {
                 unreachable!()
             },
         }
@@ -449,6 +585,47 @@ impl fmt::Display for AssetFmt<'_> {
     }
 }
 
+/// Source, destination, mode, owner, and how the asset has been processed, in aligned columns
+pub(crate) fn asset_table(assets: &[Asset], cwd: &Path) -> String {
+    let rows = assets.iter().map(|asset| {
+        let source = match &asset.source {
+            AssetSource::Symlink(SymlinkKind::Created { link_name, .. }) => format!("-> {}", link_name.display()),
+            AssetSource::Hardlink { link_to, .. } => format!("=> /{}", link_to.display()),
+            AssetSource::Data(_) if asset.processed_from.as_ref().is_none_or(|p| p.original_path.is_none()) => "-".into(),
+            source => {
+                let path = asset.processed_from.as_ref().and_then(|p| p.original_path.as_deref())
+                    .or(source.source_path()).unwrap_or(Path::new("-"));
+                path.strip_prefix(cwd).unwrap_or(path).display().to_string()
+            },
+        };
+        let mode = asset.archive_mode();
+        let action = match (asset.processed_from.as_ref(), asset.c.is_built()) {
+            (Some(p), true) => format!("{}; built", p.action),
+            (Some(p), false) => p.action.into(),
+            (None, true) => "built".into(),
+            (None, false) => "-".into(),
+        };
+        let dest = format!("/{}{}", asset.c.target_path.display(), if asset.source.is_dir() { "/" } else { "" });
+        [source, dest, format!("{mode:04o}"), asset.c.owner.to_string(), action]
+    }).collect::<Vec<_>>();
+
+    let header = ["SOURCE", "DESTINATION", "MODE", "OWNER", "ACTION"].map(String::from);
+    let mut widths = [0; 5];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (w, col) in widths.iter_mut().zip(row) {
+            *w = (*w).max(col.chars().count());
+        }
+    }
+    let mut out = String::new();
+    for row in std::iter::once(&header).chain(&rows) {
+        let [source, dest, mode, owner, action] = row;
+        let line = format!("{source:<w0$}  {dest:<w1$}  {mode:<w2$}  {owner:<w3$}  {action}", w0 = widths[0], w1 = widths[1], w2 = widths[2], w3 = widths[3]);
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
 #[derive(Debug, Clone)]
 pub struct Asset {
     pub source: AssetSource,
@@ -485,10 +662,16 @@ impl Asset {
         Self {
             source,
             processed_from: None,
-            c: AssetCommon { target_path, chmod, asset_kind, is_built },
+            c: AssetCommon { target_path, chmod, owner: AssetOwner::default(), asset_kind, is_built },
         }
     }
 
+    #[must_use]
+    pub fn with_owner(mut self, owner: AssetOwner) -> Self {
+        self.c.owner = owner;
+        self
+    }
+
     #[must_use]
     pub fn processed(mut self, action: &'static str, original_path: impl Into<Option<PathBuf>>) -> Self {
         debug_assert!(self.processed_from.is_none());
@@ -499,6 +682,15 @@ impl Asset {
         self
     }
 
+    /// Permissions in the tarball, with defaults for files and dirs without a mode
+    pub(crate) fn archive_mode(&self) -> u32 {
+        match &self.source {
+            AssetSource::Symlink(_) => 0o777,
+            AssetSource::Dir => self.c.chmod.unwrap_or(0o755),
+            _ => self.c.chmod.unwrap_or(0o644),
+        }
+    }
+
     pub(crate) fn is_binary_executable(&self) -> bool {
         self.c.is_executable()
             && self.c.target_path.extension().is_none_or(|ext| ext != "sh")
@@ -576,7 +768,7 @@ pub fn compressed_assets(package_deb: &PackageConfig, listener: &dyn Listener) -
 
     package_deb.assets.resolved.iter().enumerate()
         .filter(|(_, asset)| {
-            asset.c.target_path.starts_with("usr") && !asset.c.is_built() && needs_compression(&asset.c.target_path.to_string_lossy())
+            asset.c.target_path.starts_with("usr") && !asset.c.is_built() && !asset.source.is_dir() && needs_compression(&asset.c.target_path.to_string_lossy())
         })
         .par_bridge()
         .map(|(idx, orig_asset)| {
@@ -592,7 +784,7 @@ pub fn compressed_assets(package_deb: &PackageConfig, listener: &dyn Listener) -
                 orig_asset.c.chmod,
                 IsBuilt::No,
                 AssetKind::Any,
-            ).processed("compressed",
+            ).with_owner(orig_asset.c.owner.clone()).processed("compressed",
                 orig_asset.source.source_path().unwrap_or(&orig_asset.c.target_path).to_path_buf()
             )))
         }).collect()
@@ -604,6 +796,58 @@ pub fn apply_compressed_assets(package_deb: &mut PackageConfig, new_assets: Vec<
     }
 }
 
+/// Files with the same contents, mode, and owner as an earlier asset are archived as hardlinks to it,
+/// so their data is stored and installed only once. Must be called after the assets are sorted.
+pub fn link_duplicate_assets(package_deb: &mut PackageConfig, listener: &dyn Listener) -> CDResult<()> {
+    link_duplicates(&mut package_deb.assets.resolved, &package_deb.conf_files, listener)
+}
+
+fn link_duplicates(assets: &mut [Asset], conf_files: &[String], listener: &dyn Listener) -> CDResult<()> {
+    // Only files of the same size need to be read to compare them
+    let mut same_size = HashMap::<_, Vec<usize>>::new();
+    for (idx, asset) in assets.iter().enumerate() {
+        if !matches!(asset.source, AssetSource::Path(_) | AssetSource::Data(_)) {
+            continue;
+        }
+        // conffiles are edited by admins, and dpkg tracks them separately
+        if conf_files.iter().any(|c| Path::new(c.trim_start_matches('/')) == asset.c.target_path) {
+            continue;
+        }
+        let Some(size) = asset.source.file_size().filter(|&s| s > 0) else { continue };
+        same_size.entry((size, asset.archive_mode(), asset.c.owner.clone())).or_default().push(idx);
+    }
+    let mut groups = same_size.into_values().filter(|g| g.len() > 1).collect::<Vec<_>>();
+    if groups.is_empty() {
+        return Ok(());
+    }
+    groups.sort_unstable();
+
+    let digests = groups.iter().flatten().copied().collect::<Vec<_>>().into_par_iter()
+        .map(|idx| {
+            let (_, mut reader) = assets[idx].source.reader()?;
+            let mut hasher = Sha256::new();
+            std::io::copy(&mut reader, &mut hasher)
+                .map_err(|e| CargoDebError::Io(e).context("error while looking for duplicate assets"))?;
+            Ok((idx, <[u8; 32]>::from(hasher.finalize())))
+        })
+        .collect::<CDResult<HashMap<_, _>>>()?;
+
+    for group in groups {
+        let mut first_with_digest = HashMap::new();
+        for idx in group {
+            let original = *first_with_digest.entry(digests[&idx]).or_insert(idx);
+            if original == idx {
+                continue;
+            }
+            let link_to = assets[original].c.target_path.clone();
+            listener.info(format!("{} is the same as {}, and will be a hardlink to it", assets[idx].c.target_path.display(), link_to.display()));
+            let source = std::mem::replace(&mut assets[idx].source, AssetSource::Dir);
+            assets[idx].source = AssetSource::Hardlink { link_to, source: Box::new(source) };
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -647,6 +891,7 @@ mod tests {
             c: AssetCommon {
                 target_path: PathBuf::from("usr/share/test/"),
                 chmod: None, // no permissions specified
+                owner: AssetOwner::default(),
                 asset_kind: AssetKind::Any,
                 is_built: IsBuilt::No,
             },
@@ -677,6 +922,7 @@ mod tests {
             c: AssetCommon {
                 target_path: PathBuf::from("usr/share/test/"),
                 chmod: Some(0o755), // explicit permissions
+                owner: AssetOwner::default(),
                 asset_kind: AssetKind::Any,
                 is_built: IsBuilt::No,
             },
@@ -695,6 +941,7 @@ mod tests {
             c: AssetCommon {
                 target_path: PathBuf::from("usr/lib/systemd/system/multi-user.target.wants/"),
                 chmod: None, 
+                owner: AssetOwner::default(),
                 asset_kind: AssetKind::Any,
                 is_built: IsBuilt::No,
             },
@@ -723,6 +970,7 @@ mod tests {
                 c: AssetCommon {
                     target_path: PathBuf::from("bar/"),
                     chmod: Some(0o644),
+                    owner: AssetOwner::default(),
                     asset_kind: AssetKind::Any,
                     is_built: IsBuilt::SamePackage,
                 },
@@ -747,6 +995,62 @@ mod tests {
         assert_eq!(debug_filename(path), Path::new("/my/test/file.debug"));
     }
 
+    #[test]
+    fn duplicates_become_hardlinks() {
+        let data = |d: &[u8], path: &str, mode| Asset::new(AssetSource::Data(d.to_vec()), path.into(), Some(mode), IsBuilt::No, AssetKind::Any);
+        let mut assets = [
+            data(b"same", "usr/bin/a", 0o755),
+            data(b"diff", "usr/bin/b", 0o755),
+            data(b"same", "usr/libexec/a", 0o755),
+            data(b"same", "usr/share/a", 0o644),
+            data(b"same", "etc/a", 0o755),
+            data(b"", "usr/bin/empty", 0o755),
+            data(b"", "usr/bin/empty2", 0o755),
+            data(b"same", "usr/lib/a", 0o755).with_owner(AssetOwner { user: OwnerId::parse("1000"), group: None }),
+        ];
+        link_duplicates(&mut assets, &["/etc/a".into()], &crate::listener::NoOpListener).unwrap();
+        let links = assets.iter().map(|a| match &a.source {
+            AssetSource::Hardlink { link_to, .. } => Some(link_to.to_str().unwrap()),
+            _ => None,
+        }).collect::<Vec<_>>();
+        assert_eq!(links, [None, None, Some("usr/bin/a"), None, None, None, None, None]);
+        assert_eq!(&*assets[2].source.data().unwrap(), b"same");
+    }
+
+    #[test]
+    fn asset_table_columns() {
+        let cwd = Path::new("/project");
+        let assets = [
+            Asset::new(AssetSource::Path("/project/target/release/bar".into()), "usr/bin/".into(), Some(0o755), IsBuilt::SamePackage, AssetKind::Any),
+            Asset::new(AssetSource::Data(b"x".to_vec()), "usr/share/doc/bar/changelog.gz".into(), None, IsBuilt::No, AssetKind::Any)
+                .with_owner(AssetOwner { user: OwnerId::parse("www-data"), group: OwnerId::parse("33") })
+                .processed("compressed", PathBuf::from("/project/CHANGELOG")),
+            Asset::new(AssetSource::Symlink(SymlinkKind::Created { target_path: "usr/bin/baz".into(), link_name: "bar".into() }), "usr/bin/baz".into(), None, IsBuilt::No, AssetKind::Any),
+        ];
+        assert_eq!(asset_table(&assets, cwd), "\
+SOURCE              DESTINATION                      MODE  OWNER        ACTION
+target/release/bar  /usr/bin/bar                     0755  root:root    built
+CHANGELOG           /usr/share/doc/bar/changelog.gz  0644  www-data:33  compressed
+-> bar              /usr/bin/baz                     0777  root:root    -
+");
+    }
+
+    #[test]
+    fn owner_ids() {
+        assert_eq!(OwnerId::parse("0"), Some(OwnerId::Id(0)));
+        assert_eq!(OwnerId::parse("root"), Some(OwnerId::Id(0)));
+        assert_eq!(OwnerId::parse("1000"), Some(OwnerId::Id(1000)));
+        assert_eq!(OwnerId::parse("_apt"), Some(OwnerId::Name("_apt".into())));
+        assert_eq!(OwnerId::parse("www-data"), Some(OwnerId::Name("www-data".into())));
+        for bad in ["", "-rf", "a:b", "a b", "a/b", "x'y"] {
+            assert_eq!(OwnerId::parse(bad), None, "{bad}");
+        }
+        let owner = AssetOwner { user: OwnerId::parse("www-data"), group: None };
+        assert!(owner.has_names());
+        assert_eq!(owner.to_string(), "www-data:root");
+        assert!(!AssetOwner { user: OwnerId::parse("33"), group: OwnerId::parse("33") }.has_names());
+    }
+
     /// Tests that getting the debug target for an Asset that `is_built` returns
     /// the path "/usr/lib/debug/<path-to-target>.debug"
     #[test]
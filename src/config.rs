@@ -1,17 +1,20 @@
-use crate::assets::{Asset, AssetFmt, AssetKind, AssetSource, Assets, IsBuilt, RawAsset, RawAssetOrAuto, UnresolvedAsset};
+use crate::assets::{Asset, AssetFmt, AssetKind, AssetOwner, AssetSource, Assets, IsBuilt, OwnerId, RawAsset, RawAssetOrAuto, UnresolvedAsset};
 use crate::assets::is_dynamic_library_filename;
-use crate::util::compress::gzipped;
+use crate::util::compress::{gzipped, parse_byte_size, CompressionOptions, Format};
+use crate::deb822::{encode_value, ControlParagraph};
+use crate::relation::{parse_depends, DependsEntry, RelationList, Rules};
 use crate::dependencies::resolve_with_dpkg;
 use crate::dh::dh_installsystemd;
 use crate::error::{CDResult, CargoDebError};
 use crate::listener::Listener;
 use crate::parse::cargo::CargoConfig;
-use crate::parse::manifest::{cargo_metadata, debug_flags, find_profile, manifest_version_string};
-use crate::parse::manifest::{CargoDeb, CargoDebAssetArrayOrTable, CargoMetadataTarget, CargoPackageMetadata, ManifestFound};
-use crate::parse::manifest::{DependencyList, SystemUnitsSingleOrMultiple, SystemdUnitsConfig, LicenseFile, ManifestDebugFlags};
+use crate::parse::manifest::{cargo_metadata, debug_flags, find_profile, linked_crates, manifest_version_string};
+use crate::parse::manifest::{CargoDeb, CargoDebAssetArrayOrTable, CargoDebOwner, CargoMetadataTarget, CargoPackageMetadata, ManifestFound};
+use crate::parse::manifest::{ByteSize, CompressionSettings, DependencyList, SystemUnitsSingleOrMultiple, SystemdUnitsConfig, LicenseFile, ManifestDebugFlags};
 use crate::util::wordsplit::WordSplit;
 use crate::{debian_architecture_from_rust_triple, debian_triple_from_rust_triple, CargoLockingFlags, OutputPath, DEFAULT_TARGET};
 use itertools::Itertools;
+use md5::{Digest, Md5};
 use rayon::prelude::*;
 use std::borrow::Cow;
 use std::collections::{BTreeSet, HashMap, HashSet};
@@ -40,47 +43,22 @@ impl From<&SystemdUnitsConfig> for dh_installsystemd::Options {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-enum ArchSpec {
-    /// e.g. [armhf]
-    Require(String),
-    /// e.g. [!armhf]
-    NegRequire(String),
-}
-
-fn get_architecture_specification(depend: &str) -> CDResult<(String, Option<ArchSpec>)> {
-    use ArchSpec::{NegRequire, Require};
-    let re = regex::Regex::new(r"(.*)\[(!?)(.*)\]").map_err(|_| CargoDebError::Str("internal"))?;
-    match re.captures(depend) {
-        Some(caps) => {
-            let spec = if &caps[2] == "!" {
-                NegRequire(caps[3].to_string())
-            } else {
-                assert_eq!(&caps[2], "");
-                Require(caps[3].to_string())
-            };
-            Ok((caps[1].trim().to_string(), Some(spec)))
-        },
-        None => Ok((depend.to_string(), None)),
-    }
-}
-
 /// Architecture specification strings
 /// <https://www.debian.org/doc/debian-policy/ch-customized-programs.html#s-arch-spec>
-fn match_architecture(spec: ArchSpec, target_arch: &str) -> CDResult<bool> {
-    let (neg, spec) = match spec {
-        ArchSpec::NegRequire(pkg) => (true, pkg),
-        ArchSpec::Require(pkg) => (false, pkg),
-    };
+///
+/// Only wildcards like `linux-any` need `dpkg-architecture`
+fn match_architecture(spec: &str, target_arch: &str) -> CDResult<bool> {
+    if !spec.split('-').any(|part| part == "any") {
+        return Ok(spec == target_arch);
+    }
+    if spec == "any" {
+        return Ok(target_arch != "all");
+    }
     let output = Command::new("dpkg-architecture")
-        .args(["-a", target_arch, "-i", &spec])
+        .args(["-a", target_arch, "-i", spec])
         .output()
         .map_err(|e| CargoDebError::CommandFailed(e, "dpkg-architecture".into()))?;
-    if neg {
-        Ok(!output.status.success())
-    } else {
-        Ok(output.status.success())
-    }
+    Ok(output.status.success())
 }
 
 #[derive(Debug)]
@@ -186,6 +164,31 @@ pub struct PackageConfig {
     ///
     /// See [PackageTransition](https://wiki.debian.org/PackageTransition).
     pub provides: Option<String>,
+    /// `Built-Using` Debian control field, for code from other Debian source packages, e.g. `gcc-12 (= 12.2.0-14)`
+    pub built_using: Option<String>,
+
+    /// `Essential: yes`, the package can't be removed
+    pub essential: bool,
+    /// `Protected: yes`, the package can't be removed without `--allow-remove-protected`
+    pub protected: bool,
+    /// `Important: yes`, the package isn't removed without asking
+    pub important: bool,
+    /// `Bugs` URL of the bug tracker
+    pub bugs: Option<String>,
+    /// `Origin` name of the distributor
+    pub origin: Option<String>,
+    /// `Tag` list of [debtags](https://wiki.debian.org/Debtags)
+    pub tag: Option<String>,
+    /// `Vcs-Git`, defaults to `repository` on known git hosts
+    pub vcs_git: Option<String>,
+    /// `Vcs-Browser`, defaults to `repository`
+    pub vcs_browser: Option<String>,
+    /// Add `Description-md5` used to look up translated descriptions
+    pub description_md5: bool,
+    /// Add `X-Cargo-Built-Using` with crates linked into the binaries
+    pub cargo_built_using: bool,
+    /// `X-Cargo-Built-Using` value, set after the build
+    pub resolved_cargo_built_using: Option<String>,
 
     /// The Debian architecture of the target system.
     pub architecture: String,
@@ -207,14 +210,43 @@ pub struct PackageConfig {
     pub maintainer_scripts_rel_path: Option<PathBuf>,
     /// Should symlinks be preserved in the assets
     pub preserve_symlinks: bool,
+    /// Add `sha256sums` next to `md5sums` in the control archive
+    pub sha256sums: bool,
+    /// Order and format archive entries the same way as `dpkg-deb --build`
+    pub dpkg_deb_compat: bool,
+    /// Make a `.udeb` for debian-installer, without docs
+    pub udeb: bool,
     /// Details of how to install any systemd units
     pub(crate) systemd_units: Option<Vec<SystemdUnitsConfig>>,
+    /// Compression format from `Cargo.toml`, can be overridden by `CompressConfig`
+    pub compress_type: Option<Format>,
+    /// Compression format of `control.tar` from `Cargo.toml`
+    pub control_compress_type: Option<Format>,
+    /// Compression tuning from `Cargo.toml`
+    pub compress_options: CompressionOptions,
+    /// Overrides for RPM packages
+    pub rpm: RpmConfig,
+    /// Custom fields appended to the control file, with continuation lines already indented
+    pub extra_fields: Vec<(String, String)>,
     /// unix timestamp for generated files
     pub default_timestamp: u64,
     /// Save it under a different path
     pub is_split_dbgsym_package: bool,
 }
 
+/// `[package.metadata.deb.rpm]`. Unset fields are taken from the Debian metadata.
+#[derive(Debug, Clone, Default)]
+pub struct RpmConfig {
+    /// Defaults to the Debian revision
+    pub release: Option<String>,
+    pub requires: Option<String>,
+    pub provides: Option<String>,
+    pub conflicts: Option<String>,
+    pub obsoletes: Option<String>,
+    pub recommends: Option<String>,
+    pub suggests: Option<String>,
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum DebugSymbols {
     /// No change (also used if Cargo already stripped the symbols
@@ -248,6 +280,10 @@ pub struct DebConfigOverrides {
     pub features: Vec<String>,
     pub no_default_features: bool,
     pub all_features: bool,
+    pub dpkg_deb_compat: bool,
+    pub udeb: bool,
+    /// `Name=value` from the command line. An empty value removes the field.
+    pub extra_fields: Vec<(String, String)>,
     pub(crate) systemd_units: Option<Vec<SystemdUnitsConfig>>,
     pub(crate) maintainer_scripts_rel_path: Option<PathBuf>,
 }
@@ -574,8 +610,11 @@ impl BuildEnvironment {
             }
         }
 
-        self.add_copyright_asset(package_deb, listener)?;
-        self.add_changelog_asset(package_deb)?;
+        // udebs are stripped down for debian-installer, and must not have any docs
+        if !package_deb.udeb {
+            self.add_copyright_asset(package_deb, listener)?;
+            self.add_changelog_asset(package_deb)?;
+        }
         self.add_systemd_assets(package_deb, listener)?;
 
         self.reset_deb_temp_directory(package_deb)
@@ -623,6 +662,48 @@ impl BuildEnvironment {
         Ok(())
     }
 
+    fn set_cargo_feature_flags(&self, cmd: &mut Command) {
+        if self.all_features {
+            cmd.arg("--all-features");
+        } else if !self.default_features {
+            cmd.arg("--no-default-features");
+        }
+        if !self.features.is_empty() {
+            cmd.arg("--features").arg(self.features.join(","));
+        }
+    }
+
+    /// Crates from `Cargo.lock` linked into the package for its target and features, and the rustc version,
+    /// sorted like `rustc (= 1.80.0), serde (= 1.0.200)`
+    pub(crate) fn cargo_built_using(&self, package_deb: &PackageConfig) -> CDResult<String> {
+        let mut cmd = Command::new("cargo");
+        cmd.current_dir(&self.cargo_run_current_dir);
+        cmd.args(["metadata", "--format-version=1", "--filter-platform"]);
+        cmd.arg(package_deb.rust_target_triple.as_deref().unwrap_or(DEFAULT_TARGET));
+        cmd.arg("--manifest-path").arg(self.manifest_path());
+        cmd.args(self.cargo_locking_flags.flags());
+        self.set_cargo_feature_flags(&mut cmd);
+        let output = cmd.output().map_err(|e| CargoDebError::CommandFailed(e, "cargo".into()))?;
+        if !output.status.success() {
+            return Err(CargoDebError::CommandError("cargo", "metadata".to_owned(), output.stderr));
+        }
+        let mut crates = linked_crates(&output.stdout)?;
+
+        let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+        let output = Command::new(&rustc).current_dir(&self.cargo_run_current_dir).arg("-vV").output()
+            .map_err(|e| CargoDebError::CommandFailed(e, "rustc".into()))?;
+        if !output.status.success() {
+            return Err(CargoDebError::CommandError("rustc", "-vV".to_owned(), output.stderr));
+        }
+        let rustc_version = String::from_utf8_lossy(&output.stdout).lines()
+            .find_map(|line| line.strip_prefix("release: "))
+            .ok_or(CargoDebError::Str("can't find the rustc version"))?
+            .trim().to_owned();
+        crates.insert(("rustc".into(), rustc_version));
+
+        Ok(crates.into_iter().map(|(name, version)| format!("{name} (= {version})")).join(", "))
+    }
+
     pub fn set_cargo_build_flags_for_packages(&self, package_debs: &[PackageConfig], cmd: &mut Command) {
         let manifest_path = self.manifest_path();
         debug_assert!(manifest_path.exists());
@@ -657,14 +738,7 @@ impl BuildEnvironment {
             }
         }
 
-        if self.all_features {
-            cmd.arg("--all-features");
-        } else if !self.default_features {
-            cmd.arg("--no-default-features");
-        }
-        if !self.features.is_empty() {
-            cmd.arg("--features").arg(self.features.join(","));
-        }
+        self.set_cargo_feature_flags(cmd);
 
         cmd.args(&self.cargo_build_flags);
         let flags_already_build_a_workspace = self.cargo_build_flags.iter().any(|f| f == "--workspace" || f == "--all");
@@ -984,6 +1058,8 @@ impl PackageConfig {
         if let Err(why) = check_debian_version(&deb_version) {
             return Err(CargoDebError::InvalidVersion(why, deb_version));
         }
+        let (compress_type, control_compress_type, compress_options) = parse_compression(deb.compression.as_ref())?;
+        let (default_vcs_git, default_vcs_browser) = cargo_package.repository().map(vcs_from_repository).unwrap_or_default();
         Ok(Self {
             deb_version,
             default_timestamp,
@@ -1019,16 +1095,34 @@ impl PackageConfig {
                 ExtendedDescription::None
             },
             readme_rel_path: cargo_package.readme().as_path().map(|p| p.to_path_buf()),
-            wildcard_depends: deb.depends.as_ref().map_or_else(|| "$auto".to_owned(), DependencyList::to_depends_string),
+            wildcard_depends: deb.depends.as_ref().map(|d| relations("Depends", d, Rules { auto: true, ..Rules::default() })).transpose()?.unwrap_or_else(|| "$auto".to_owned()),
             resolved_depends: None,
-            pre_depends: deb.pre_depends.as_ref().map(DependencyList::to_depends_string),
-            recommends: deb.recommends.as_ref().map(DependencyList::to_depends_string),
-            suggests: deb.suggests.as_ref().map(DependencyList::to_depends_string),
-            enhances: deb.enhances.as_ref().map(DependencyList::to_depends_string),
-            conflicts: deb.conflicts.as_ref().map(DependencyList::to_depends_string),
-            breaks: deb.breaks.as_ref().map(DependencyList::to_depends_string),
-            replaces: deb.replaces.as_ref().map(DependencyList::to_depends_string),
-            provides: deb.provides.as_ref().map(DependencyList::to_depends_string),
+            pre_depends: deb.pre_depends.as_ref().map(|d| relations("Pre-Depends", d, Rules::default())).transpose()?,
+            recommends: deb.recommends.as_ref().map(|d| relations("Recommends", d, Rules::default())).transpose()?,
+            suggests: deb.suggests.as_ref().map(|d| relations("Suggests", d, Rules::default())).transpose()?,
+            enhances: deb.enhances.as_ref().map(|d| relations("Enhances", d, Rules::default())).transpose()?,
+            conflicts: deb.conflicts.as_ref().map(|d| relations("Conflicts", d, Rules { no_alternatives: true, ..Rules::default() })).transpose()?,
+            breaks: deb.breaks.as_ref().map(|d| relations("Breaks", d, Rules { no_alternatives: true, ..Rules::default() })).transpose()?,
+            replaces: deb.replaces.as_ref().map(|d| relations("Replaces", d, Rules { no_alternatives: true, ..Rules::default() })).transpose()?,
+            provides: deb.provides.as_ref().map(|d| relations("Provides", d, Rules { no_alternatives: true, exact_versions: true, ..Rules::default() })).transpose()?,
+            built_using: deb.built_using.as_ref().map(|b| built_using(&b.to_depends_string())).transpose()?,
+            essential: deb.essential.unwrap_or(false),
+            protected: deb.protected.unwrap_or(false),
+            important: deb.important.unwrap_or(false),
+            bugs: deb.bugs.as_deref().map(|url| control_url("Bugs", url)).transpose()?,
+            origin: deb.origin.as_deref().map(|origin| single_line("Origin", origin)).transpose()?,
+            tag: deb.tag.as_ref().map(|tag| single_line("Tag", &tag.to_depends_string())).transpose()?,
+            vcs_git: match deb.vcs_git.as_deref() {
+                Some(vcs) => (!vcs.trim().is_empty()).then(|| vcs_git(vcs)).transpose()?,
+                None => default_vcs_git,
+            },
+            vcs_browser: match deb.vcs_browser.as_deref() {
+                Some(url) => (!url.trim().is_empty()).then(|| control_url("Vcs-Browser", url)).transpose()?,
+                None => default_vcs_browser,
+            },
+            description_md5: deb.description_md5.unwrap_or(false),
+            cargo_built_using: deb.cargo_built_using.unwrap_or(false),
+            resolved_cargo_built_using: None,
             section: overrides.section.as_deref().or(deb.section.as_deref()).map(From::from),
             priority: deb.priority.as_deref().unwrap_or("optional").into(),
             architecture: architecture.to_owned(),
@@ -1040,12 +1134,28 @@ impl PackageConfig {
             maintainer_scripts_rel_path: overrides.maintainer_scripts_rel_path.clone()
                 .or_else(|| deb.maintainer_scripts.as_deref().map(PathBuf::from)),
             preserve_symlinks: deb.preserve_symlinks.unwrap_or(false),
+            sha256sums: deb.sha256sums.unwrap_or(false),
+            dpkg_deb_compat: overrides.dpkg_deb_compat || deb.dpkg_deb_compat.unwrap_or(false),
+            udeb: overrides.udeb || deb.udeb.unwrap_or(false),
             systemd_units: overrides.systemd_units.clone().or_else(|| match &deb.systemd_units {
                 None => None,
                 Some(SystemUnitsSingleOrMultiple::Single(s)) => Some(vec![s.clone()]),
                 Some(SystemUnitsSingleOrMultiple::Multi(v)) => Some(v.clone()),
             }),
             multiarch,
+            compress_type,
+            control_compress_type,
+            compress_options,
+            rpm: deb.rpm.as_ref().map(|rpm| RpmConfig {
+                release: rpm.release.clone(),
+                requires: rpm.requires.as_ref().map(DependencyList::to_depends_string),
+                provides: rpm.provides.as_ref().map(DependencyList::to_depends_string),
+                conflicts: rpm.conflicts.as_ref().map(DependencyList::to_depends_string),
+                obsoletes: rpm.obsoletes.as_ref().map(DependencyList::to_depends_string),
+                recommends: rpm.recommends.as_ref().map(DependencyList::to_depends_string),
+                suggests: rpm.suggests.as_ref().map(DependencyList::to_depends_string),
+            }).unwrap_or_default(),
+            extra_fields: extra_control_fields(deb.extra_fields.as_ref(), &overrides.extra_fields)?,
             is_split_dbgsym_package: false,
         })
     }
@@ -1098,9 +1208,22 @@ impl PackageConfig {
         }
 
         self.add_conf_files();
+        if self.udeb {
+            self.warn_about_udeb_assets(listener);
+        }
         Ok(())
     }
 
+    /// debian-installer doesn't want any documentation
+    fn warn_about_udeb_assets(&self, listener: &dyn Listener) {
+        for asset in &self.assets.resolved {
+            let path = &asset.c.target_path;
+            if ["usr/share/doc", "usr/share/man", "usr/share/info", "usr/share/lintian"].iter().any(|dir| path.starts_with(dir)) {
+                listener.warning(format!("udeb packages must not contain documentation, but there's an asset for /{}", path.display()));
+            }
+        }
+    }
+
     /// Debian defaults all /etc files to be conf files
     /// <https://www.debian.org/doc/manuals/maint-guide/dother.en.html#conffiles>
     fn add_conf_files(&mut self) {
@@ -1140,41 +1263,47 @@ impl PackageConfig {
             })
             .collect();
 
+        // like dpkg-gencontrol, relations with <profile> restrictions are for the profiles being built
+        let active_profiles = std::env::var("DEB_BUILD_PROFILES").unwrap_or_default();
+        let active_profiles = active_profiles.split_whitespace().collect::<Vec<_>>();
+        let mut arch_matches = |spec: &str| {
+            Ok(match_architecture(spec, &self.architecture).unwrap_or_else(|e| {
+                listener.warning(format!("Can't match the architecture '{spec}', assuming it matches\n{e}"));
+                true
+            }))
+        };
+
         let mut deps = BTreeSet::new();
         let mut used_auto_deps = false;
-        for word in self.wildcard_depends.split(',') {
-            let word = word.trim();
-            if word == "$auto" {
-                used_auto_deps = true;
-                let bin = self.all_binaries();
-                let resolved = bin.par_iter()
-                    .filter(|bin| !bin.source.archive_as_symlink_only())
-                    .filter_map(|&bin| {
-                        let bname = bin.source.source_path()?;
-                        match resolve_with_dpkg(bname, &self.architecture, &lib_search_paths) {
-                            Ok(bindeps) => {
-                                log::debug!("$auto depends for '{}': {bindeps:?}", bin.c.target_path.display());
-                                Some(bindeps)
-                            },
-                            Err(err) => {
-                                listener.warning(format!("{err}\nNo $auto deps for {}", bname.display()));
-                                None
-                            },
-                        }
-                    })
-                    .collect_vec_list();
-                deps.extend(resolved.into_iter().flatten().flatten());
-            } else {
-                let (dep, arch_spec) = get_architecture_specification(word)?;
-                if let Some(spec) = arch_spec {
-                    let matches = match_architecture(spec, &self.architecture)
-                        .inspect_err(|e| listener.warning(format!("Can't get arch spec for '{word}'\n{e}")));
-                    if matches.unwrap_or(true) {
-                        deps.insert(dep);
+        for entry in parse_depends("Depends", &self.wildcard_depends, Rules { auto: true, ..Rules::default() })? {
+            match entry {
+                DependsEntry::Auto => {
+                    used_auto_deps = true;
+                    let bin = self.all_binaries();
+                    let resolved = bin.par_iter()
+                        .filter(|bin| !bin.source.archive_as_symlink_only())
+                        .filter_map(|&bin| {
+                            let bname = bin.source.source_path()?;
+                            match resolve_with_dpkg(bname, &self.architecture, &lib_search_paths) {
+                                Ok(bindeps) => {
+                                    log::debug!("$auto depends for '{}': {bindeps:?}", bin.c.target_path.display());
+                                    Some(bindeps)
+                                },
+                                Err(err) => {
+                                    listener.warning(format!("{err}\nNo $auto deps for {}", bname.display()));
+                                    None
+                                },
+                            }
+                        })
+                        .collect_vec_list();
+                    deps.extend(resolved.into_iter().flatten().flatten());
+                },
+                DependsEntry::Relations(alternatives) => {
+                    let filtered = RelationList(vec![alternatives]).for_architecture(&mut arch_matches, &active_profiles)?;
+                    if !filtered.is_empty() {
+                        deps.insert(filtered.to_string());
                     }
-                } else {
-                    deps.insert(dep);
-                }
+                },
             }
         }
 
@@ -1207,6 +1336,11 @@ impl PackageConfig {
 
     /// similar files next to each other improve tarball compression
     pub fn sort_assets_by_type(&mut self) {
+        if self.dpkg_deb_compat {
+            // dpkg-deb walks the tree with entries of each directory sorted by name
+            self.assets.resolved.sort_by(|a, b| a.c.target_path.cmp(&b.c.target_path));
+            return;
+        }
         self.assets.resolved.sort_by(|a,b| {
             a.c.is_executable().cmp(&b.c.is_executable())
             .then(a.c.is_dynamic_library().cmp(&b.c.is_dynamic_library()))
@@ -1216,7 +1350,7 @@ impl PackageConfig {
         });
     }
 
-    fn extended_description(&self, config: &BuildEnvironment) -> CDResult<Option<Cow<'_, str>>> {
+    pub(crate) fn extended_description(&self, config: &BuildEnvironment) -> CDResult<Option<Cow<'_, str>>> {
         let path = match &self.extended_description {
             ExtendedDescription::None => return Ok(None),
             ExtendedDescription::String(s) => return Ok(Some(s.as_str().into())),
@@ -1230,35 +1364,65 @@ impl PackageConfig {
 
     /// Generates the control file that obtains all the important information about the package.
     pub fn generate_control(&self, config: &BuildEnvironment) -> CDResult<String> {
-        use fmt::Write;
+        let mut control = self.control_paragraph(config)?.to_string();
+        control.push('\n');
+        Ok(control)
+    }
 
-        // Create and return the handle to the control file with write access.
-        let mut control = String::with_capacity(1024);
+    /// Fields of the control file, in order. They can be modified before writing
+    /// the control file with `ControlArchiveBuilder::generate_archive_with_control`.
+    pub fn control_paragraph(&self, config: &BuildEnvironment) -> CDResult<ControlParagraph> {
+        let mut control = ControlParagraph::new();
 
-        // Write all of the lines required by the control file.
-        writeln!(control, "Package: {}", self.deb_name)?;
-        writeln!(control, "Version: {}", self.deb_version)?;
-        writeln!(control, "Architecture: {}", self.architecture)?;
+        control.set("Package", &self.deb_name)?;
+        control.set("Version", &self.deb_version)?;
+        control.set("Architecture", &self.architecture)?;
+        if self.udeb {
+            control.set("Package-Type", "udeb")?;
+        }
+        if self.essential {
+            control.set("Essential", "yes")?;
+        }
+        if self.protected {
+            control.set("Protected", "yes")?;
+        }
+        if self.important {
+            control.set("Important", "yes")?;
+        }
         let ma = match self.multiarch {
             Multiarch::None => "",
             Multiarch::Same => "same",
             Multiarch::Foreign => "foreign",
         };
         if !ma.is_empty() {
-            writeln!(control, "Multi-Arch: {ma}")?;
+            control.set("Multi-Arch", ma)?;
         }
         if self.is_split_dbgsym_package {
-            writeln!(control, "Auto-Built-Package: debug-symbols")?;
+            control.set("Auto-Built-Package", "debug-symbols")?;
         }
         if let Some(homepage) = self.homepage.as_deref().or(self.documentation.as_deref()).or(self.repository.as_deref()) {
-            writeln!(control, "Homepage: {homepage}")?;
+            control.set("Homepage", homepage)?;
         }
-        if let Some(ref section) = self.section {
-            writeln!(control, "Section: {section}")?;
+        if let Some(vcs_browser) = &self.vcs_browser {
+            control.set("Vcs-Browser", vcs_browser)?;
         }
-        writeln!(control, "Priority: {}", self.priority)?;
+        if let Some(vcs_git) = &self.vcs_git {
+            control.set("Vcs-Git", vcs_git)?;
+        }
+        if self.udeb {
+            control.set("Section", "debian-installer")?;
+        } else if let Some(ref section) = self.section {
+            control.set("Section", section)?;
+        }
+        control.set("Priority", &self.priority)?;
         if let Some(maintainer) = self.maintainer.as_deref() {
-            writeln!(control, "Maintainer: {maintainer}")?;
+            control.set("Maintainer", maintainer)?;
+        }
+        if let Some(origin) = &self.origin {
+            control.set("Origin", origin)?;
+        }
+        if let Some(bugs) = &self.bugs {
+            control.set("Bugs", bugs)?;
         }
 
         let installed_size = self.assets.resolved
@@ -1266,68 +1430,67 @@ impl PackageConfig {
             .map(|m| (m.source.file_size().unwrap_or(0) + 2047) / 1024) // assume 1KB of fs overhead per file
             .sum::<u64>();
 
-        writeln!(control, "Installed-Size: {installed_size}")?;
+        control.set("Installed-Size", &installed_size.to_string())?;
 
         if let Some(deps) = &self.resolved_depends {
-            writeln!(control, "Depends: {deps}")?;
-        }
-
-        if let Some(ref pre_depends) = self.pre_depends {
-            let pre_depends_normalized = pre_depends.trim();
-
-            if !pre_depends_normalized.is_empty() {
-                writeln!(control, "Pre-Depends: {pre_depends_normalized}")?;
-            }
-        }
-
-        if let Some(ref recommends) = self.recommends {
-            let recommends_normalized = recommends.trim();
-
-            if !recommends_normalized.is_empty() {
-                writeln!(control, "Recommends: {recommends_normalized}")?;
-            }
-        }
-
-        if let Some(ref suggests) = self.suggests {
-            let suggests_normalized = suggests.trim();
-
-            if !suggests_normalized.is_empty() {
-                writeln!(control, "Suggests: {suggests_normalized}")?;
-            }
-        }
-
-        if let Some(ref enhances) = self.enhances {
-            let enhances_normalized = enhances.trim();
-
-            if !enhances_normalized.is_empty() {
-                writeln!(control, "Enhances: {enhances_normalized}")?;
+            control.set("Depends", deps)?;
+        }
+
+        let relations = [
+            ("Pre-Depends", &self.pre_depends),
+            ("Recommends", &self.recommends),
+            ("Suggests", &self.suggests),
+            ("Enhances", &self.enhances),
+        ];
+        for (name, relation) in relations {
+            if let Some(relation) = relation.as_deref().map(str::trim).filter(|r| !r.is_empty()) {
+                control.set(name, relation)?;
             }
         }
 
         if let Some(ref conflicts) = self.conflicts {
-            writeln!(control, "Conflicts: {conflicts}")?;
+            control.set("Conflicts", conflicts)?;
         }
         if let Some(ref breaks) = self.breaks {
-            writeln!(control, "Breaks: {breaks}")?;
+            control.set("Breaks", breaks)?;
         }
         if let Some(ref replaces) = self.replaces {
-            writeln!(control, "Replaces: {replaces}")?;
+            control.set("Replaces", replaces)?;
         }
         if let Some(ref provides) = self.provides {
-            writeln!(control, "Provides: {provides}")?;
+            control.set("Provides", provides)?;
         }
-
-        write!(&mut control, "Description:")?;
-        for line in self.description.split_by_chars(79) {
-            writeln!(control, " {line}")?;
+        if let Some(ref built_using) = self.built_using {
+            control.set("Built-Using", built_using)?;
+        }
+        if let Some(cargo_built_using) = &self.resolved_cargo_built_using {
+            control.set("X-Cargo-Built-Using", cargo_built_using)?;
         }
 
+        let mut description = self.description.split_by_chars(79);
+        if description.is_empty() {
+            description.push(String::new());
+        }
         if let Some(desc) = self.extended_description(config)? {
-            for line in desc.split_by_chars(79) {
-                writeln!(control, " {line}")?;
+            description.extend(desc.split_by_chars(79));
+        }
+        control.set_lines("Description", &description)?;
+        if self.description_md5 {
+            // same as apt: the whole field value, with indented continuation lines and a final newline
+            let md5 = Md5::digest(format!("{}\n", control.get("Description").unwrap_or_default()));
+            let hex: String = md5.iter().map(|byte| format!("{byte:02x}")).collect();
+            control.set("Description-md5", &hex)?;
+        }
+        if let Some(tag) = &self.tag {
+            control.set("Tag", tag)?;
+        }
+
+        for (name, value) in &self.extra_fields {
+            if control.contains(name) {
+                return Err(CargoDebError::InvalidControlField(name.clone(), "cargo-deb already sets this field"));
             }
+            control.set(name, value)?;
         }
-        control.push('\n');
 
         Ok(control)
     }
@@ -1372,7 +1535,7 @@ impl PackageConfig {
                 self.deb_name,
                 self.deb_version,
                 self.architecture,
-                if self.is_split_dbgsym_package { "ddeb" } else { "deb" }
+                if self.is_split_dbgsym_package { "ddeb" } else if self.udeb { "udeb" } else { "deb" }
             ))
         } else if self.is_split_dbgsym_package {
             path.path.with_extension("ddeb")
@@ -1424,6 +1587,18 @@ impl PackageConfig {
             breaks: None,
             replaces: None,
             provides: None,
+            built_using: None,
+            essential: false,
+            protected: false,
+            important: false,
+            bugs: self.bugs.clone(),
+            origin: self.origin.clone(),
+            tag: None,
+            vcs_git: None,
+            vcs_browser: None,
+            description_md5: false,
+            cargo_built_using: false,
+            resolved_cargo_built_using: None,
             architecture: self.architecture.clone(),
             rust_target_triple: self.rust_target_triple.clone(),
             multiarch: if self.multiarch == Multiarch::Same { Multiarch::Same } else { Multiarch::None },
@@ -1433,13 +1608,152 @@ impl PackageConfig {
             triggers_file_rel_path: None,
             maintainer_scripts_rel_path: None,
             preserve_symlinks: self.preserve_symlinks,
+            sha256sums: self.sha256sums,
+            dpkg_deb_compat: self.dpkg_deb_compat,
+            udeb: false,
             systemd_units: None,
+            compress_type: self.compress_type,
+            control_compress_type: self.control_compress_type,
+            compress_options: self.compress_options,
+            rpm: RpmConfig::default(),
+            extra_fields: self.extra_fields.clone(),
             default_timestamp: self.default_timestamp,
             is_split_dbgsym_package: true,
         })
     }
 }
 
+/// `scheme://…` without whitespace
+fn control_url(field: &str, url: &str) -> CDResult<String> {
+    let url = url.trim();
+    let is_valid = url.split_once("://").is_some_and(|(scheme, rest)| {
+        scheme.starts_with(|c: char| c.is_ascii_alphabetic()) &&
+        scheme.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'-' | b'.')) &&
+        !rest.is_empty()
+    }) && !url.bytes().any(|b| b.is_ascii_whitespace() || b.is_ascii_control());
+    if !is_valid {
+        return Err(CargoDebError::InvalidControlField(field.into(), "must be a URL like https://example.com/"));
+    }
+    Ok(url.into())
+}
+
+/// `url [-b branch] [[path]]`
+fn vcs_git(value: &str) -> CDResult<String> {
+    let mut words = value.split_whitespace();
+    control_url("Vcs-Git", words.next().unwrap_or_default())?;
+    while let Some(word) = words.next() {
+        let is_valid = match word {
+            "-b" => words.next().is_some(),
+            path => path.len() > 2 && path.starts_with('[') && path.ends_with(']'),
+        };
+        if !is_valid {
+            return Err(CargoDebError::InvalidControlField("Vcs-Git".into(), "must be a URL, optionally followed by -b branch and [path]"));
+        }
+    }
+    Ok(value.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+fn single_line(field: &str, value: &str) -> CDResult<String> {
+    let value = value.trim();
+    if value.is_empty() || value.contains(['\n', '\r']) {
+        return Err(CargoDebError::InvalidControlField(field.into(), "must be a single line of text"));
+    }
+    Ok(value.into())
+}
+
+/// Every package needs an exact version of its source, like `gcc-12 (= 12.2.0-14)`
+fn built_using(value: &str) -> CDResult<String> {
+    RelationList::parse_with("Built-Using", value, Rules { no_alternatives: true, exact_versions: true, version_required: true, ..Rules::default() })?;
+    single_line("Built-Using", value)
+}
+
+/// Checks the syntax, so that typos fail here rather than in dpkg or apt
+fn relations(field: &str, list: &DependencyList, rules: Rules) -> CDResult<String> {
+    let value = list.to_depends_string();
+    parse_depends(field, &value, rules)?;
+    Ok(value)
+}
+
+/// `Vcs-Git` (only on hosts known to use git) and `Vcs-Browser` from Cargo's `repository` URL
+fn vcs_from_repository(repository: &str) -> (Option<String>, Option<String>) {
+    let repository = repository.trim().trim_end_matches('/');
+    let Some(rest) = repository.strip_prefix("https://").or_else(|| repository.strip_prefix("http://")) else {
+        return (None, None);
+    };
+    if repository.contains(char::is_whitespace) {
+        return (None, None);
+    }
+    let browser = repository.strip_suffix(".git").unwrap_or(repository);
+    let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+    let git = if repository.ends_with(".git") {
+        Some(repository.to_owned())
+    } else {
+        let repo_path = match host {
+            // links to subdirectories are like owner/repo/tree/main/path
+            "github.com" | "codeberg.org" | "bitbucket.org" => {
+                let mut parts = path.splitn(3, '/');
+                parts.next().zip(parts.next()).map(|(owner, repo)| format!("{owner}/{repo}"))
+            },
+            // GitLab has nested groups, and subdirectories after /-/
+            "gitlab.com" | "salsa.debian.org" => Some(path.split("/-/").next().unwrap_or(path).to_owned()),
+            _ => None,
+        };
+        repo_path.filter(|p| p.contains('/')).map(|p| format!("https://{host}/{p}.git"))
+    };
+    (git, Some(browser.to_owned()))
+}
+
+/// Merges the command-line overrides into fields from `Cargo.toml`, and checks that they're valid deb822
+fn extra_control_fields(manifest: Option<&toml::Table>, overrides: &[(String, String)]) -> CDResult<Vec<(String, String)>> {
+    let manifest = manifest.into_iter().flatten().map(|(name, value)| match value {
+        toml::Value::String(s) => Ok((name.as_str(), Cow::Borrowed(s.as_str()))),
+        toml::Value::Integer(_) | toml::Value::Float(_) | toml::Value::Boolean(_) => Ok((name.as_str(), Cow::Owned(value.to_string()))),
+        _ => Err(CargoDebError::InvalidControlField(name.clone(), "the value must be a string")),
+    });
+    let overrides = overrides.iter().map(|(name, value)| Ok((name.as_str(), Cow::Borrowed(value.as_str()))));
+
+    let mut fields = Vec::<(String, String)>::new();
+    for field in manifest.chain(overrides) {
+        let (name, value) = field?;
+        let existing = fields.iter().position(|(n, _)| n.eq_ignore_ascii_case(name));
+        // an empty value in a variant or on the command line removes the field
+        if value.trim().is_empty() {
+            if let Some(pos) = existing {
+                fields.remove(pos);
+            }
+            continue;
+        }
+        let value = encode_value(name, &value)?;
+        match existing {
+            Some(pos) => fields[pos].1 = value,
+            None => fields.push((name.to_owned(), value)),
+        }
+    }
+    Ok(fields)
+}
+
+fn parse_compression(compression: Option<&CompressionSettings>) -> CDResult<(Option<Format>, Option<Format>, CompressionOptions)> {
+    let Some(compression) = compression else {
+        return Ok((None, None, CompressionOptions::default()));
+    };
+    let parse_format = |name: &str| {
+        Format::from_name(name).ok_or_else(|| CargoDebError::InvalidCompression(format!("unknown format '{name}', expected xz, gz, zstd, or none")))
+    };
+    let compress_type = compression.format.as_deref().map(parse_format).transpose()?;
+    let control_compress_type = compression.control_format.as_deref().map(parse_format).transpose()?;
+    let memlimit = compression.memlimit.as_ref().map(|memlimit| match memlimit {
+        ByteSize::Bytes(bytes) => Ok(*bytes),
+        ByteSize::String(size) => parse_byte_size(size)
+            .ok_or_else(|| CargoDebError::InvalidCompression(format!("memlimit '{size}' is not a size like 512MiB"))),
+    }).transpose()?;
+    Ok((compress_type, control_compress_type, CompressionOptions {
+        level: compression.level,
+        extreme: compression.extreme,
+        threads: compression.threads,
+        memlimit,
+    }))
+}
+
 fn license_doesnt_need_author_info(license_identifier: &str) -> bool {
     ["UNLICENSED", "PROPRIETARY", "CC-PDDC", "CC0-1.0"].iter()
         .any(|l| l.eq_ignore_ascii_case(license_identifier))
@@ -1454,16 +1768,34 @@ impl TryFrom<CargoDebAssetArrayOrTable> for RawAssetOrAuto {
         fn parse_chmod(mode: &str) -> Result<u32, String> {
             u32::from_str_radix(mode, 8).map_err(|e| format!("Unable to parse mode argument (third array element) as an octal number in an asset: {e}"))
         }
+        fn parse_owner(owner: Option<CargoDebOwner>) -> Result<Option<OwnerId>, String> {
+            owner.map(|owner| match owner {
+                CargoDebOwner::Id(id) => Ok(OwnerId::Id(id)),
+                CargoDebOwner::Name(name) => OwnerId::parse(&name)
+                    .ok_or_else(|| format!("'{name}' is not a valid user or group name for an asset. Use a name like `www-data` or a numeric id")),
+            }).transpose()
+        }
         let raw_asset = match toml {
             CargoDebAssetArrayOrTable::Table(a) => Self::RawAsset(RawAsset::Asset {
                 source_path: a.source.into(),
                 target_path: a.dest.into(),
                 chmod: a.mode.as_deref().map(parse_chmod).transpose()?,
                 preserve_symlinks: a.preserve_symlinks,
+                owner: AssetOwner { user: parse_owner(a.user)?, group: parse_owner(a.group)? },
             }),
             CargoDebAssetArrayOrTable::Symlink(a) => {
                 RawAssetOrAuto::RawAsset(RawAsset::Symlink { target_path: a.dest.into(), link_name: a.link_name.into() })
             }
+            CargoDebAssetArrayOrTable::Dir(a) => {
+                if !a.dir {
+                    return Err(format!("{EXPECTED}, but found a table without a `source` for '{}'", a.dest));
+                }
+                RawAssetOrAuto::RawAsset(RawAsset::Dir {
+                    target_path: a.dest.into(),
+                    chmod: a.mode.as_deref().map(parse_chmod).transpose()?,
+                    owner: AssetOwner { user: parse_owner(a.user)?, group: parse_owner(a.group)? },
+                })
+            }
             CargoDebAssetArrayOrTable::Array(a) => {
                 if a.len() < 2 || a.len() > 3 {
                     return Err(format!("{EXPECTED}, but found an array with {} elements", a.len()));
@@ -1474,7 +1806,7 @@ impl TryFrom<CargoDebAssetArrayOrTable> for RawAssetOrAuto {
                     target_path: PathBuf::from(a.next().ok_or("missing dest path (second array entry) for asset in Cargo.toml. Use something like \"usr/local/bin/\".")?),
                     chmod: a.next().map(|s| parse_chmod(&s)).transpose()?,
                     preserve_symlinks: None,
-                    
+                    owner: AssetOwner::default(),
                 })
             },
             CargoDebAssetArrayOrTable::Auto(s) if s == "$auto" => Self::Auto,
@@ -1560,7 +1892,7 @@ impl BuildEnvironment {
                 RawAssetOrAuto::RawAsset(asset) => Some(asset),
             }
         }).map(|asset| {
-            let (RawAsset::Symlink { target_path, .. } | RawAsset::Asset { target_path, .. }) = asset;
+            let (RawAsset::Symlink { target_path, .. } | RawAsset::Asset { target_path, .. } | RawAsset::Dir { target_path, .. }) = asset;
             
             let mut target_path = target_path.to_owned();
             if package_deb.multiarch != Multiarch::None {
@@ -1568,7 +1900,7 @@ impl BuildEnvironment {
             }
 
             match asset {
-                RawAsset::Asset { source_path, target_path:_, chmod, preserve_symlinks } => {
+                RawAsset::Asset { source_path, target_path:_, chmod, preserve_symlinks, owner } => {
                     // target/release is treated as a magic prefix that resolves to any profile
                     let target_artifact_rel_path = source_path.strip_prefix("target/release").ok()
                         .or_else(|| source_path.strip_prefix(custom_profile_target_dir.as_deref()?).ok());
@@ -1582,11 +1914,14 @@ impl BuildEnvironment {
                         (IsBuilt::No, self.path_in_cargo_crate(source_path), false)
                     };
 
-                    UnresolvedAsset::new_asset(source_path, target_path, *chmod, is_built, if is_example { AssetKind::CargoExampleBinary } else { AssetKind::Any }, preserve_symlinks.unwrap_or(package_deb.preserve_symlinks))
+                    UnresolvedAsset::new_asset(source_path, target_path, *chmod, owner.clone(), is_built, if is_example { AssetKind::CargoExampleBinary } else { AssetKind::Any }, preserve_symlinks.unwrap_or(package_deb.preserve_symlinks))
                 },
                 RawAsset::Symlink { target_path:_, link_name } => {
                     UnresolvedAsset::new_symlink(target_path, link_name.to_owned())
                 },
+                RawAsset::Dir { target_path:_, chmod, owner } => {
+                    UnresolvedAsset::new_dir(target_path, *chmod, owner.clone())
+                },
             }
         }).collect::<Vec<_>>();
         let resolved = if has_auto { self.implicit_assets(package_deb)? } else { vec![] };
@@ -1623,7 +1958,7 @@ impl BuildEnvironment {
         if implied_assets.is_empty() {
             return Err(CargoDebError::BinariesNotFound(package_deb.cargo_crate_name.clone()));
         }
-        if let Some(readme_rel_path) = package_deb.readme_rel_path.as_deref() {
+        if let Some(readme_rel_path) = package_deb.readme_rel_path.as_deref().filter(|_| !package_deb.udeb) {
             let path = self.path_in_cargo_crate(readme_rel_path);
             let target_path = Path::new("usr/share/doc")
                 .join(&package_deb.deb_name)
@@ -1685,7 +2020,7 @@ fn format_conffiles<S: AsRef<str>>(files: &[S]) -> String {
     })
 }
 
-fn check_debian_version(mut ver: &str) -> Result<(), &'static str> {
+pub(crate) fn check_debian_version(mut ver: &str) -> Result<(), &'static str> {
     if ver.trim_start().is_empty() {
         return Err("empty string");
     }
@@ -1718,17 +2053,22 @@ mod tests {
 
     #[test]
     fn arch_spec() {
-        use ArchSpec::*;
-        // req
-        assert_eq!(
-            get_architecture_specification("libjpeg64-turbo [armhf]").expect("arch"),
-            ("libjpeg64-turbo".to_owned(), Some(Require("armhf".to_owned())))
-        );
-        // neg
-        assert_eq!(
-            get_architecture_specification("libjpeg64-turbo [!amd64]").expect("arch"),
-            ("libjpeg64-turbo".to_owned(), Some(NegRequire("amd64".to_owned())))
-        );
+        assert!(match_architecture("armhf", "armhf").unwrap());
+        assert!(!match_architecture("amd64", "armhf").unwrap());
+        assert!(match_architecture("any", "armhf").unwrap());
+        let depends = RelationList::parse("Depends", "libjpeg64-turbo [armhf], libfoo [!amd64]").unwrap();
+        let for_arch = |arch| depends.for_architecture(|spec| match_architecture(spec, arch), &[]).unwrap().to_string();
+        assert_eq!(for_arch("armhf"), "libjpeg64-turbo, libfoo");
+        assert_eq!(for_arch("amd64"), "");
+    }
+
+    #[test]
+    fn relation_fields() {
+        let list = |s: &str| DependencyList::String(s.into());
+        assert_eq!(relations("Depends", &list("$auto, libc6 (>= 2.36)"), Rules { auto: true, ..Rules::default() }).unwrap(), "$auto, libc6 (>= 2.36)");
+        assert!(relations("Recommends", &list("$auto"), Rules::default()).is_err());
+        assert!(relations("Depends", &list("libssl3 (>= 3.0"), Rules { auto: true, ..Rules::default() }).is_err());
+        assert!(relations("Conflicts", &list("a1 | b1"), Rules { no_alternatives: true, ..Rules::default() }).is_err());
     }
 
     #[test]
@@ -1749,4 +2089,53 @@ mod tests {
 
         assert_eq!("/etc/my-pkg/conf.toml\n/etc/my-pkg/conf2.toml\n", actual);
     }
+
+    #[test]
+    fn extra_fields() {
+        let manifest: toml::Table = toml::from_str(r#"
+            X-Zeta = "z"
+            Origin = "Example"
+            X-Multi = "one\ntwo\n  .\nthree"
+            X-Build = 42
+        "#).unwrap();
+        let overrides = [("x-zeta".to_owned(), String::new()), ("X-Git-Commit".to_owned(), "abc".to_owned()), ("origin".to_owned(), "Other".to_owned())];
+        let fields = extra_control_fields(Some(&manifest), &overrides).unwrap();
+        assert_eq!(fields, [
+            ("Origin".to_owned(), "Other".to_owned()),
+            ("X-Multi".to_owned(), "one\n two\n  .\n three".to_owned()),
+            ("X-Build".to_owned(), "42".to_owned()),
+            ("X-Git-Commit".to_owned(), "abc".to_owned()),
+        ]);
+
+        assert!(encode_value("X-Ok", "a").is_ok());
+        assert!(encode_value("Bad:Name", "a").is_err());
+        assert!(encode_value("#Comment", "a").is_err());
+        assert!(encode_value("X Space", "a").is_err());
+        assert!(encode_value("X-Blank", "a\n\nb").is_err());
+    }
+
+    #[test]
+    fn standard_fields() {
+        assert_eq!(vcs_from_repository("https://github.com/kornelski/cargo-deb/tree/main/src/"), (
+            Some("https://github.com/kornelski/cargo-deb.git".into()),
+            Some("https://github.com/kornelski/cargo-deb/tree/main/src".into()),
+        ));
+        assert_eq!(vcs_from_repository("https://gitlab.com/group/sub/project/-/tree/main"), (
+            Some("https://gitlab.com/group/sub/project.git".into()),
+            Some("https://gitlab.com/group/sub/project/-/tree/main".into()),
+        ));
+        assert_eq!(vcs_from_repository("https://git.example.com/repo.git"), (Some("https://git.example.com/repo.git".into()), Some("https://git.example.com/repo".into())));
+        assert_eq!(vcs_from_repository("https://hg.example.com/repo"), (None, Some("https://hg.example.com/repo".into())));
+        assert_eq!(vcs_from_repository("git@github.com:foo/bar.git"), (None, None));
+
+        assert_eq!(control_url("Bugs", " debbugs://bugs.debian.org ").unwrap(), "debbugs://bugs.debian.org");
+        assert!(control_url("Bugs", "bugs.debian.org").is_err());
+        assert!(control_url("Bugs", "https://example.com/a b").is_err());
+        assert_eq!(vcs_git("https://example.com/r.git  -b main [sub/dir]").unwrap(), "https://example.com/r.git -b main [sub/dir]");
+        assert!(vcs_git("https://example.com/r.git main").is_err());
+        assert!(built_using("gcc-12 (= 12.2.0-14), rustc (= 1.80.0+dfsg1-1)").is_ok());
+        assert!(built_using("gcc-12 (>= 12)").is_err());
+        assert!(built_using("gcc-12").is_err());
+        assert!(single_line("Origin", "a\nb").is_err());
+    }
 }
@@ -3,7 +3,7 @@ use crate::{CDResult, CargoDebError};
 use ar::{Builder, Header};
 use std::fs;
 use std::fs::File;
-use std::io::BufWriter;
+use std::io::{BufWriter, Read};
 use std::path::PathBuf;
 
 /// The outermost `ar` archive that contains tarballs inside
@@ -25,20 +25,21 @@ impl DebArchive {
             ar_builder,
             mtime_timestamp,
         };
-        ar.add_file("debian-binary".into(), b"2.0\n")?;
+        ar.add_file("debian-binary".into(), 4, &b"2.0\n"[..])?;
         Ok(ar)
     }
 
     pub fn add_control(&mut self, control_tarball: Compressed) -> CDResult<()> {
-        self.add_file(format!("control.tar.{}", control_tarball.extension()), &control_tarball)
+        self.add_file(tar_member_name("control", &control_tarball), control_tarball.len(), control_tarball)
     }
 
     pub fn add_data(&mut self, data_tarball: Compressed) -> CDResult<()> {
-        self.add_file(format!("data.tar.{}", data_tarball.extension()), &data_tarball)
+        self.add_file(tar_member_name("data", &data_tarball), data_tarball.len(), data_tarball)
     }
 
-    fn add_file(&mut self, dest_path: String, data: &[u8]) -> CDResult<()> {
-        let mut header = Header::new(dest_path.into(), data.len() as u64);
+    /// Copies `size` bytes from `data` without loading it all into memory
+    fn add_file(&mut self, dest_path: String, size: u64, data: impl Read) -> CDResult<()> {
+        let mut header = Header::new(dest_path.into(), size);
         header.set_mode(0o100644); // dpkg uses 100644
         header.set_mtime(self.mtime_timestamp);
         header.set_uid(0);
@@ -51,3 +52,11 @@ impl DebArchive {
         Ok(self.out_abspath)
     }
 }
+
+/// Uncompressed tarballs are just `data.tar`
+fn tar_member_name(base: &str, tarball: &Compressed) -> String {
+    match tarball.extension() {
+        "" => format!("{base}.tar"),
+        ext => format!("{base}.tar.{ext}"),
+    }
+}
@@ -1,16 +1,21 @@
+use crate::assets::Asset;
 use crate::config::{BuildEnvironment, PackageConfig};
+use crate::deb::sums::FileSums;
 use crate::deb::tar::Tarball;
 use crate::dh::{dh_installsystemd, dh_lib};
 use crate::error::{CDResult, CargoDebError};
 use crate::listener::Listener;
 use crate::util::{is_path_file, read_file_to_string};
 use dh_lib::ScriptFragments;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 pub struct ControlArchiveBuilder<'l, W: Write> {
     archive: Tarball<W>,
+    /// dpkg-deb sorts the control files by name, so they're written all at once in `finish`
+    sorted_files: Option<BTreeMap<PathBuf, (Vec<u8>, u32)>>,
     listener: &'l dyn Listener,
 }
 
@@ -18,13 +23,38 @@ impl<'l, W: Write> ControlArchiveBuilder<'l, W> {
     pub fn new(dest: W, time: u64, listener: &'l dyn Listener) -> Self {
         Self {
             archive: Tarball::new(dest, time),
+            sorted_files: None,
             listener,
         }
     }
 
-    /// Generates an uncompressed tar archive with `control`, and others
-    pub fn generate_archive(&mut self, config: &BuildEnvironment, package_deb: &PackageConfig) -> CDResult<()> {
-        self.add_control(package_deb.generate_control(config)?.as_bytes())?;
+    /// Makes the same `control.tar` as `dpkg-deb --build --root-owner-group` would
+    #[must_use]
+    pub fn dpkg_deb_compatible(mut self, enabled: bool) -> Self {
+        self.archive = self.archive.dpkg_deb_compatible(enabled);
+        self.sorted_files = enabled.then(BTreeMap::new);
+        self
+    }
+
+    /// Generates an uncompressed tar archive with `control`, and others.
+    /// `sums` are of the files in the data archive.
+    pub fn generate_archive(&mut self, config: &BuildEnvironment, package_deb: &PackageConfig, sums: &FileSums) -> CDResult<()> {
+        self.generate_archive_with_control(package_deb.generate_control(config)?.as_bytes(), config, package_deb, sums)
+    }
+
+    /// Same as `generate_archive`, but with the given contents of the `control` file
+    pub fn generate_archive_with_control(&mut self, control: &[u8], config: &BuildEnvironment, package_deb: &PackageConfig, sums: &FileSums) -> CDResult<()> {
+        self.add_control(control)?;
+
+        // like dh_md5sums, skipped for udebs, because debian-installer has no use for them
+        if !package_deb.udeb {
+            if let Some(md5sums) = sums.md5sums(&package_deb.conf_files) {
+                self.add_file_with_log("md5sums".as_ref(), md5sums.as_bytes(), 0o644, None)?;
+            }
+            if let Some(sha256sums) = sums.sha256sums(&package_deb.conf_files) {
+                self.add_file_with_log("sha256sums".as_ref(), sha256sums.as_bytes(), 0o644, None)?;
+            }
+        }
 
         if let Some(files) = package_deb.conf_files() {
             self.add_conf_files(&files)?;
@@ -37,7 +67,10 @@ impl<'l, W: Write> ControlArchiveBuilder<'l, W> {
         Ok(())
     }
 
-    pub fn finish(self) -> CDResult<W> {
+    pub fn finish(mut self) -> CDResult<W> {
+        for (name, (contents, permissions)) in self.sorted_files.take().unwrap_or_default() {
+            self.archive.file(name, &contents, permissions)?;
+        }
         self.archive.into_inner().map_err(|e| CargoDebError::Io(e).context("error while finalizing control archive"))
     }
 
@@ -48,34 +81,38 @@ impl<'l, W: Write> ControlArchiveBuilder<'l, W> {
     /// Additionally, when `systemd_units` is configured, shell script fragments
     /// "for enabling, disabling, starting, stopping and restarting systemd unit
     /// files" (quoting `man 1 dh_installsystemd`) will replace the `#DEBHELPER#`
-    /// token in the provided maintainer scripts.
+    /// token in the provided maintainer scripts. Assets with a `user` or `group` name
+    /// get a `chown` fragment in `postinst` the same way.
     ///
     /// If a shell fragment cannot be inserted because the target script is missing
     /// then the entire script will be generated and appended to the archive.
     ///
     /// # Requirements
     ///
-    /// When `systemd_units` is configured, user supplied `maintainer_scripts` must
+    /// When `systemd_units` or asset owners are configured, user supplied `maintainer_scripts` must
     /// contain a `#DEBHELPER#` token at the point where shell script fragments
     /// should be inserted.
     fn generate_scripts(&mut self, config: &BuildEnvironment, package_deb: &PackageConfig) -> CDResult<()> {
-        let Some(maintainer_scripts_dir) = &package_deb.maintainer_scripts_rel_path else {
-            return Ok(());
-        };
-
-        let maintainer_scripts_dir = config.path_in_cargo_crate(maintainer_scripts_dir);
+        let maintainer_scripts_dir = package_deb.maintainer_scripts_rel_path.as_ref()
+            .map(|dir| config.path_in_cargo_crate(dir));
+        let ownership_scripts = ownership_fragments(&package_deb.deb_name, &package_deb.assets.resolved, self.listener)?;
         let mut scripts = ScriptFragments::new();
 
-        if let Some(systemd_units_config_vec) = &package_deb.systemd_units {
+        if let (Some(maintainer_scripts_dir), Some(systemd_units_config_vec)) = (&maintainer_scripts_dir, &package_deb.systemd_units) {
             for systemd_units_config in systemd_units_config_vec {
                 // Select and populate autoscript templates relevant to the unit
                 // file(s) in this package and the configuration settings chosen.
-                scripts = dh_installsystemd::generate(
+                scripts = ownership_scripts.clone();
+                let unit_scripts = dh_installsystemd::generate(
                     &package_deb.deb_name,
                     &package_deb.assets.resolved,
                     &dh_installsystemd::Options::from(systemd_units_config),
                     self.listener,
                 )?;
+                // files need their owners before the services start
+                for (name, text) in unit_scripts {
+                    scripts.entry(name).or_default().push_str(&text);
+                }
 
                 // Get Option<&str> from Option<String>
                 let unit_name = systemd_units_config.unit_name.as_deref();
@@ -83,33 +120,39 @@ impl<'l, W: Write> ControlArchiveBuilder<'l, W> {
                 // Replace the #DEBHELPER# token in the users maintainer scripts
                 // and/or generate maintainer scripts from scratch as needed.
                 dh_lib::apply(
-                    &maintainer_scripts_dir,
+                    Some(maintainer_scripts_dir),
                     &mut scripts,
                     &package_deb.deb_name,
                     unit_name,
                     self.listener,
                 )?;
             }
+        } else if !ownership_scripts.is_empty() {
+            scripts = ownership_scripts;
+            dh_lib::apply(maintainer_scripts_dir.as_deref(), &mut scripts, &package_deb.deb_name, None, self.listener)?;
         }
 
+        let generated_by = Path::new(if package_deb.systemd_units.is_some() { "systemd_units" } else { "assets" });
         let mut found_any = !scripts.is_empty();
 
         // Add maintainer scripts to the archive, either those supplied by the
         // user or if available prefer modified versions generated above.
         for name in ["config", "preinst", "postinst", "prerm", "postrm", "templates"] {
-            let script_path = maintainer_scripts_dir.join(name);
-            let script_path_exists = is_path_file(&script_path);
+            let script_path = maintainer_scripts_dir.as_ref().map(|dir| dir.join(name));
+            let script_path_exists = script_path.as_deref().is_some_and(is_path_file);
             let (contents, source_path) = if let Some(script) = scripts.remove(name) {
-                if script_path_exists {
-                    log::info!("maintainer script replaced by autogenerated systemd script {}", script_path.display());
+                if let Some(script_path) = script_path.as_ref().filter(|_| script_path_exists) {
+                    log::info!("maintainer script replaced by autogenerated script {}", script_path.display());
                 }
-                (script, Some(Path::new("systemd_units")))
+                (script, Some(generated_by))
             } else {
-                if !script_path_exists {
-                    log::info!("maintainer script {} not found", script_path.display());
+                let Some(script_path) = script_path.as_ref().filter(|_| script_path_exists) else {
+                    if let Some(script_path) = &script_path {
+                        log::info!("maintainer script {} not found", script_path.display());
+                    }
                     continue;
-                }
-                let file = read_file_to_string(&script_path)
+                };
+                let file = read_file_to_string(script_path)
                     .map_err(|e| CargoDebError::IoFile("Can't read script", e, script_path.clone()))?;
                 (file, Some(script_path.as_path()))
             };
@@ -124,7 +167,7 @@ impl<'l, W: Write> ControlArchiveBuilder<'l, W> {
             self.add_file_with_log(name.as_ref(), contents.as_bytes(), permissions, source_path)?;
         }
 
-        if !found_any {
+        if let Some(maintainer_scripts_dir) = maintainer_scripts_dir.filter(|_| !found_any) {
             self.listener.warning(format!("no maintainer scripts found in {}", maintainer_scripts_dir.display()));
         }
         Ok(())
@@ -133,13 +176,22 @@ impl<'l, W: Write> ControlArchiveBuilder<'l, W> {
     fn add_file_with_log(&mut self, name: &Path, contents: &[u8], permissions: u32, source_path: Option<&Path>) -> CDResult<()> {
         let source_path = source_path.and_then(|s| s.to_str()).unwrap_or("-");
         self.listener.progress("Adding", format!("'{}' control-> {}", source_path, name.display()));
-        self.archive.file(name, contents, permissions)
+        self.append(name, contents, permissions)
     }
 
     // Add the control file to the tar archive.
     fn add_control(&mut self, control: &[u8]) -> CDResult<()> {
-        self.archive.file("control", control, 0o644)?;
-        Ok(())
+        self.append("control".as_ref(), control, 0o644)
+    }
+
+    fn append(&mut self, name: &Path, contents: &[u8], permissions: u32) -> CDResult<()> {
+        match &mut self.sorted_files {
+            Some(files) => {
+                files.insert(name.into(), (contents.into(), permissions));
+                Ok(())
+            },
+            None => self.archive.file(name, contents, permissions),
+        }
     }
 
     /// If configuration files are required, the conffiles file will be created.
@@ -157,6 +209,32 @@ impl<'l, W: Write> ControlArchiveBuilder<'l, W> {
     }
 }
 
+/// dpkg unpacks files before `postinst` gets a chance to create users,
+/// so owners given by name are set again when the package is configured.
+fn ownership_fragments(package: &str, assets: &[Asset], listener: &dyn Listener) -> CDResult<ScriptFragments> {
+    let mut by_owner = BTreeMap::<(String, u32), Vec<String>>::new();
+    for asset in assets.iter().filter(|a| a.c.owner.has_names() && !a.source.archive_as_symlink_only()) {
+        let path = format!("/{}", asset.c.target_path.display());
+        by_owner.entry((asset.c.owner.to_string(), asset.archive_mode()))
+            .or_default().push(shell_quote(&path));
+    }
+
+    let mut scripts = ScriptFragments::new();
+    for ((owner, mode), files) in by_owner {
+        let replacements = HashMap::from([
+            ("FILES", files.join(" ")),
+            ("OWNER", shell_quote(&owner)),
+            ("MODE", format!("{mode:04o}")),
+        ]);
+        dh_lib::autoscript(&mut scripts, package, "postinst", "postinst-chown", &replacements, false, listener)?;
+    }
+    Ok(scripts)
+}
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
 #[cfg(test)]
 mod tests {
     // The following test suite verifies that `fn generate_scripts()` correctly
@@ -183,12 +261,11 @@ mod tests {
     //         Cargo.toml
 
     use super::*;
-    use crate::assets::{Asset, AssetSource, IsBuilt};
+    use crate::assets::{Asset, AssetOwner, AssetSource, IsBuilt, OwnerId};
     use crate::config::DebugSymbolOptions;
     use crate::listener::MockListener;
     use crate::parse::manifest::SystemdUnitsConfig;
     use crate::util::tests::{add_test_fs_paths, set_test_fs_path_content};
-    use std::collections::HashMap;
     use std::io::prelude::Read;
     use std::path::PathBuf;
 
@@ -276,6 +353,30 @@ mod tests {
         assert!(archived_file_names.is_empty());
     }
 
+    #[test]
+    fn generate_scripts_chowns_assets_owned_by_name() {
+        let mut listener = MockListener::new();
+        let (config, mut package_deb, mut in_ar) = prepare(vec![], None, &mut listener);
+
+        let owner = AssetOwner { user: OwnerId::parse("www-data"), group: OwnerId::parse("adm") };
+        for (path, owner) in [("var/lib/foo/db", owner.clone()), ("var/lib/foo/it's", owner), ("usr/bin/foo", AssetOwner::default())] {
+            package_deb.assets.resolved.push(Asset::new(AssetSource::Data(vec![]), path.into(), Some(0o640), IsBuilt::No, crate::assets::AssetKind::Any)
+                .with_owner(owner));
+        }
+
+        in_ar.generate_scripts(&config, &package_deb).unwrap();
+        let archive_bytes = in_ar.finish().unwrap();
+        let scripts = extract_contents(&mut tar::Archive::new(&archive_bytes[..]));
+
+        assert_eq!(scripts.len(), 1);
+        let postinst = &scripts["./postinst"];
+        assert!(postinst.starts_with("#!/bin/sh\nset -e\n"), "{postinst}");
+        assert!(postinst.contains("for f in '/var/lib/foo/db' '/var/lib/foo/it'\\''s' ; do"), "{postinst}");
+        assert!(postinst.contains("chown 'www-data:adm' \"$f\""), "{postinst}");
+        assert!(postinst.contains("chmod 0640 \"$f\""), "{postinst}");
+        assert!(!postinst.contains("usr/bin/foo"));
+    }
+
     #[test]
     fn generate_scripts_archives_user_supplied_maintainer_scripts_in_root_package() {
         let maintainer_script_paths = vec![
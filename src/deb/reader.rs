@@ -0,0 +1,347 @@
+//! Reading of existing `.deb` files, e.g. to check what has been built
+
+use crate::deb822::ControlParagraph;
+use crate::error::{CDResult, CargoDebError};
+use crate::util::compress::{decompressor, Format};
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use tar::EntryType;
+
+/// A `.deb` package opened for reading.
+///
+/// The control archive is read upfront, since it's small. The data archive
+/// is decompressed again every time it's listed or extracted.
+pub struct DebReader {
+    path: PathBuf,
+    control: ControlParagraph,
+    control_files: Vec<ControlFile>,
+}
+
+/// A file from `control.tar`, like `control`, `md5sums`, or a maintainer script
+pub struct ControlFile {
+    /// File name, without the `./` prefix
+    pub name: String,
+    pub mode: u32,
+    pub data: Vec<u8>,
+}
+
+/// An entry of `data.tar`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataFile {
+    /// Path relative to the root of the filesystem, e.g. `usr/bin/foo`
+    pub path: PathBuf,
+    pub kind: DataFileKind,
+    /// Permission bits, e.g. `0o755`
+    pub mode: u32,
+    /// Size of the file contents, 0 for links and directories
+    pub size: u64,
+    pub uid: u64,
+    pub gid: u64,
+    pub user: Option<String>,
+    pub group: Option<String>,
+    pub mtime: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DataFileKind {
+    File,
+    Dir,
+    /// Target of the symlink, as written in the archive
+    Symlink(PathBuf),
+    /// Path of the file it links to, relative to the root
+    Hardlink(PathBuf),
+    Other,
+}
+
+/// Names of control files that dpkg runs
+pub const MAINTAINER_SCRIPTS: [&str; 5] = ["preinst", "postinst", "prerm", "postrm", "config"];
+
+impl DebReader {
+    pub fn open(path: impl Into<PathBuf>) -> CDResult<Self> {
+        let path = path.into();
+        let mut ar = open_ar(&path)?;
+
+        let mut control = None;
+        let mut has_data = false;
+        let mut first = true;
+        while let Some(entry) = ar.next_entry() {
+            let mut entry = entry.map_err(|e| CargoDebError::IoFile("Can't read the deb archive", e, path.clone()))?;
+            let name = member_name(&entry);
+            if first {
+                first = false;
+                let mut version = String::new();
+                let is_deb = name == "debian-binary" && entry.read_to_string(&mut version).is_ok() && version.starts_with("2.");
+                if !is_deb {
+                    return Err(invalid(&path, "it doesn't start with the debian-binary 2.x header"));
+                }
+            } else if let Some(format) = tarball_format(&name, "control.tar") {
+                let mut files = Vec::new();
+                read_control_tarball(decompressor(format, entry)?, &mut files)
+                    .map_err(|e| CargoDebError::IoFile("Can't read control.tar", e, path.clone()))?;
+                control = Some(files);
+            } else if tarball_format(&name, "data.tar").is_some() {
+                has_data = true;
+            }
+        }
+        let control_files = control.ok_or_else(|| invalid(&path, "control.tar is missing"))?;
+        if !has_data {
+            return Err(invalid(&path, "data.tar is missing"));
+        }
+        let control = control_files.iter().find(|f| f.name == "control")
+            .ok_or_else(|| invalid(&path, "control.tar has no control file"))?;
+        let control = ControlParagraph::parse(&String::from_utf8_lossy(&control.data))
+            .map_err(|e| invalid(&path, &format!("its control file can't be parsed: {e}")))?;
+
+        Ok(Self { path, control, control_files })
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Fields of the `control` file, in their original order
+    #[must_use]
+    pub fn control(&self) -> &ControlParagraph {
+        &self.control
+    }
+
+    /// Value of a control field. Field names are case-insensitive.
+    /// Values of multi-line fields keep the leading space of their continuation lines.
+    #[must_use]
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.control.get(name)
+    }
+
+    /// All files of `control.tar`
+    #[must_use]
+    pub fn control_files(&self) -> &[ControlFile] {
+        &self.control_files
+    }
+
+    #[must_use]
+    pub fn control_file(&self, name: &str) -> Option<&ControlFile> {
+        self.control_files.iter().find(|f| f.name == name)
+    }
+
+    /// `preinst`, `postinst`, `prerm`, `postrm`, and `config` scripts that are in the package
+    pub fn maintainer_scripts(&self) -> impl Iterator<Item = &ControlFile> {
+        self.control_files.iter().filter(|f| MAINTAINER_SCRIPTS.contains(&f.name.as_str()))
+    }
+
+    /// Absolute paths listed in the `conffiles` file
+    #[must_use]
+    pub fn conffiles(&self) -> Vec<&str> {
+        self.control_file("conffiles")
+            .and_then(|f| std::str::from_utf8(&f.data).ok())
+            .map(|list| list.lines().map(|l| l.trim()).filter(|l| !l.is_empty()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Lists all entries of `data.tar`, in the order they're in the archive
+    pub fn data_files(&self) -> CDResult<Vec<DataFile>> {
+        self.with_data_tarball(|tar| {
+            let mut files = Vec::new();
+            for entry in tar.entries()? {
+                let entry = entry?;
+                if let Some(file) = data_file(&entry)? {
+                    files.push(file);
+                }
+            }
+            Ok(files)
+        })
+    }
+
+    /// Unpacks `data.tar` into `dest_dir`, like `dpkg-deb --extract`.
+    /// File ownership isn't preserved.
+    pub fn extract(&self, dest_dir: &Path) -> CDResult<()> {
+        fs::create_dir_all(dest_dir)
+            .map_err(|e| CargoDebError::IoFile("Can't create the directory to extract into", e, dest_dir.into()))?;
+        self.with_data_tarball(|tar| {
+            tar.set_preserve_permissions(true);
+            tar.set_preserve_mtime(true);
+            tar.set_overwrite(true);
+            tar.unpack(dest_dir)
+        })
+    }
+
+    fn with_data_tarball<T>(&self, callback: impl FnOnce(&mut tar::Archive<Box<dyn Read + '_>>) -> io::Result<T>) -> CDResult<T> {
+        let mut ar = open_ar(&self.path)?;
+        while let Some(entry) = ar.next_entry() {
+            let entry = entry.map_err(|e| CargoDebError::IoFile("Can't read the deb archive", e, self.path.clone()))?;
+            if let Some(format) = tarball_format(&member_name(&entry), "data.tar") {
+                let mut tar = tar::Archive::new(decompressor(format, entry)?);
+                return callback(&mut tar).map_err(|e| CargoDebError::IoFile("Can't read data.tar", e, self.path.clone()));
+            }
+        }
+        Err(invalid(&self.path, "data.tar is missing"))
+    }
+}
+
+fn open_ar(path: &Path) -> CDResult<ar::Archive<BufReader<File>>> {
+    let file = File::open(path).map_err(|e| CargoDebError::IoFile("Can't open the deb file", e, path.into()))?;
+    Ok(ar::Archive::new(BufReader::new(file)))
+}
+
+fn invalid(path: &Path, reason: &str) -> CargoDebError {
+    CargoDebError::InvalidPackage(path.into(), reason.into())
+}
+
+/// GNU ar may add a `/` at the end
+fn member_name<R: Read>(entry: &ar::Entry<'_, R>) -> String {
+    String::from_utf8_lossy(entry.header().identifier()).trim_end_matches('/').to_owned()
+}
+
+/// `data.tar.xz` is `Some(Xz)`, a plain `data.tar` is `Some(None)`
+fn tarball_format(name: &str, base: &str) -> Option<Format> {
+    match name.strip_prefix(base)? {
+        "" => Some(Format::None),
+        ext => Format::from_name(ext.strip_prefix('.')?),
+    }
+}
+
+pub(crate) fn read_control_tarball(input: impl Read, files: &mut Vec<ControlFile>) -> io::Result<()> {
+    let mut tar = tar::Archive::new(input);
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        if entry.header().entry_type() != EntryType::Regular {
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().trim_start_matches("./").to_owned();
+        let mode = entry.header().mode()?;
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+        files.push(ControlFile { name, mode, data });
+    }
+    Ok(())
+}
+
+fn data_file<R: Read>(entry: &tar::Entry<'_, R>) -> io::Result<Option<DataFile>> {
+    let header = entry.header();
+    let path = entry.path()?;
+    let path = path.strip_prefix(".").unwrap_or(&path);
+    let path = path.strip_prefix("/").unwrap_or(path).to_path_buf();
+    if path.as_os_str().is_empty() {
+        // the root dir
+        return Ok(None);
+    }
+    let link_name = || entry.link_name().map(|l| l.unwrap_or_default().into_owned());
+    let kind = match header.entry_type() {
+        EntryType::Regular | EntryType::Continuous => DataFileKind::File,
+        EntryType::Directory => DataFileKind::Dir,
+        EntryType::Symlink => DataFileKind::Symlink(link_name()?),
+        EntryType::Link => {
+            let target = link_name()?;
+            let target = target.strip_prefix(".").unwrap_or(&target).to_path_buf();
+            DataFileKind::Hardlink(target.strip_prefix("/").unwrap_or(&target).to_path_buf())
+        },
+        _ => DataFileKind::Other,
+    };
+    let name = |n: Result<Option<&str>, _>| n.ok().flatten().filter(|n| !n.is_empty()).map(String::from);
+    Ok(Some(DataFile {
+        path,
+        size: if kind == DataFileKind::File { entry.size() } else { 0 },
+        kind,
+        mode: header.mode()? & 0o7777,
+        // empty fields are the same as 0 for tar and dpkg
+        uid: header.uid().unwrap_or(0),
+        gid: header.gid().unwrap_or(0),
+        user: name(header.username()),
+        group: name(header.groupname()),
+        mtime: header.mtime().unwrap_or(0),
+    }))
+}
+
+impl DataFile {
+    /// Like in `ls -l`, e.g. `-rwxr-xr-x`
+    #[must_use]
+    pub fn mode_string(&self) -> String {
+        let kind = match self.kind {
+            DataFileKind::File | DataFileKind::Other => '-',
+            DataFileKind::Hardlink(_) => 'h',
+            DataFileKind::Dir => 'd',
+            DataFileKind::Symlink(_) => 'l',
+        };
+        let mut s = String::with_capacity(10);
+        s.push(kind);
+        for shift in [6, 3, 0] {
+            let bits = self.mode >> shift;
+            s.push(if bits & 4 != 0 { 'r' } else { '-' });
+            s.push(if bits & 2 != 0 { 'w' } else { '-' });
+            s.push(if bits & 1 != 0 { 'x' } else { '-' });
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deb::ar::DebArchive;
+    use crate::deb::tar::Tarball;
+    use crate::util::compress::{select_compressor, Compressed, CompressionOptions};
+
+    fn tarball(files: impl FnOnce(&mut Tarball<crate::util::compress::Compressor>)) -> Compressed {
+        let temp_dir = std::env::temp_dir();
+        let mut tar = Tarball::new(select_compressor(true, Format::None, &CompressionOptions::default(), false, false, &temp_dir).unwrap(), 0);
+        files(&mut tar);
+        tar.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn read_deb() {
+        let dir = tempfile::tempdir().unwrap();
+        let deb_path = dir.path().join("test.deb");
+        let mut deb = DebArchive::new(deb_path.clone(), 0).unwrap();
+        deb.add_control(tarball(|tar| {
+            tar.file("control", b"Package: test\nVersion: 1.0\nDescription: short\n long\n .\n more\n", 0o644).unwrap();
+            tar.file("conffiles", b"/etc/test.conf\n", 0o644).unwrap();
+            tar.file("postinst", b"#!/bin/sh\n", 0o755).unwrap();
+        })).unwrap();
+        deb.add_data(tarball(|tar| {
+            tar.file("usr/bin/test", b"binary", 0o755).unwrap();
+            tar.symlink(Path::new("usr/bin/link"), Path::new("test")).unwrap();
+            tar.file("etc/test.conf", b"x=1\n", 0o644).unwrap();
+        })).unwrap();
+        deb.finish().unwrap();
+
+        let deb = DebReader::open(&deb_path).unwrap();
+        assert_eq!(deb.field("package"), Some("test"));
+        assert_eq!(deb.field("Description"), Some("short\n long\n .\n more"));
+        assert_eq!(deb.control().len(), 3);
+        assert_eq!(deb.conffiles(), ["/etc/test.conf"]);
+        assert_eq!(deb.maintainer_scripts().map(|s| s.name.as_str()).collect::<Vec<_>>(), ["postinst"]);
+        assert_eq!(deb.control_file("postinst").unwrap().mode, 0o755);
+
+        let files = deb.data_files().unwrap();
+        let bin = files.iter().find(|f| f.path == Path::new("usr/bin/test")).unwrap();
+        assert_eq!((bin.size, bin.mode, &bin.kind), (6, 0o755, &DataFileKind::File));
+        assert_eq!(bin.mode_string(), "-rwxr-xr-x");
+        let link = files.iter().find(|f| f.path == Path::new("usr/bin/link")).unwrap();
+        assert_eq!(link.kind, DataFileKind::Symlink("test".into()));
+        assert!(files.iter().any(|f| f.path == Path::new("usr/bin") && f.kind == DataFileKind::Dir));
+
+        let out = dir.path().join("out");
+        deb.extract(&out).unwrap();
+        assert_eq!(fs::read(out.join("usr/bin/test")).unwrap(), b"binary");
+        assert_eq!(fs::read(out.join("etc/test.conf")).unwrap(), b"x=1\n");
+    }
+
+    #[test]
+    fn rejects_non_debs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("not.deb");
+        fs::write(&path, b"!<arch>\n").unwrap();
+        assert!(matches!(DebReader::open(&path), Err(CargoDebError::InvalidPackage(..))));
+    }
+
+    #[test]
+    fn tarball_names() {
+        assert_eq!(tarball_format("data.tar.xz", "data.tar"), Some(Format::Xz));
+        assert_eq!(tarball_format("data.tar", "data.tar"), Some(Format::None));
+        assert_eq!(tarball_format("control.tar.zst", "control.tar"), Some(Format::Zstd));
+        assert_eq!(tarball_format("data.tar.bz2", "data.tar"), None);
+        assert_eq!(tarball_format("data.tarball", "data.tar"), None);
+    }
+}
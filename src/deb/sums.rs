@@ -0,0 +1,116 @@
+use md5::{Digest, Md5};
+use sha2::Sha256;
+use std::fmt::Write as _;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Checksums of regular files in `data.tar`, for the `md5sums` control file used by `dpkg --verify` and `debsums`
+#[derive(Debug, Default)]
+pub struct FileSums {
+    files: Vec<FileSum>,
+    with_sha256: bool,
+}
+
+#[derive(Debug)]
+struct FileSum {
+    path: PathBuf,
+    md5: [u8; 16],
+    sha256: Option<[u8; 32]>,
+}
+
+impl FileSums {
+    pub fn new(with_sha256: bool) -> Self {
+        Self { files: Vec::new(), with_sha256 }
+    }
+
+    /// Hashes everything read through the returned reader. Call `add` after reading it all.
+    pub fn hasher<R: Read>(&self, inner: R) -> HashingReader<R> {
+        HashingReader {
+            inner,
+            md5: Md5::new(),
+            sha256: self.with_sha256.then(Sha256::new),
+        }
+    }
+
+    /// `path` is relative to the root
+    pub fn add<R>(&mut self, path: &Path, hasher: HashingReader<R>) {
+        self.files.push(FileSum {
+            path: path.to_owned(),
+            md5: hasher.md5.finalize().into(),
+            sha256: hasher.sha256.map(|h| h.finalize().into()),
+        });
+    }
+
+    /// For hardlinks to an already added file
+    pub fn add_duplicate(&mut self, path: &Path, original: &Path) {
+        if let Some(sum) = self.files.iter().find(|f| f.path == original) {
+            let (md5, sha256) = (sum.md5, sum.sha256);
+            self.files.push(FileSum { path: path.to_owned(), md5, sha256 });
+        }
+    }
+
+    /// Conffiles are left out, because dpkg tracks their checksums itself (same as `dh_md5sums`)
+    pub fn md5sums(&self, conf_files: &[String]) -> Option<String> {
+        self.format(conf_files, |f| Some(&f.md5[..]))
+    }
+
+    /// `sha256sums`, if enabled
+    pub fn sha256sums(&self, conf_files: &[String]) -> Option<String> {
+        self.format(conf_files, |f| f.sha256.as_ref().map(|s| &s[..]))
+    }
+
+    fn format(&self, conf_files: &[String], sum: impl Fn(&FileSum) -> Option<&[u8]>) -> Option<String> {
+        let mut out = String::new();
+        for file in &self.files {
+            let Some(sum) = sum(file) else { continue };
+            let Some(path) = file.path.to_str() else { continue };
+            if conf_files.iter().any(|c| c.trim_start_matches('/') == path) {
+                continue;
+            }
+            for byte in sum {
+                let _ = write!(out, "{byte:02x}");
+            }
+            let _ = writeln!(out, "  {path}");
+        }
+        (!out.is_empty()).then_some(out)
+    }
+}
+
+pub struct HashingReader<R> {
+    inner: R,
+    md5: Md5,
+    sha256: Option<Sha256>,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.md5.update(&buf[..n]);
+        if let Some(sha256) = &mut self.sha256 {
+            sha256.update(&buf[..n]);
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sums_format() {
+        let mut sums = FileSums::new(true);
+        for (path, data) in [("usr/bin/foo", &b"foo"[..]), ("etc/foo.conf", b"")] {
+            let mut hasher = sums.hasher(data);
+            io::copy(&mut hasher, &mut io::sink()).unwrap();
+            sums.add(Path::new(path), hasher);
+        }
+        assert_eq!(sums.md5sums(&[]).unwrap(), "\
+acbd18db4cc2f85cedef654fccc4a4d8  usr/bin/foo
+d41d8cd98f00b204e9800998ecf8427e  etc/foo.conf
+");
+        assert_eq!(sums.md5sums(&["/etc/foo.conf".into()]).unwrap(), "acbd18db4cc2f85cedef654fccc4a4d8  usr/bin/foo\n");
+        assert_eq!(sums.sha256sums(&["/etc/foo.conf".into()]).unwrap(), "2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae  usr/bin/foo\n");
+        assert!(FileSums::new(false).sha256sums(&[]).is_none());
+    }
+}
@@ -1,61 +1,116 @@
-use crate::assets::{Asset, AssetSource, SymlinkKind};
+use crate::assets::{Asset, AssetOwner, AssetSource, SymlinkKind};
+use crate::deb::sums::FileSums;
 use crate::error::{CDResult, CargoDebError};
 use crate::listener::Listener;
 use crate::PackageConfig;
 use crate::util::pathbytes::AsUnixPathBytes;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::io::{Read, Write};
 use std::path::{Component, Path, PathBuf};
 use std::{fs, io};
 use tar::{EntryType, Header as TarHeader};
 
+/// The tar header has the size written upfront, so a file that changes while being
+/// archived must not make the entry shorter or longer than declared.
+struct ExactSize<R> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R: Read> Read for ExactSize<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 {
+            return Ok(0);
+        }
+        let max = buf.len().min(self.remaining.try_into().unwrap_or(usize::MAX));
+        let n = self.inner.read(&mut buf[..max])?;
+        if n == 0 && max > 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "file has been truncated while being archived"));
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+/// Keeps track of the archive length, so it can be padded to a full tar record
+struct CountingWriter<W> {
+    inner: W,
+    written: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// GNU tar's default blocking factor of 20
+const TAR_RECORD_SIZE: u64 = 20 * 512;
+
 /// Tarball for control and data files
 pub(crate) struct Tarball<W: Write> {
     added_directories: HashSet<Box<Path>>,
+    /// Mode and owner of directory assets, also used when they're implied parents of other files
+    explicit_directories: HashMap<Box<Path>, (u32, AssetOwner)>,
     time: u64,
-    tar: tar::Builder<W>,
+    /// Write headers the way GNU tar does for `dpkg-deb --build`
+    dpkg_deb_compat: bool,
+    tar: tar::Builder<CountingWriter<W>>,
 }
 
 impl<W: Write> Tarball<W> {
     pub fn new(dest: W, time: u64) -> Self {
         Self {
             added_directories: HashSet::new(),
+            explicit_directories: HashMap::new(),
             time,
-            tar: tar::Builder::new(dest),
+            dpkg_deb_compat: false,
+            tar: tar::Builder::new(CountingWriter { inner: dest, written: 0 }),
         }
     }
 
-    /// Copies all the files to be packaged into the tar archive.
-    pub fn archive_files(mut self, package_deb: &PackageConfig, rsyncable: bool, listener: &dyn Listener) -> CDResult<W> {
+    /// Adds the `./` root directory entry, uses GNU headers with `root` owner names,
+    /// and pads the archive to a full record, like `dpkg-deb --build --root-owner-group`.
+    ///
+    /// Entries are still written in the order they're added.
+    #[must_use]
+    pub fn dpkg_deb_compatible(mut self, enabled: bool) -> Self {
+        self.dpkg_deb_compat = enabled;
+        self
+    }
+
+    /// Copies all the files to be packaged into the tar archive, and hashes them for `md5sums`.
+    pub fn archive_files(mut self, package_deb: &PackageConfig, rsyncable: bool, listener: &dyn Listener) -> CDResult<(W, FileSums)> {
+        let mut sums = FileSums::new(package_deb.sha256sums);
         let mut archive_data_added = 0;
         let mut prev_is_built = false;
         let log_display_base_dir = std::env::current_dir().unwrap_or_default();
 
         debug_assert!(package_deb.assets.unresolved.is_empty());
+        self.explicit_directories = package_deb.assets.resolved.iter()
+            .filter(|asset| asset.source.is_dir())
+            .map(|asset| (asset.c.target_path.as_path().into(), (asset.archive_mode(), asset.c.owner.clone())))
+            .collect();
+
         for asset in &package_deb.assets.resolved {
             log_asset(asset, &log_display_base_dir, listener);
 
-            if let AssetSource::Symlink(symlink_kind) = &asset.source {
-
-                let link_name;
-                let link_name = match symlink_kind {
-                    SymlinkKind::Copied {source_path} => {
-                        link_name = fs::read_link(source_path)
-                            .map_err(|e| CargoDebError::IoFile("Symlink asset", e, source_path.clone()))?;
-                        &link_name
-                    }
-                    SymlinkKind::Created { target_path:_, link_name } => {
-                        link_name
-                    }
-                };
-
-                let Some(normalized_link_name) = normalize_link_name(&asset.c.target_path, link_name) else {
-                    return Err(CargoDebError::InvalidSymlink(asset.c.target_path.clone(), link_name.clone(), "would ascend beyond the root dir"));
-                };
-                
-                self.symlink(&asset.c.target_path, &normalized_link_name)?;
+            if asset.source.is_dir() {
+                // may have been added already as a parent of another asset
+                self.add_directories(&asset.c.target_path)?;
+            } else if let AssetSource::Hardlink { link_to, .. } = &asset.source {
+                self.hardlink(&asset.c.target_path, link_to, asset.archive_mode(), &asset.c.owner)?;
+                sums.add_duplicate(&asset.c.target_path, link_to);
+            } else if let AssetSource::Symlink(symlink_kind) = &asset.source {
+                self.symlink(&asset.c.target_path, &symlink_link_name(&asset.c.target_path, symlink_kind)?)?;
             } else {
-                let out_data = asset.source.data()?;
+                let (size, mut reader) = asset.source.reader()?;
                 if rsyncable {
                     if archive_data_added > 1_000_000 || prev_is_built != asset.c.is_built() {
                         self.flush().map_err(|e| CargoDebError::Io(e).context("error while writing tar archive"))?;
@@ -63,31 +118,43 @@ impl<W: Write> Tarball<W> {
                     }
                     // puts synchronization point between non-code and code assets
                     prev_is_built = asset.c.is_built();
-                    archive_data_added += out_data.len();
+                    archive_data_added += size;
                 }
-                self.file(&asset.c.target_path, &out_data, asset.c.chmod.unwrap_or(0o644))?;
+                let mut hasher = sums.hasher(&mut reader);
+                self.file_from_reader(&asset.c.target_path, size, &mut hasher, asset.archive_mode(), &asset.c.owner)?;
+                sums.add(&asset.c.target_path, hasher);
             }
         }
 
-        self.tar.into_inner().map_err(|e| CargoDebError::Io(e).context("error while finalizing tar archive"))
+        let tar = self.into_inner().map_err(|e| CargoDebError::Io(e).context("error while finalizing tar archive"))?;
+        Ok((tar, sums))
     }
 
     fn directory(&mut self, path: &Path) -> io::Result<()> {
-        let mut header = self.header_for_path(path, true)?;
+        let (mode, owner) = self.explicit_directories.get(path).cloned().unwrap_or((0o755, AssetOwner::default()));
+        let mut header = self.header_for_path(path, true, &owner)?;
         header.set_mtime(self.time);
         header.set_size(0);
-        header.set_mode(0o755);
+        header.set_mode(mode);
         header.set_entry_type(EntryType::Directory);
-        header.set_cksum();
+        self.set_cksum(&mut header);
         self.tar.append(&header, &mut io::empty())
     }
 
     fn add_parent_directories(&mut self, path: &Path) -> CDResult<()> {
         debug_assert!(path.is_relative());
+        match path.parent() {
+            Some(parent) => self.add_directories(parent),
+            None => Ok(()),
+        }
+    }
 
-        let dirs = path.ancestors().skip(1)
+    /// The directory and its parents, unless they've been added already.
+    /// The root is only added in the dpkg-deb compatible mode.
+    fn add_directories(&mut self, dir: &Path) -> CDResult<()> {
+        let dirs = dir.ancestors()
             .take_while(|&d| !self.added_directories.contains(d))
-            .filter(|&d| !d.as_os_str().is_empty())
+            .filter(|&d| self.dpkg_deb_compat || !d.as_os_str().is_empty())
             .map(Box::from)
             .collect::<Vec<_>>();
 
@@ -105,16 +172,22 @@ impl<W: Write> Tarball<W> {
     }
 
     fn file_(&mut self, path: &Path, out_data: &[u8], chmod: u32) -> CDResult<()> {
+        self.file_from_reader(path, out_data.len() as u64, out_data, chmod, &AssetOwner::default())
+    }
+
+    /// Streams `size` bytes of the file's content from `data`
+    pub(crate) fn file_from_reader(&mut self, path: &Path, size: u64, data: impl Read, chmod: u32, owner: &AssetOwner) -> CDResult<()> {
         debug_assert!(path.is_relative());
         self.add_parent_directories(path)?;
 
-        let mut header = self.header_for_path(path, false)
+        let mut header = self.header_for_path(path, false, owner)
             .map_err(|e| CargoDebError::IoFile("Can't set header path", e, path.into()))?;
         header.set_mtime(self.time);
         header.set_mode(chmod);
-        header.set_size(out_data.len() as u64);
-        header.set_cksum();
-        self.tar.append(&header, out_data)
+        header.set_size(size);
+        header.set_entry_type(EntryType::Regular);
+        self.set_cksum(&mut header);
+        self.tar.append(&header, ExactSize { inner: data, remaining: size })
             .map_err(|e| CargoDebError::IoFile("Can't add file to tarball", e, path.into()))?;
         Ok(())
     }
@@ -123,7 +196,7 @@ impl<W: Write> Tarball<W> {
         debug_assert!(path.is_relative());
         self.add_parent_directories(path.as_ref())?;
 
-        let mut header = self.header_for_path(path, false)
+        let mut header = self.header_for_path(path, false, &AssetOwner::default())
             .map_err(|e| CargoDebError::IoFile("Can't set header path", e, path.into()))?;
         header.set_mtime(self.time);
         header.set_entry_type(EntryType::Symlink);
@@ -131,30 +204,104 @@ impl<W: Write> Tarball<W> {
         header.set_mode(0o777);
         header.set_link_name(link_name)
             .map_err(|e| CargoDebError::IoFile("Can't set header link name", e, path.into()))?;
-        header.set_cksum();
+        self.set_cksum(&mut header);
         self.tar.append(&header, &mut io::empty())
             .map_err(|e| CargoDebError::IoFile("Can't add symlink to tarball", e, path.into()))?;
         Ok(())
     }
 
+    /// `link_to` must have been added earlier
+    fn hardlink(&mut self, path: &Path, link_to: &Path, chmod: u32, owner: &AssetOwner) -> CDResult<()> {
+        debug_assert!(path.is_relative() && link_to.is_relative());
+        self.add_parent_directories(path)?;
+
+        let mut link_bytes = b"./".to_vec();
+        link_bytes.extend_from_slice(link_to.to_bytes());
+        let mut header = self.header_for_path(path, false, owner)
+            .map_err(|e| CargoDebError::IoFile("Can't set header path", e, path.into()))?;
+        header.set_mtime(self.time);
+        header.set_entry_type(EntryType::Link);
+        header.set_size(0);
+        header.set_mode(chmod);
+        if link_bytes.len() < 100 {
+            header.as_old_mut().linkname[..link_bytes.len()].copy_from_slice(&link_bytes);
+        } else {
+            // GNU long link name extension, same as the long name in `set_header_path`
+            let long_link = self.long_link_header(b'K', link_bytes.len() as u64 + 1)
+                .map_err(|e| CargoDebError::IoFile("Can't add hardlink to tarball", e, path.into()))?;
+            self.tar.append(&long_link, link_bytes.chain(&b"\0"[..]))
+                .map_err(|e| CargoDebError::IoFile("Can't add hardlink to tarball", e, path.into()))?;
+            header.as_old_mut().linkname.copy_from_slice(&link_bytes[..100]);
+        }
+        self.set_cksum(&mut header);
+        self.tar.append(&header, &mut io::empty())
+            .map_err(|e| CargoDebError::IoFile("Can't add hardlink to tarball", e, path.into()))?;
+        Ok(())
+    }
+
     #[inline]
-    fn header_for_path(&mut self, path: &Path, is_dir: bool) -> io::Result<TarHeader> {
+    fn header_for_path(&mut self, path: &Path, is_dir: bool, owner: &AssetOwner) -> io::Result<TarHeader> {
         debug_assert!(path.is_relative());
         let path_bytes = path.to_bytes();
 
-        let mut header = if path_bytes.len() < 98 {
+        // the old header format has no room for user and group names
+        let mut header = if path_bytes.len() < 98 && !owner.has_names() && !self.dpkg_deb_compat {
             TarHeader::new_old()
         } else {
             TarHeader::new_gnu()
         };
         self.set_header_path(&mut header, path_bytes, is_dir)?;
+        self.set_owner(&mut header, owner)?;
+        Ok(header)
+    }
+
+    /// GNU tar writes 6 digits followed by NUL and space, the tar crate writes 7 digits
+    fn set_cksum(&self, header: &mut TarHeader) {
+        if !self.dpkg_deb_compat {
+            header.set_cksum();
+            return;
+        }
+        header.as_old_mut().cksum = [b' '; 8];
+        let sum: u32 = header.as_bytes().iter().map(|&b| u32::from(b)).sum();
+        let _ = write!(&mut header.as_old_mut().cksum[..], "{sum:06o}\0 ");
+    }
+
+    /// Numeric ids are used as-is. Names are looked up by dpkg when unpacking, falling back to root.
+    fn set_owner(&self, header: &mut TarHeader, owner: &AssetOwner) -> io::Result<()> {
+        let uid = owner.user.as_ref().map_or(0, |u| u.id());
+        let gid = owner.group.as_ref().map_or(0, |g| g.id());
+        header.set_uid(uid.into());
+        header.set_gid(gid.into());
+        // GNU tar names the owner even when it's root
+        let default_name = |id| (self.dpkg_deb_compat && id == 0).then_some("root");
+        if let Some(name) = owner.user.as_ref().and_then(|u| u.name()).or(default_name(uid)) {
+            header.set_username(name)?;
+        }
+        if let Some(name) = owner.group.as_ref().and_then(|g| g.name()).or(default_name(gid)) {
+            header.set_groupname(name)?;
+        }
+        Ok(())
+    }
+
+    /// Header of a GNU extension entry holding a name that doesn't fit in the next header
+    fn long_link_header(&self, entry_type: u8, size: u64) -> io::Result<TarHeader> {
+        let mut header = TarHeader::new_gnu();
+        const LONG_LINK: &[u8] = b"././@LongLink\0";
+        header.as_old_mut().name[..LONG_LINK.len()].copy_from_slice(LONG_LINK);
+        header.set_mode(0o644);
+        header.set_mtime(0);
+        header.set_size(size);
+        header.set_entry_type(EntryType::new(entry_type));
+        self.set_owner(&mut header, &AssetOwner::default())?;
+        self.set_cksum(&mut header);
         Ok(header)
     }
 
     #[inline(never)]
     fn set_header_path(&mut self, header: &mut TarHeader, path_bytes: &[u8], is_dir: bool) -> io::Result<()> {
         debug_assert!(is_dir || path_bytes.last() != Some(&b'/'));
-        let needs_slash = is_dir && path_bytes.last() != Some(&b'/');
+        // the root is just `./`
+        let needs_slash = is_dir && !path_bytes.is_empty() && path_bytes.last() != Some(&b'/');
 
         const PREFIX: &[u8] = b"./";
         let (prefix, path_slot) = header.as_old_mut().name.split_at_mut(PREFIX.len());
@@ -180,22 +327,12 @@ impl<W: Write> Tarball<W> {
             }
         }
 
-        // GNU long name extension, copied from
+        // GNU long name extension, based on
         // https://github.com/alexcrichton/tar-rs/blob/a1c3036af48fa02437909112239f0632e4cfcfae/src/builder.rs#L731-L744
-        let mut header = TarHeader::new_gnu();
-        const LONG_LINK: &[u8] = b"././@LongLink\0";
-        header.as_gnu_mut().ok_or(io::ErrorKind::Other)?
-            .name[..LONG_LINK.len()].copy_from_slice(LONG_LINK);
-        header.set_mode(0o644);
-        header.set_uid(0);
-        header.set_gid(0);
-        header.set_mtime(0);
         // include \0 in len to be compliant with GNU tar
         let suffix = b"/\0";
         let suffix = if needs_slash { &suffix[..] } else { &suffix[1..] };
-        header.set_size((PREFIX.len() + path_bytes.len() + suffix.len()) as u64);
-        header.set_entry_type(EntryType::new(b'L'));
-        header.set_cksum();
+        let header = self.long_link_header(b'L', (PREFIX.len() + path_bytes.len() + suffix.len()) as u64)?;
         self.tar.append(&header, PREFIX.chain(path_bytes).chain(suffix))
     }
 
@@ -203,11 +340,37 @@ impl<W: Write> Tarball<W> {
         self.tar.get_mut().flush()
     }
 
-    pub fn into_inner(self) -> io::Result<W> {
-        self.tar.into_inner()
+    pub fn into_inner(mut self) -> io::Result<W> {
+        if self.dpkg_deb_compat && !self.added_directories.contains(Path::new("")) {
+            self.directory(Path::new(""))?;
+        }
+        let mut out = self.tar.into_inner()?;
+        if self.dpkg_deb_compat {
+            let padding = out.written.next_multiple_of(TAR_RECORD_SIZE) - out.written;
+            io::copy(&mut io::repeat(0).take(padding), &mut out)?;
+        }
+        Ok(out.inner)
     }
 }
 
+/// Where the symlink at `target_path` points to, normalized
+pub(crate) fn symlink_link_name(target_path: &Path, symlink_kind: &SymlinkKind) -> CDResult<PathBuf> {
+    let link_name;
+    let link_name = match symlink_kind {
+        SymlinkKind::Copied {source_path} => {
+            link_name = fs::read_link(source_path)
+                .map_err(|e| CargoDebError::IoFile("Symlink asset", e, source_path.clone()))?;
+            &link_name
+        }
+        SymlinkKind::Created { target_path:_, link_name } => {
+            link_name
+        }
+    };
+
+    normalize_link_name(target_path, link_name)
+        .ok_or_else(|| CargoDebError::InvalidSymlink(target_path.into(), link_name.clone(), "would ascend beyond the root dir"))
+}
+
 fn normalize_link_name(target_path: &Path, link_name: &Path) -> Option<PathBuf> {
     // normalize symlinks according to https://www.debian.org/doc/debian-policy/ch-files.html#symbolic-links 
     // like dh_link https://manpages.debian.org/testing/debhelper/dh_link.1.en.html#DESCRIPTION
@@ -310,7 +473,7 @@ fn normalized_links() {
 }
 
 fn log_asset(asset: &Asset, log_display_base_dir: &Path, listener: &dyn Listener) {
-    let operation = if let AssetSource::Symlink(_) = &asset.source {
+    let operation = if let AssetSource::Symlink(_) | AssetSource::Hardlink { .. } = &asset.source {
         "Linking"
     } else {
         "Adding"
@@ -343,6 +506,7 @@ fn human_size(len: u64) -> (u64, &'static str) {
 #[cfg(test)]
 mod tests {
     use super::Tarball;
+    use crate::assets::{AssetOwner, OwnerId};
     use std::{io::{Cursor, Read}, path::Path};
     use tar::{Archive, EntryType};
 
@@ -447,4 +611,117 @@ mod tests {
         ]);
         check_tarball_content(buffer, &expected_entries);
     }
+
+    #[test]
+    fn file_from_reader() {
+        let mut tarball = Tarball::new(Vec::new(), 1234567890);
+        assert!(tarball.file_from_reader(Path::new("truncated.txt"), 100, &b"short"[..], 0o644, &AssetOwner::default()).is_err());
+
+        let content = b"streamed".repeat(100);
+        let mut tarball = Tarball::new(Vec::new(), 1234567890);
+        tarball.file_from_reader(Path::new("streamed.txt"), content.len() as u64, &content[..], 0o644, &AssetOwner::default()).unwrap();
+        // the size in the header wins over whatever the reader has left
+        tarball.file_from_reader(Path::new("grown.txt"), 4, &b"longer than declared"[..], 0o644, &AssetOwner::default()).unwrap();
+        let buffer = tarball.into_inner().unwrap();
+        check_tarball_content(buffer, &[
+            expected_entry("streamed.txt", EntryType::Regular, 0o644).with_check(|entry| {
+                let mut read = Vec::new();
+                entry.read_to_end(&mut read).unwrap();
+                assert_eq!(read, content);
+            }),
+            expected_entry("grown.txt", EntryType::Regular, 0o644).with_check(|entry| {
+                let mut read = Vec::new();
+                entry.read_to_end(&mut read).unwrap();
+                assert_eq!(read, b"long");
+            }),
+        ]);
+    }
+
+    #[test]
+    fn file_owner() {
+        let mut tarball = Tarball::new(Vec::new(), 1234567890);
+        let named = AssetOwner { user: OwnerId::parse("www-data"), group: OwnerId::parse("33") };
+        tarball.file_from_reader(Path::new("named"), 0, &b""[..], 0o640, &named).unwrap();
+        let numeric = AssetOwner { user: OwnerId::parse("1000"), group: None };
+        tarball.file_from_reader(Path::new("numeric"), 0, &b""[..], 0o644, &numeric).unwrap();
+        let buffer = tarball.into_inner().unwrap();
+        check_tarball_content(buffer, &[
+            expected_entry("named", EntryType::Regular, 0o640).with_check(|entry| {
+                let header = entry.header();
+                assert_eq!((header.uid().unwrap(), header.gid().unwrap()), (0, 33));
+                assert_eq!(header.username().unwrap(), Some("www-data"));
+                assert_eq!(header.groupname().unwrap(), Some(""));
+            }),
+            expected_entry("numeric", EntryType::Regular, 0o644).with_check(|entry| {
+                let header = entry.header();
+                assert_eq!((header.uid().unwrap(), header.gid().unwrap()), (1000, 0));
+                assert!(header.as_ustar().is_none() && header.as_gnu().is_none());
+            }),
+        ]);
+    }
+
+    #[test]
+    fn explicit_directory_as_parent() {
+        let mut tarball = Tarball::new(Vec::new(), 1234567890);
+        let owner = AssetOwner { user: OwnerId::parse("www-data"), group: None };
+        tarball.explicit_directories.insert(Path::new("var/lib/foo").into(), (0o750, owner));
+        tarball.file("var/lib/foo/bar", b"", 0o640).unwrap();
+        tarball.add_directories(Path::new("var/lib/foo")).unwrap();
+        tarball.add_directories(Path::new("var/empty")).unwrap();
+        let buffer = tarball.into_inner().unwrap();
+        check_tarball_content(buffer, &[
+            expected_entry("var/", EntryType::Directory, 0o755),
+            expected_entry("var/lib/", EntryType::Directory, 0o755),
+            expected_entry("var/lib/foo/", EntryType::Directory, 0o750).with_check(|entry| {
+                assert_eq!(entry.header().username().unwrap(), Some("www-data"));
+            }),
+            expected_entry("var/lib/foo/bar", EntryType::Regular, 0o640),
+            expected_entry("var/empty/", EntryType::Directory, 0o755),
+        ]);
+    }
+
+    #[test]
+    fn hardlinks() {
+        let mut tarball = Tarball::new(Vec::new(), 1234567890);
+        let long_dir = "long/".repeat(25);
+        tarball.file(format!("{long_dir}original"), b"data", 0o755).unwrap();
+        tarball.hardlink(Path::new("usr/bin/short"), Path::new(&format!("{long_dir}original")), 0o755, &AssetOwner::default()).unwrap();
+        tarball.file("b", b"data", 0o755).unwrap();
+        tarball.hardlink(Path::new("usr/bin/b"), Path::new("b"), 0o755, &AssetOwner::default()).unwrap();
+        let buffer = tarball.into_inner().unwrap();
+
+        let mut archive = Archive::new(Cursor::new(buffer));
+        let links = archive.entries().unwrap().map(|e| e.unwrap())
+            .filter(|e| e.header().entry_type() == EntryType::Link)
+            .map(|e| (e.path().unwrap().into_owned(), e.link_name().unwrap().unwrap().into_owned()))
+            .collect::<Vec<_>>();
+        assert_eq!(links, [
+            ("./usr/bin/short".into(), format!("./{long_dir}original").into()),
+            ("./usr/bin/b".into(), "./b".into()),
+        ]);
+    }
+
+    #[test]
+    fn dpkg_deb_compatible() {
+        let mut tarball = Tarball::new(Vec::new(), 1234567890).dpkg_deb_compatible(true);
+        tarball.file("usr/bin/foo", b"foo", 0o755).unwrap();
+        tarball.symlink(Path::new("usr/bin/bar"), Path::new("foo")).unwrap();
+        let buffer = tarball.into_inner().unwrap();
+        assert_eq!(buffer.len(), 10240);
+
+        let headers = Archive::new(Cursor::new(&buffer)).entries().unwrap()
+            .map(|e| e.unwrap().header().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(headers[0].as_old().name[..3], *b"./\0");
+        for header in &headers {
+            assert!(header.as_gnu().is_some());
+            assert_eq!((header.username().unwrap(), header.groupname().unwrap()), (Some("root"), Some("root")));
+            assert_eq!(header.as_old().cksum[6..], *b"\0 ");
+        }
+        assert_eq!(headers.iter().map(|h| h.entry_type()).collect::<Vec<_>>(),
+            [EntryType::Directory, EntryType::Directory, EntryType::Directory, EntryType::Regular, EntryType::Symlink]);
+
+        let empty = Tarball::new(Vec::new(), 1234567890).dpkg_deb_compatible(true).into_inner().unwrap();
+        assert_eq!(Archive::new(Cursor::new(&empty)).entries().unwrap().count(), 1);
+    }
 }
@@ -0,0 +1,241 @@
+//! [deb822](https://manpages.debian.org/deb822) paragraphs, the format of `DEBIAN/control`
+//!
+//! Values are kept the way they're written in the file: the first line without
+//! the space after the colon, and continuation lines verbatim, including their
+//! leading whitespace, and `.` standing for a blank line. This makes parsing and
+//! writing lossless. Use [`ControlParagraph::lines`] or [`ControlParagraph::folded`]
+//! to get the decoded text.
+
+use crate::error::{CDResult, CargoDebError};
+use std::fmt;
+
+/// A single paragraph of `Name: value` fields, in their original order
+///
+/// Field names are case-insensitive, but keep the case they've been set with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ControlParagraph {
+    fields: Vec<(String, String)>,
+}
+
+impl ControlParagraph {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses the first paragraph of the text. Comment lines starting with `#` are skipped.
+    pub fn parse(text: &str) -> CDResult<Self> {
+        let mut fields: Vec<(String, String)> = Vec::new();
+        for line in text.lines() {
+            if line.starts_with('#') {
+                continue;
+            }
+            if line.trim().is_empty() {
+                if fields.is_empty() {
+                    continue;
+                }
+                // only the first paragraph
+                break;
+            }
+            if line.starts_with([' ', '\t']) {
+                let Some((_, value)) = fields.last_mut() else {
+                    return Err(CargoDebError::InvalidControlField(line.trim().into(), "a continuation line must follow a field"));
+                };
+                value.push('\n');
+                value.push_str(line.trim_end());
+                continue;
+            }
+            let Some((name, value)) = line.split_once(':') else {
+                return Err(CargoDebError::InvalidControlField(line.into(), "lines must be 'Name: value', or start with a space to continue the previous field"));
+            };
+            check_name(name)?;
+            if fields.iter().any(|(n, _)| n.eq_ignore_ascii_case(name)) {
+                return Err(CargoDebError::InvalidControlField(name.into(), "the field is set more than once"));
+            }
+            fields.push((name.to_owned(), value.trim().to_owned()));
+        }
+        Ok(Self { fields })
+    }
+
+    /// Raw value of the field, with continuation lines as written in the file.
+    /// Field names are case-insensitive.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.position(name).map(|i| self.fields[i].1.as_str())
+    }
+
+    /// Lines of a multiline field like `Description`, with the indentation of
+    /// continuation lines removed, and `.` lines turned into empty ones
+    pub fn lines(&self, name: &str) -> Option<impl Iterator<Item = &str>> {
+        let mut lines = self.get(name)?.split('\n');
+        let first = lines.next();
+        Some(first.into_iter().chain(lines.map(|line| {
+            let line = line.strip_prefix([' ', '\t']).unwrap_or(line);
+            if line == "." { "" } else { line }
+        })))
+    }
+
+    /// Value of a folded field like `Depends`, with line breaks and indentation collapsed into single spaces
+    #[must_use]
+    pub fn folded(&self, name: &str) -> Option<String> {
+        let value = self.get(name)?;
+        Some(value.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
+
+    /// Replaces the value in place, keeping the field's position, or appends a new field at the end.
+    ///
+    /// Continuation lines are indented if they aren't already. They can't be empty.
+    pub fn set(&mut self, name: &str, value: &str) -> CDResult<()> {
+        let value = encode_value(name, value)?;
+        match self.position(name) {
+            Some(i) => self.fields[i].1 = value,
+            None => self.fields.push((name.to_owned(), value)),
+        }
+        Ok(())
+    }
+
+    /// Sets a multiline field from the first line and the following lines of text.
+    /// Empty lines are written as ` .`.
+    pub fn set_lines<S: AsRef<str>>(&mut self, name: &str, lines: impl IntoIterator<Item = S>) -> CDResult<()> {
+        let mut value = String::new();
+        for (n, line) in lines.into_iter().enumerate() {
+            let line = line.as_ref();
+            if line.contains('\n') {
+                return Err(CargoDebError::InvalidControlField(name.into(), "lines can't contain line breaks"));
+            }
+            if n > 0 {
+                value.push_str("\n ");
+                value.push_str(if line.trim().is_empty() { "." } else { line });
+            } else {
+                value.push_str(line.trim());
+            }
+        }
+        self.set(name, &value)
+    }
+
+    /// Adds the field just before the `before` field, or at the end if there's no such field.
+    /// If the field already exists, it's moved.
+    pub fn insert_before(&mut self, before: &str, name: &str, value: &str) -> CDResult<()> {
+        let value = encode_value(name, value)?;
+        self.remove(name);
+        let pos = self.position(before).unwrap_or(self.fields.len());
+        self.fields.insert(pos, (name.to_owned(), value));
+        Ok(())
+    }
+
+    /// Returns the removed raw value
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        self.position(name).map(|i| self.fields.remove(i).1)
+    }
+
+    /// Keeps only fields for which the callback returns `true`
+    pub fn retain(&mut self, mut keep: impl FnMut(&str, &str) -> bool) {
+        self.fields.retain(|(name, value)| keep(name, value));
+    }
+
+    #[must_use]
+    pub fn contains(&self, name: &str) -> bool {
+        self.position(name).is_some()
+    }
+
+    /// Names and raw values, in order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.fields.iter().position(|(n, _)| n.eq_ignore_ascii_case(name))
+    }
+}
+
+/// Writes all fields, each ending with a newline, without the blank line that separates paragraphs
+impl fmt::Display for ControlParagraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in &self.fields {
+            f.write_str(name)?;
+            f.write_str(":")?;
+            if !value.is_empty() && !value.starts_with('\n') {
+                f.write_str(" ")?;
+            }
+            f.write_str(value)?;
+            f.write_str("\n")?;
+        }
+        Ok(())
+    }
+}
+
+fn check_name(name: &str) -> CDResult<()> {
+    if name.is_empty() || name.starts_with(['#', '-']) || !name.bytes().all(|b| b.is_ascii_graphic() && b != b':') {
+        return Err(CargoDebError::InvalidControlField(name.into(), "names must be ASCII letters, digits, or punctuation other than ':', and can't start with '#' or '-'"));
+    }
+    Ok(())
+}
+
+/// Validates the name, and indents continuation lines
+pub(crate) fn encode_value(name: &str, value: &str) -> CDResult<String> {
+    check_name(name)?;
+    let mut lines = value.trim_end().lines();
+    let mut out = lines.next().unwrap_or_default().trim().to_owned();
+    for line in lines {
+        if line.trim().is_empty() {
+            return Err(CargoDebError::InvalidControlField(name.into(), "continuation lines can't be empty, use '.' for a blank line"));
+        }
+        out.push('\n');
+        if !line.starts_with([' ', '\t']) {
+            out.push(' ');
+        }
+        out.push_str(line.trim_end());
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let text = "Package: foo\nVersion: 1.0\nDepends: libc6,\n  libfoo (>= 2)\nDescription: Short\n Long text\n .\n   indented\nX-Empty:\n";
+        let control = ControlParagraph::parse(text).unwrap();
+        assert_eq!(control.len(), 5);
+        assert_eq!(control.to_string(), text);
+        assert_eq!(control.get("package"), Some("foo"));
+        assert_eq!(control.get("X-Empty"), Some(""));
+        assert_eq!(control.folded("Depends").unwrap(), "libc6, libfoo (>= 2)");
+        assert_eq!(control.lines("Description").unwrap().collect::<Vec<_>>(), ["Short", "Long text", "", "  indented"]);
+    }
+
+    #[test]
+    fn only_first_paragraph() {
+        let control = ControlParagraph::parse("\n# comment\nA: 1\n# comment\nB: 2\n\nC: 3\n").unwrap();
+        assert_eq!(control.to_string(), "A: 1\nB: 2\n");
+        assert!(ControlParagraph::parse(" orphan\n").is_err());
+        assert!(ControlParagraph::parse("A: 1\nno colon\n").is_err());
+        assert!(ControlParagraph::parse("A: 1\na: 2\n").is_err());
+    }
+
+    #[test]
+    fn modify() {
+        let mut control = ControlParagraph::parse("Package: foo\nArchitecture: amd64\nDescription: Foo\n").unwrap();
+        control.set("architecture", "arm64").unwrap();
+        control.insert_before("Description", "License", "MIT").unwrap();
+        control.set("X-Notes", "one\ntwo\n  three").unwrap();
+        control.set_lines("Y", ["first", "", "second"]).unwrap();
+        assert_eq!(control.to_string(), "Package: foo\nArchitecture: arm64\nLicense: MIT\nDescription: Foo\nX-Notes: one\n two\n  three\nY: first\n .\n second\n");
+        assert_eq!(control.remove("LICENSE").as_deref(), Some("MIT"));
+        assert!(!control.contains("License"));
+        assert!(control.set("X-Bad", "one\n\ntwo").is_err());
+        assert!(control.set("Bad Name", "x").is_err());
+        assert!(control.set_lines("X", ["a\nb"]).is_err());
+    }
+}
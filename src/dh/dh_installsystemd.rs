@@ -242,6 +242,60 @@ pub fn generate(package: &str, assets: &[Asset], options: &Options, listener: &d
     // installed, upgraded or removed.
     // see: https://git.launchpad.net/ubuntu/+source/debhelper/tree/dh_installsystemd?h=applied/12.10ubuntu1#n312
 
+    let (enable_units, start_units) = units_to_enable_and_start(assets, listener)?;
+
+    // update the maintainer scripts to enable units unless forbidden by the
+    // options passed to us.
+    // see: https://git.launchpad.net/ubuntu/+source/debhelper/tree/dh_installsystemd?h=applied/12.10ubuntu1#n390
+    if !enable_units.is_empty() {
+        let snippet = if options.no_enable { "postinst-systemd-dont-enable" } else { "postinst-systemd-enable" };
+        for unit in &enable_units {
+            autoscript(&mut scripts, package, "postinst", snippet,
+                &map!{ "UNITFILE" => unit.clone() }, true, listener)?;
+        }
+        autoscript(&mut scripts, package, "postrm", "postrm-systemd",
+            &map!{ "UNITFILES" => enable_units.join(" ") }, false, listener)?;
+    }
+
+    // update the maintainer scripts to start units, where the exact action to
+    // be taken is influenced by the options passed to us.
+    // see: https://git.launchpad.net/ubuntu/+source/debhelper/tree/dh_installsystemd?h=applied/12.10ubuntu1#n398
+    if !start_units.is_empty() {
+        let mut replace = map! { "UNITFILES" => start_units.join(" ") };
+
+        if options.restart_after_upgrade {
+            let snippet = if options.no_start {
+                replace.insert("RESTART_ACTION", "try-restart".into());
+                "postinst-systemd-restartnostart"
+            } else {
+                replace.insert("RESTART_ACTION", "restart".into());
+                "postinst-systemd-restart"
+            };
+            autoscript(&mut scripts, package, "postinst", snippet, &replace, true, listener)?;
+        } else if !options.no_start {
+            // (stop|start) service (before|after) upgrade
+            autoscript(&mut scripts, package, "postinst", "postinst-systemd-start", &replace, true, listener)?;
+        }
+
+        if options.no_stop_on_upgrade || options.restart_after_upgrade {
+            // stop service only on remove
+            autoscript(&mut scripts, package, "prerm", "prerm-systemd-restart", &replace, true, listener)?;
+        } else if !options.no_start {
+            // always stop service
+            autoscript(&mut scripts, package, "prerm", "prerm-systemd", &replace, true, listener)?;
+        }
+
+        // Run this with "default" order so it is always after other service
+        // related autosnippets.
+        autoscript(&mut scripts, package, "postrm", "postrm-systemd-reload-only", &replace, false, listener)?;
+    }
+
+    Ok(scripts)
+}
+
+/// Installed non-template units (and units they refer to with `Also=`) that should be started,
+/// and those of them that have an `[Install]` section, so they can be enabled.
+pub(crate) fn units_to_enable_and_start(assets: &[Asset], listener: &dyn Listener) -> CDResult<(BTreeSet<String>, BTreeSet<String>)> {
     // skip template service files. Enabling, disabling, starting or stopping
     // those services without specifying the instance is not useful.
     let mut installed_non_template_units: BTreeSet<String> = BTreeSet::new();
@@ -331,53 +385,7 @@ pub fn generate(package: &str, assets: &[Asset], options: &Options, listener: &d
         units = also_units;
     }
 
-    // update the maintainer scripts to enable units unless forbidden by the
-    // options passed to us.
-    // see: https://git.launchpad.net/ubuntu/+source/debhelper/tree/dh_installsystemd?h=applied/12.10ubuntu1#n390
-    if !enable_units.is_empty() {
-        let snippet = if options.no_enable { "postinst-systemd-dont-enable" } else { "postinst-systemd-enable" };
-        for unit in &enable_units {
-            autoscript(&mut scripts, package, "postinst", snippet,
-                &map!{ "UNITFILE" => unit.clone() }, true, listener)?;
-        }
-        autoscript(&mut scripts, package, "postrm", "postrm-systemd",
-            &map!{ "UNITFILES" => enable_units.join(" ") }, false, listener)?;
-    }
-
-    // update the maintainer scripts to start units, where the exact action to
-    // be taken is influenced by the options passed to us.
-    // see: https://git.launchpad.net/ubuntu/+source/debhelper/tree/dh_installsystemd?h=applied/12.10ubuntu1#n398
-    if !start_units.is_empty() {
-        let mut replace = map! { "UNITFILES" => start_units.join(" ") };
-
-        if options.restart_after_upgrade {
-            let snippet = if options.no_start {
-                replace.insert("RESTART_ACTION", "try-restart".into());
-                "postinst-systemd-restartnostart"
-            } else {
-                replace.insert("RESTART_ACTION", "restart".into());
-                "postinst-systemd-restart"
-            };
-            autoscript(&mut scripts, package, "postinst", snippet, &replace, true, listener)?;
-        } else if !options.no_start {
-            // (stop|start) service (before|after) upgrade
-            autoscript(&mut scripts, package, "postinst", "postinst-systemd-start", &replace, true, listener)?;
-        }
-
-        if options.no_stop_on_upgrade || options.restart_after_upgrade {
-            // stop service only on remove
-            autoscript(&mut scripts, package, "prerm", "prerm-systemd-restart", &replace, true, listener)?;
-        } else if !options.no_start {
-            // always stop service
-            autoscript(&mut scripts, package, "prerm", "prerm-systemd", &replace, true, listener)?;
-        }
-
-        // Run this with "default" order so it is always after other service
-        // related autosnippets.
-        autoscript(&mut scripts, package, "postrm", "postrm-systemd-reload-only", &replace, false, listener)?;
-    }
-
-    Ok(scripts)
+    Ok((enable_units, start_units))
 }
 
 #[cfg(test)]
@@ -25,9 +25,11 @@ use crate::CDResult;
 /// DebHelper autoscripts are embedded in the Rust library binary.
 /// The autoscripts were taken from:
 ///   <https://git.launchpad.net/ubuntu/+source/debhelper/tree/autoscripts?h=applied/12.10ubuntu1>
+/// except `postinst-chown`, which is cargo-deb's own, for assets with a `user` or `group`.
 /// To understand which scripts are invoked when, consult:
 ///   <https://www.debian.org/doc/debian-policy/ap-flowcharts.htm>
-static AUTOSCRIPTS: [(&str, &[u8]); 10] = [
+static AUTOSCRIPTS: [(&str, &[u8]); 11] = [
+    ("postinst-chown", include_bytes!("../../autoscripts/postinst-chown")),
     ("postinst-init-tmpfiles", include_bytes!("../../autoscripts/postinst-init-tmpfiles")),
     ("postinst-systemd-dont-enable", include_bytes!("../../autoscripts/postinst-systemd-dont-enable")),
     ("postinst-systemd-enable", include_bytes!("../../autoscripts/postinst-systemd-enable")),
@@ -248,10 +250,10 @@ fn autoscript_sed(snippet_filename: &str, replacements: &HashMap<&str, String>)
 /// # References
 ///
 /// <https://git.launchpad.net/ubuntu/+source/debhelper/tree/lib/Debian/Debhelper/Dh_Lib.pm?h=applied/12.10ubuntu1#n2161>
-fn debhelper_script_subst(user_scripts_dir: &Path, scripts: &mut ScriptFragments, package: &str, script: &str, unit_name: Option<&str>,
+fn debhelper_script_subst(user_scripts_dir: Option<&Path>, scripts: &mut ScriptFragments, package: &str, script: &str, unit_name: Option<&str>,
     listener: &dyn Listener) -> CDResult<()>
 {
-    let user_file = pkgfile(user_scripts_dir, package, package, script, unit_name);
+    let user_file = user_scripts_dir.and_then(|dir| pkgfile(dir, package, package, script, unit_name));
     let mut generated_scripts: Vec<String> = vec![
         format!("{package}.{script}.debhelper"),
         format!("{package}.{script}.service"),
@@ -299,10 +301,10 @@ fn debhelper_script_subst(user_scripts_dir: &Path, scripts: &mut ScriptFragments
 
 /// Generate final maintainer scripts by merging the autoscripts that have been
 /// collected in the `ScriptFragments` map  with the maintainer scripts
-/// on disk supplied by the user.
+/// on disk supplied by the user, if there's a directory for them.
 ///
 /// See: <https://git.launchpad.net/ubuntu/+source/debhelper/tree/dh_installdeb?h=applied/12.10ubuntu1#n300>
-pub(crate) fn apply(user_scripts_dir: &Path, scripts: &mut ScriptFragments, package: &str, unit_name: Option<&str>, listener: &dyn Listener) -> CDResult<()> {
+pub(crate) fn apply(user_scripts_dir: Option<&Path>, scripts: &mut ScriptFragments, package: &str, unit_name: Option<&str>, listener: &dyn Listener) -> CDResult<()> {
     for script in &["postinst", "preinst", "prerm", "postrm"] {
         // note: we don't support custom defines thus we don't have the final
         // 'package_subst' argument to debhelper_script_subst().
@@ -445,6 +447,7 @@ mod tests {
         actual_scripts.sort_unstable();
 
         let expected_scripts = vec![
+            "postinst-chown",
             "postinst-init-tmpfiles",
             "postinst-systemd-dont-enable",
             "postinst-systemd-enable",
@@ -594,7 +597,7 @@ mod tests {
         let mut scripts = ScriptFragments::new();
 
         assert_eq!(0, scripts.len());
-        debhelper_script_subst(Path::new(""), &mut scripts, "mypkg", "myscript", None, &mock_listener).unwrap();
+        debhelper_script_subst(Some(Path::new("")), &mut scripts, "mypkg", "myscript", None, &mock_listener).unwrap();
         assert_eq!(0, scripts.len());
     }
 
@@ -609,7 +612,7 @@ mod tests {
 
         let mut scripts = ScriptFragments::new();
 
-        match debhelper_script_subst(Path::new(""), &mut scripts, "mypkg", "myscript", None, &mock_listener) {
+        match debhelper_script_subst(Some(Path::new("")), &mut scripts, "mypkg", "myscript", None, &mock_listener) {
             Ok(()) => (),
             Err(CargoDebError::DebHelperReplaceFailed(_)) => panic!("Test failed as expected"),
             Err(err) => panic!("Unexpected error {err:?}"),
@@ -628,7 +631,7 @@ mod tests {
         let mut scripts = ScriptFragments::new();
 
         assert_eq!(0, scripts.len());
-        debhelper_script_subst(Path::new(""), &mut scripts, "mypkg", "myscript", None, &mock_listener).unwrap();
+        debhelper_script_subst(Some(Path::new("")), &mut scripts, "mypkg", "myscript", None, &mock_listener).unwrap();
         assert_eq!(1, scripts.len());
         assert!(scripts.contains_key("myscript"));
     }
@@ -647,7 +650,7 @@ mod tests {
         scripts.insert("mypkg.myscript.debhelper".to_owned(), "injected".into());
 
         assert_eq!(1, scripts.len());
-        debhelper_script_subst(Path::new(""), &mut scripts, "mypkg", "myscript", None, &mock_listener).unwrap();
+        debhelper_script_subst(Some(Path::new("")), &mut scripts, "mypkg", "myscript", None, &mock_listener).unwrap();
         assert_eq!(2, scripts.len());
         assert!(scripts.contains_key("mypkg.myscript.debhelper"));
         assert!(scripts.contains_key("myscript"));
@@ -669,7 +672,7 @@ mod tests {
         scripts.insert("mypkg.myscript.debhelper".to_owned(), "injected".into());
 
         assert_eq!(1, scripts.len());
-        debhelper_script_subst(Path::new(""), &mut scripts, "mypkg", "myscript", None, &mock_listener).unwrap();
+        debhelper_script_subst(Some(Path::new("")), &mut scripts, "mypkg", "myscript", None, &mock_listener).unwrap();
         assert_eq!(2, scripts.len());
         assert!(scripts.contains_key("mypkg.myscript.debhelper"));
         assert!(scripts.contains_key("myscript"));
@@ -701,7 +704,7 @@ mod tests {
         scripts.insert(format!("mypkg.{maintainer_script}.service"), "second".into());
 
         assert_eq!(2, scripts.len());
-        debhelper_script_subst(Path::new(""), &mut scripts, "mypkg", maintainer_script, None, &mock_listener).unwrap();
+        debhelper_script_subst(Some(Path::new("")), &mut scripts, "mypkg", maintainer_script, None, &mock_listener).unwrap();
         assert_eq!(3, scripts.len());
         assert!(scripts.contains_key(&format!("mypkg.{maintainer_script}.debhelper")));
         assert!(scripts.contains_key(&format!("mypkg.{maintainer_script}.service")));
@@ -735,7 +738,7 @@ mod tests {
         let mut scripts = ScriptFragments::new();
 
         assert_eq!(0, scripts.len());
-        let result = debhelper_script_subst(Path::new(""), &mut scripts, "mypkg", "myscript", None, &mock_listener);
+        let result = debhelper_script_subst(Some(Path::new("")), &mut scripts, "mypkg", "myscript", None, &mock_listener);
 
         assert!(matches!(result, Err(CargoDebError::IoFile(..))));
         if let CargoDebError::IoFile(_, err, _) = result.unwrap_err() {
@@ -749,7 +752,7 @@ mod tests {
     fn apply_with_no_matching_files() {
         let mut mock_listener = crate::listener::MockListener::new();
         mock_listener.expect_info().times(0).return_const(());
-        apply(Path::new(""), &mut ScriptFragments::new(), "mypkg", None, &mock_listener).unwrap();
+        apply(Some(Path::new("")), &mut ScriptFragments::new(), "mypkg", None, &mock_listener).unwrap();
     }
 
     #[rstest]
@@ -765,6 +768,6 @@ mod tests {
         let mut mock_listener = crate::listener::MockListener::new();
         mock_listener.expect_progress().times(scripts.len()).return_const(());
 
-        apply(Path::new(""), &mut ScriptFragments::new(), "mypkg", None, &mock_listener).unwrap();
+        apply(Some(Path::new("")), &mut ScriptFragments::new(), "mypkg", None, &mock_listener).unwrap();
     }
 }
@@ -1,5 +1,6 @@
 use quick_error::quick_error;
 use std::borrow::Cow;
+use std::ops::Range;
 use std::path::PathBuf;
 use std::process::ExitStatus;
 use std::{env, fmt, io, num, time};
@@ -43,6 +44,25 @@ quick_error! {
         InvalidVersion(msg: &'static str, ver: String) {
             display("Version '{ver}' is invalid: {msg}")
         }
+        InvalidCompression(msg: String) {
+            display("Invalid compression setting: {msg}")
+        }
+        InvalidControlField(name: String, reason: &'static str) {
+            display("Invalid control field '{name}': {reason}")
+        }
+        InvalidRelation(field: String, value: String, span: Range<usize>, reason: &'static str) {
+            display("Invalid {field}: {reason}\n    {}\n    {}{}",
+                value.replace(['\n', '\t'], " "),
+                " ".repeat(value[..span.start].chars().count()),
+                "^".repeat(value[span.clone()].chars().count().max(1)),
+            )
+        }
+        InvalidPackage(path: PathBuf, reason: String) {
+            display("{} is not a valid deb package: {reason}", path.display())
+        }
+        OutputDirNotEmpty(path: PathBuf) {
+            display("The output directory {} is not empty", path.display())
+        }
         InstallFailed(status: ExitStatus) {
             display("Installation failed, because `dpkg -i` returned error {status}")
         }
@@ -0,0 +1,159 @@
+//! [opkg](https://openwrt.org/docs/guide-user/additional-software/opkg) packages for OpenWrt
+//!
+//! An `.ipk` is a gzipped tarball holding `debian-binary`, `control.tar.gz`, and `data.tar.gz`,
+//! so the tarballs are the same as in a .deb, only the container and the control fields differ.
+
+use crate::assets::AssetOwner;
+use crate::config::{BuildEnvironment, PackageConfig};
+use crate::deb::control::ControlArchiveBuilder;
+use crate::deb::tar::Tarball;
+use crate::deb822::ControlParagraph;
+use crate::error::{CDResult, CargoDebError};
+use crate::listener::Listener;
+use crate::relation::{parse_depends, DependsEntry, Rules};
+use crate::util::compress::{self, CompressConfig, Format};
+use crate::OutputPath;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Fields that opkg understands. Everything else in the Debian control file is dropped.
+const OPKG_FIELDS: &[&str] = &[
+    "Package", "Version", "Architecture", "Maintainer", "Section", "Priority", "Essential", "Source",
+    "Installed-Size", "Depends", "Pre-Depends", "Recommends", "Suggests", "Conflicts", "Replaces", "Provides",
+    "Description",
+];
+
+/// The file name is `{name}_{version}_{arch}.ipk` when writing to a directory
+pub(crate) fn ipk_output_path(package_deb: &PackageConfig, path: &OutputPath<'_>) -> PathBuf {
+    if path.is_dir {
+        path.path.join(format!("{}_{}_{}.ipk", package_deb.deb_name, package_deb.deb_version, architecture(package_deb)))
+    } else {
+        path.path.to_owned()
+    }
+}
+
+/// Writes an `.ipk` package. All the archives are always gzipped, because that's the only format opkg can rely on.
+pub(crate) fn write_ipk(config: &BuildEnvironment, ipk_output_path: PathBuf, package_deb: &PackageConfig, &CompressConfig { fast, compress_type, compress_system, options, .. }: &CompressConfig, listener: &dyn Listener) -> CDResult<PathBuf> {
+    if compress_type.is_some_and(|f| f != Format::Gzip) {
+        return Err(CargoDebError::InvalidCompression("ipk packages can only use gz".into()));
+    }
+    if has_auto_depends(&package_deb.wildcard_depends)? {
+        listener.warning("Depends uses $auto, which finds Debian's package names, like libc6, that OpenWrt doesn't have.\n\
+            Set `depends` explicitly for the ipk package".into());
+    }
+    let ipk_temp_dir = config.deb_temp_dir(package_deb);
+    let gzip = || compress::select_compressor(fast, Format::Gzip, &options, compress_system, false, &ipk_temp_dir);
+
+    let archive = Tarball::new(gzip()?, package_deb.default_timestamp);
+    let (data, sums) = archive.archive_files(package_deb, false, listener)?;
+    let data = data.finish()?;
+
+    let control = opkg_control(package_deb.control_paragraph(config)?, architecture(package_deb), package_deb.license_identifier.as_deref())?;
+    let mut control_builder = ControlArchiveBuilder::new(gzip()?, package_deb.default_timestamp, listener);
+    control_builder.generate_archive_with_control(control.as_bytes(), config, package_deb, &sums)?;
+    let control = control_builder.finish()?.finish()?;
+
+    let mut ipk = Tarball::new(gzip()?, package_deb.default_timestamp);
+    let owner = AssetOwner::default();
+    ipk.file("debian-binary", b"2.0\n", 0o644)?;
+    ipk.file_from_reader(Path::new("control.tar.gz"), control.len(), control, 0o644, &owner)?;
+    ipk.file_from_reader(Path::new("data.tar.gz"), data.len(), data, 0o644, &owner)?;
+    let mut ipk = ipk.into_inner().map_err(|e| CargoDebError::Io(e).context("error while finalizing ipk archive"))?.finish()?;
+
+    let _ = fs::create_dir_all(ipk_output_path.parent().ok_or("invalid output path")?);
+    let mut file = fs::File::create(&ipk_output_path)
+        .map_err(|e| CargoDebError::IoFile("can't create file for the archive", e, ipk_output_path.clone()))?;
+    io::copy(&mut ipk, &mut file)
+        .map_err(|e| CargoDebError::IoFile("can't write the archive", e, ipk_output_path.clone()))?;
+
+    let _ = fs::remove_dir(&ipk_temp_dir);
+    Ok(ipk_output_path)
+}
+
+/// `$auto` is the default when `depends` isn't set
+fn has_auto_depends(depends: &str) -> CDResult<bool> {
+    Ok(parse_depends("Depends", depends, Rules { auto: true, ..Rules::default() })?.contains(&DependsEntry::Auto))
+}
+
+/// Keeps only fields known to opkg, with OpenWrt's architecture name, and adds `License`
+fn opkg_control(mut control: ControlParagraph, architecture: &str, license: Option<&str>) -> CDResult<String> {
+    control.retain(|name, _| OPKG_FIELDS.iter().any(|f| f.eq_ignore_ascii_case(name)));
+    if control.contains("Architecture") {
+        control.set("Architecture", architecture)?;
+    }
+    if let Some(license) = license.filter(|_| control.contains("Description")) {
+        control.insert_before("Description", "License", license)?;
+    }
+    Ok(control.to_string())
+}
+
+fn architecture(package_deb: &PackageConfig) -> &str {
+    if package_deb.architecture == "all" {
+        return "all";
+    }
+    openwrt_architecture_from_rust_triple(package_deb.rust_target_triple.as_deref().unwrap_or(crate::DEFAULT_TARGET))
+}
+
+/// OpenWrt names architectures after the CPU its packages are tuned for.
+/// These are the generic or most common ones for each Rust target.
+fn openwrt_architecture_from_rust_triple(rust_target_triple: &str) -> &str {
+    let mut p = rust_target_triple.split('-');
+    let arch = p.next().unwrap();
+    let abi = p.next_back().unwrap_or("");
+
+    match (arch, abi) {
+        ("x86_64", _) => "x86_64",
+        ("i686", _) => "i386_pentium4",
+        ("i586", _) => "i386_pentium-mmx",
+        ("aarch64", _) => "aarch64_generic",
+        (arm, abi) if arm.starts_with("armv7") || arm.starts_with("thumbv7") => {
+            if abi.ends_with("hf") { "arm_cortex-a7_neon-vfpv4" } else { "arm_cortex-a7" }
+        },
+        ("arm" | "armv6", abi) if abi.ends_with("hf") => "arm_arm1176jzf-s_vfp",
+        (arm, _) if arm.starts_with("arm") => "arm_arm926ej-s",
+        ("mips", _) => "mips_24kc",
+        ("mipsel", _) => "mipsel_24kc",
+        ("mips64", _) => "mips64_octeonplus",
+        ("mips64el", _) => "mips64el_mips64r2",
+        ("powerpc", _) => "powerpc_8548",
+        ("powerpc64", _) => "powerpc64_e5500",
+        ("loongarch64", _) => "loongarch64_generic",
+        (risc, _) if risc.starts_with("riscv64") => "riscv64_riscv64",
+        (arch, _) => arch,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn architectures() {
+        assert_eq!(openwrt_architecture_from_rust_triple("x86_64-unknown-linux-musl"), "x86_64");
+        assert_eq!(openwrt_architecture_from_rust_triple("aarch64-unknown-linux-musl"), "aarch64_generic");
+        assert_eq!(openwrt_architecture_from_rust_triple("armv7-unknown-linux-musleabihf"), "arm_cortex-a7_neon-vfpv4");
+        assert_eq!(openwrt_architecture_from_rust_triple("arm-unknown-linux-musleabihf"), "arm_arm1176jzf-s_vfp");
+        assert_eq!(openwrt_architecture_from_rust_triple("armv5te-unknown-linux-musleabi"), "arm_arm926ej-s");
+        assert_eq!(openwrt_architecture_from_rust_triple("mipsel-unknown-linux-musl"), "mipsel_24kc");
+        assert_eq!(openwrt_architecture_from_rust_triple("riscv64gc-unknown-linux-musl"), "riscv64_riscv64");
+    }
+
+    #[test]
+    fn control_fields() {
+        let control = "Package: foo\nVersion: 1.0-1\nArchitecture: arm64\nMulti-Arch: same\nHomepage: https://example.com\n\
+            Priority: optional\nDepends: libc6 (>= 2.28)\nBreaks: bar\nDescription: Foo\n Extended\n .\n text\n\n";
+        let control = ControlParagraph::parse(control).unwrap();
+        assert_eq!(opkg_control(control, "aarch64_generic", Some("MIT")).unwrap(),
+            "Package: foo\nVersion: 1.0-1\nArchitecture: aarch64_generic\nPriority: optional\nDepends: libc6 (>= 2.28)\n\
+            License: MIT\nDescription: Foo\n Extended\n .\n text\n");
+    }
+
+    #[test]
+    fn auto_depends() {
+        assert!(has_auto_depends("$auto").unwrap());
+        assert!(has_auto_depends("libfoo (>= 1), $auto").unwrap());
+        assert!(!has_auto_depends("libfoo (>= 1), busybox").unwrap());
+        assert!(!has_auto_depends("").unwrap());
+    }
+}
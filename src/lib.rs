@@ -1,4 +1,4 @@
-#![recursion_limit = "128"]
+#![recursion_limit = "256"]
 #![allow(clippy::case_sensitive_file_extension_comparisons)]
 #![allow(clippy::if_not_else)]
 #![allow(clippy::missing_errors_doc)]
@@ -29,6 +29,8 @@ The library interface is experimental. See `main.rs` for usage.
 pub mod deb {
     pub mod ar;
     pub mod control;
+    pub mod reader;
+    pub mod sums;
     pub mod tar;
 }
 #[macro_use]
@@ -46,21 +48,29 @@ pub use crate::config::{BuildEnvironment, BuildProfile, DebugSymbols, PackageCon
 pub use crate::deb::ar::DebArchive;
 pub use crate::error::*;
 pub use crate::util::compress;
-use crate::util::compress::{CompressConfig, Format};
+use crate::util::compress::{CompressConfig, CompressionOptions, Format};
 
 pub mod assets;
 pub mod config;
+pub mod deb822;
 mod debuginfo;
 mod dependencies;
 mod error;
+mod ipk;
+mod oci;
+pub mod relation;
+mod rpm;
 pub use debuginfo::strip_binaries;
 
-use crate::assets::{apply_compressed_assets, compressed_assets};
+use crate::assets::{apply_compressed_assets, compressed_assets, link_duplicate_assets};
 use crate::deb::control::ControlArchiveBuilder;
 use crate::deb::tar::Tarball;
 use crate::listener::{Listener, PrefixedListener};
 use config::BuildOptions;
 use rayon::prelude::*;
+use std::borrow::Cow;
+use crate::deb::sums::FileSums;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::{env, fs};
@@ -82,8 +92,40 @@ pub struct CargoDeb<'tmp> {
     pub compress_config: CompressConfig,
     /// User-configured output path for *.deb
     pub deb_output: Option<OutputPath<'tmp>>,
+    /// Write the package's files and `DEBIAN/` into this directory.
+    /// The .deb is made only if `deb_output` is set too.
+    pub stage_dir: Option<&'tmp Path>,
+    /// Write an OCI image layout with the package's files into this directory.
+    /// The .deb is made only if `deb_output` is set too.
+    pub oci_layout: Option<&'tmp Path>,
+    /// Kind of package to make
+    pub package_format: PackageFormat,
     /// Run dpkg -i; run for dbsym
     pub install: (bool, bool),
+    /// Print what would be packaged to stdout, instead of making the deb
+    pub dry_run: bool,
+    /// Pieces printed by `dry_run`: `control`, `conffiles`, `assets`, or names of maintainer scripts.
+    /// Empty means all of them.
+    pub emit: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PackageFormat {
+    #[default]
+    Deb,
+    /// opkg package for OpenWrt
+    Ipk,
+    /// RPM v4 package for Fedora, RHEL, openSUSE, etc.
+    Rpm,
+}
+
+/// Written instead of the .deb, or in addition to it if `make_deb`
+#[derive(Clone, Copy)]
+struct OtherOutputs<'tmp> {
+    stage_dir: Option<&'tmp Path>,
+    oci_layout: Option<&'tmp Path>,
+    make_deb: bool,
+    format: PackageFormat,
 }
 
 pub struct OutputPath<'tmp> {
@@ -102,6 +144,18 @@ impl CargoDeb<'_> {
         }
         let asked_for_dbgsym_package = self.options.debug.generate_dbgsym_package.unwrap_or(false);
         let single_target_needs_back_compat = self.deb_output.is_none() && self.options.rust_target_triples.len() == 1;
+        let other_outputs = OtherOutputs {
+            stage_dir: self.stage_dir,
+            oci_layout: self.oci_layout,
+            make_deb: (self.stage_dir.is_none() && self.oci_layout.is_none()) || self.deb_output.is_some(),
+            format: self.package_format,
+        };
+        if self.install.0 && self.package_format != PackageFormat::Deb {
+            return Err(CargoDebError::Str("--install can't be used with ipk or rpm packages"));
+        }
+        if (self.stage_dir.is_some() || self.oci_layout.is_some()) && self.options.rust_target_triples.len() > 1 {
+            return Err(CargoDebError::Str("--stage-dir and --oci-layout can't be used with multiple targets"));
+        }
 
         // The profile is selected based on the given ClI options and then passed to
         // cargo build accordingly. you could argue that the other way around is
@@ -140,11 +194,14 @@ impl CargoDeb<'_> {
                 listener = &tmp_listener;
             }
 
-            Self::process_package(package_deb, &config, listener, &self.compress_config, &output, self.install, asked_for_dbgsym_package, single_target_needs_back_compat)
+            if self.dry_run {
+                return Self::print_package_plan(package_deb, &config, &self.emit, listener);
+            }
+            Self::process_package(package_deb, &config, listener, &self.compress_config, &output, other_outputs, self.install, asked_for_dbgsym_package, single_target_needs_back_compat)
         })
     }
 
-    fn process_package(mut package_deb: PackageConfig, config: &BuildEnvironment, listener: &dyn Listener, compress_config: &CompressConfig, output: &OutputPath<'_>, (install, install_dbgsym): (bool, bool), asked_for_dbgsym_package: bool, needs_back_compat: bool) -> CDResult<()> {
+    fn process_package(mut package_deb: PackageConfig, config: &BuildEnvironment, listener: &dyn Listener, compress_config: &CompressConfig, output: &OutputPath<'_>, other_outputs: OtherOutputs<'_>, (install, install_dbgsym): (bool, bool), asked_for_dbgsym_package: bool, needs_back_compat: bool) -> CDResult<()> {
         package_deb.resolve_assets(listener)?;
 
         let (depends, compressed_assets) = rayon::join(
@@ -154,6 +211,9 @@ impl CargoDeb<'_> {
 
         debug_assert!(package_deb.resolved_depends.is_none());
         package_deb.resolved_depends = Some(depends?);
+        if package_deb.cargo_built_using {
+            package_deb.resolved_cargo_built_using = Some(config.cargo_built_using(&package_deb)?);
+        }
         apply_compressed_assets(&mut package_deb, compressed_assets?);
 
         strip_binaries(config, &mut package_deb, asked_for_dbgsym_package, listener)?;
@@ -165,19 +225,47 @@ impl CargoDeb<'_> {
             listener.warning("No debug symbols found. Skipping dbgsym.ddeb".into());
         }
 
+        let entrypoint = other_outputs.oci_layout.and_then(|_| oci::entrypoint(&package_deb.assets.resolved));
+        // All outputs use the same sorted and linked assets, so the files are hashed only once
+        package_deb.sort_assets_by_type();
+        link_duplicate_assets(&mut package_deb, listener)?;
+
+        if other_outputs.stage_dir.is_some() || other_outputs.oci_layout.is_some() {
+            if let Some(stage_dir) = other_outputs.stage_dir {
+                stage_deb(config, stage_dir, &package_deb, listener)?;
+            }
+            if let Some(oci_layout) = other_outputs.oci_layout {
+                oci::write_image_layout(config, oci_layout, &package_deb, entrypoint.as_deref(), compress_config, listener)?;
+            }
+            if !other_outputs.make_deb {
+                return Ok(());
+            }
+        }
+
+        if other_outputs.format != PackageFormat::Deb {
+            if package_dbgsym_ddeb.is_some() {
+                listener.warning("dbgsym packages can only be made in the deb format. Skipping dbgsym".into());
+            }
+            let generated = if other_outputs.format == PackageFormat::Rpm {
+                rpm::write_rpm(config, rpm::rpm_output_path(&package_deb, output), &package_deb, compress_config, listener)?
+            } else {
+                ipk::write_ipk(config, ipk::ipk_output_path(&package_deb, output), &package_deb, compress_config, listener)?
+            };
+            listener.generated_archive(&generated);
+            return Ok(());
+        }
+
         let (generated_deb, generated_dbgsym_ddeb) = rayon::join(
-            || {
-                package_deb.sort_assets_by_type();
-                write_deb(
-                    config,
-                    package_deb.deb_output_path(output),
-                    &package_deb,
-                    compress_config,
-                    listener,
-                )
-            },
+            || write_deb(
+                config,
+                package_deb.deb_output_path(output),
+                &package_deb,
+                compress_config,
+                listener,
+            ),
             || package_dbgsym_ddeb.map(|mut ddeb| {
                 ddeb.sort_assets_by_type();
+                link_duplicate_assets(&mut ddeb, listener)?;
                 write_deb(
                     config,
                     ddeb.deb_output_path(output),
@@ -207,6 +295,60 @@ impl CargoDeb<'_> {
         Ok(())
     }
 
+    /// Resolves everything like `process_package`, but prints the result instead of stripping and archiving
+    fn print_package_plan(mut package_deb: PackageConfig, config: &BuildEnvironment, emit: &[String], listener: &dyn Listener) -> CDResult<()> {
+        package_deb.resolve_assets(listener)?;
+
+        let (depends, compressed_assets) = rayon::join(
+            || package_deb.resolved_binary_dependencies(listener),
+            || compressed_assets(&package_deb, listener),
+        );
+        package_deb.resolved_depends = Some(depends?);
+        if package_deb.cargo_built_using {
+            package_deb.resolved_cargo_built_using = Some(config.cargo_built_using(&package_deb)?);
+        }
+        apply_compressed_assets(&mut package_deb, compressed_assets?);
+        package_deb.sort_assets_by_type();
+        link_duplicate_assets(&mut package_deb, listener)?;
+
+        // Binaries aren't stripped in a dry run, so their checksums would be wrong.
+        // Without sums, md5sums and sha256sums are left out of the plan.
+        let mut control_builder = ControlArchiveBuilder::new(Vec::new(), package_deb.default_timestamp, listener)
+            .dpkg_deb_compatible(package_deb.dpkg_deb_compat);
+        control_builder.generate_archive(config, &package_deb, &FileSums::default())?;
+        let mut control_files = Vec::new();
+        deb::reader::read_control_tarball(&control_builder.finish()?[..], &mut control_files)?;
+
+        let cwd = env::current_dir().unwrap_or_default();
+        let mut pieces: Vec<(&str, Cow<'_, [u8]>)> = control_files.iter()
+            .map(|f| (f.name.as_str(), Cow::Borrowed(&f.data[..])))
+            .collect();
+        pieces.push(("assets", Cow::Owned(assets::asset_table(&package_deb.assets.resolved, &cwd).into_bytes())));
+
+        if !emit.is_empty() {
+            for name in emit {
+                if !pieces.iter().any(|(n, _)| n == name) {
+                    listener.warning(format!("{name} is not in the {} package", package_deb.deb_name));
+                }
+            }
+            pieces.retain(|(name, _)| emit.iter().any(|e| e == name));
+        }
+
+        // Headers are only needed to tell multiple pieces apart
+        let with_headers = pieces.len() > 1;
+        let mut out = std::io::stdout().lock();
+        for (name, data) in pieces {
+            if with_headers {
+                writeln!(out, "==> {name} <==")?;
+            }
+            out.write_all(&data)?;
+            if with_headers && !data.ends_with(b"\n") {
+                writeln!(out)?;
+            }
+        }
+        Ok(())
+    }
+
     /// given [a-linux-gnu, b-linux gnu] return len to strip for [a, b]
     fn rust_target_triple_common_suffix_len(package_debs: &[PackageConfig]) -> usize {
         if package_debs.len() < 2 {
@@ -258,14 +400,21 @@ impl Default for CargoDeb<'_> {
             options: BuildOptions::default(),
             no_build: false,
             deb_output: None,
+            stage_dir: None,
+            oci_layout: None,
+            package_format: PackageFormat::Deb,
             verbose: false,
             verbose_cargo_build: false,
             install: (false, false),
+            dry_run: false,
+            emit: Vec::new(),
             compress_config: CompressConfig {
                 fast: false,
-                compress_type: Format::Xz,
+                compress_type: None,
+                control_compress_type: None,
                 compress_system: false,
                 rsyncable: false,
+                options: CompressionOptions::default(),
             },
         }
     }
@@ -301,30 +450,32 @@ fn install_debs_inner(paths: &[&Path], no_sudo: bool) -> CDResult<()> {
     Ok(())
 }
 
-pub fn write_deb(config: &BuildEnvironment, deb_output_path: PathBuf, package_deb: &PackageConfig, &CompressConfig { fast, compress_type, compress_system, rsyncable }: &CompressConfig, listener: &dyn Listener) -> Result<PathBuf, CargoDebError> {
-    let (deb_contents, data_result) = rayon::join(
-        move || {
-            // The control archive is the metadata for the package manager
-            let mut control_builder = ControlArchiveBuilder::new(util::compress::select_compressor(fast, compress_type, compress_system)?, package_deb.default_timestamp, listener);
-            control_builder.generate_archive(config, package_deb)?;
-            let control_compressed = control_builder.finish()?.finish()?;
-
-            let mut deb_contents = DebArchive::new(deb_output_path, package_deb.default_timestamp)?;
-            let compressed_control_size = control_compressed.len();
-            deb_contents.add_control(control_compressed)?;
-            Ok::<_, CargoDebError>((deb_contents, compressed_control_size))
-        },
-        move || {
-            // Initialize the contents of the data archive (files that go into the filesystem).
-            let dest = util::compress::select_compressor(fast, compress_type, compress_system)?;
-            let archive = Tarball::new(dest, package_deb.default_timestamp);
-            let compressed = archive.archive_files(package_deb, rsyncable, listener)?;
-            let original_data_size = compressed.uncompressed_size;
-            Ok::<_, CargoDebError>((compressed.finish()?, original_data_size))
-        },
-    );
-    let (mut deb_contents, compressed_control_size) = deb_contents?;
-    let (data_compressed, original_data_size) = data_result?;
+pub fn write_deb(config: &BuildEnvironment, deb_output_path: PathBuf, package_deb: &PackageConfig, &CompressConfig { fast, compress_type, control_compress_type, compress_system, rsyncable, options }: &CompressConfig, listener: &dyn Listener) -> Result<PathBuf, CargoDebError> {
+    // Command-line options take precedence over Cargo.toml
+    let compress_type = compress_type.or(package_deb.compress_type).unwrap_or(Format::Xz);
+    let options = options.or_manifest(package_deb.compress_options, package_deb.compress_type.unwrap_or(Format::Xz), compress_type, fast);
+    let control_compress_type = control_compress_type.or(package_deb.control_compress_type).unwrap_or(compress_type);
+    // The tuning options are meant for the data, and may not even be valid for a different format
+    let control_options = if control_compress_type == compress_type { options } else { CompressionOptions::default() };
+    // Compressed tarballs are spooled to disk, so the package size isn't limited by available memory
+    let deb_temp_dir = config.deb_temp_dir(package_deb);
+    // Initialize the contents of the data archive (files that go into the filesystem).
+    // It's made first, because the control archive needs checksums of the files.
+    let dest = util::compress::select_compressor(fast, compress_type, &options, compress_system, rsyncable, &deb_temp_dir)?;
+    let archive = Tarball::new(dest, package_deb.default_timestamp).dpkg_deb_compatible(package_deb.dpkg_deb_compat);
+    let (compressed, sums) = archive.archive_files(package_deb, rsyncable, listener)?;
+    let original_data_size = compressed.uncompressed_size as u64;
+    let data_compressed = compressed.finish()?;
+
+    // The control archive is the metadata for the package manager
+    let mut control_builder = ControlArchiveBuilder::new(util::compress::select_compressor(fast, control_compress_type, &control_options, compress_system, false, &deb_temp_dir)?, package_deb.default_timestamp, listener)
+        .dpkg_deb_compatible(package_deb.dpkg_deb_compat);
+    control_builder.generate_archive(config, package_deb, &sums)?;
+    let control_compressed = control_builder.finish()?.finish()?;
+
+    let mut deb_contents = DebArchive::new(deb_output_path, package_deb.default_timestamp)?;
+    let compressed_control_size = control_compressed.len();
+    deb_contents.add_control(control_compressed)?;
 
     let compressed_size = data_compressed.len() + compressed_control_size;
     let original_size = original_data_size + compressed_control_size; // doesn't track control size
@@ -337,12 +488,62 @@ pub fn write_deb(config: &BuildEnvironment, deb_output_path: PathBuf, package_de
     deb_contents.add_data(data_compressed)?;
     let generated = deb_contents.finish()?;
 
-    let deb_temp_dir = config.deb_temp_dir(package_deb);
     let _ = fs::remove_dir(&deb_temp_dir);
 
     Ok(generated)
 }
 
+/// Writes the files of the package into `dest_dir`, and the control files into its `DEBIAN/` subdirectory,
+/// which is the layout `dpkg-deb --build` expects. File owners aren't applied.
+pub fn stage_deb(config: &BuildEnvironment, dest_dir: &Path, package_deb: &PackageConfig, listener: &dyn Listener) -> CDResult<()> {
+    if fs::read_dir(dest_dir).is_ok_and(|mut dir| dir.next().is_some()) {
+        return Err(CargoDebError::OutputDirNotEmpty(dest_dir.into()));
+    }
+    fs::create_dir_all(dest_dir)
+        .map_err(|e| CargoDebError::IoFile("Can't create the staging directory", e, dest_dir.into()))?;
+
+    // Unpacks the same tarball that would go into the .deb, so that nothing differs
+    let deb_temp_dir = config.deb_temp_dir(package_deb);
+    let dest = util::compress::select_compressor(true, Format::None, &CompressionOptions::default(), false, false, &deb_temp_dir)?;
+    let archive = Tarball::new(dest, package_deb.default_timestamp).dpkg_deb_compatible(package_deb.dpkg_deb_compat);
+    let (data, sums) = archive.archive_files(package_deb, false, listener)?;
+    let mut data = tar::Archive::new(data.finish()?);
+    data.set_preserve_permissions(true);
+    data.set_preserve_mtime(true);
+    data.unpack(dest_dir)
+        .map_err(|e| CargoDebError::IoFile("Can't unpack the package files", e, dest_dir.into()))?;
+    let _ = fs::remove_dir(&deb_temp_dir);
+
+    let mut control_builder = ControlArchiveBuilder::new(Vec::new(), package_deb.default_timestamp, listener);
+    control_builder.generate_archive(config, package_deb, &sums)?;
+    let mut control_files = Vec::new();
+    deb::reader::read_control_tarball(&control_builder.finish()?[..], &mut control_files)?;
+
+    let control_dir = dest_dir.join("DEBIAN");
+    fs::create_dir(&control_dir)
+        .map_err(|e| CargoDebError::IoFile("Can't create the control directory", e, control_dir.clone()))?;
+    set_mode(&control_dir, 0o755)?;
+    for file in control_files {
+        let path = control_dir.join(&file.name);
+        fs::write(&path, &file.data).map_err(|e| CargoDebError::IoFile("Can't write control file", e, path.clone()))?;
+        set_mode(&path, file.mode)?;
+    }
+    listener.progress("Staged", dest_dir.display().to_string());
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> CDResult<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+        .map_err(|e| CargoDebError::IoFile("Can't set permissions", e, path.into()))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_: &Path, _: u32) -> CDResult<()> {
+    Ok(())
+}
+
 // Maps Rust's blah-unknown-linux-blah to Debian's blah-linux-blah. This is debian's multiarch.
 fn debian_triple_from_rust_triple(rust_target_triple: &str) -> String {
     let mut p = rust_target_triple.split('-');
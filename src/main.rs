@@ -1,9 +1,11 @@
 use anstream::{AutoStream, ColorChoice};
-use cargo_deb::compress::{CompressConfig, Format};
+use cargo_deb::compress::{CompressConfig, CompressionOptions, Format};
 use cargo_deb::config::{BuildOptions, CompressDebugSymbols, DebugSymbolOptions, Multiarch};
-use cargo_deb::{listener, BuildProfile, CargoDeb, CargoLockingFlags, OutputPath};
+use cargo_deb::deb::reader::{DataFileKind, DebReader};
+use cargo_deb::{listener, BuildProfile, CDResult, CargoDeb, CargoLockingFlags, OutputPath, PackageFormat};
 use clap::{Arg, ArgAction, Command};
 use std::env;
+use std::io::Write;
 use std::path::Path;
 use std::process::ExitCode;
 
@@ -23,7 +25,17 @@ fn main() -> ExitCode {
             .hide_possible_values(true)
             .default_value("none").value_name("same|foreign"))
         .arg(Arg::new("profile").long("profile").help("Select which Cargo build profile to use").num_args(1).value_name("release|<custom>"))
+        .arg(Arg::new("format").long("format").num_args(1).value_parser(["deb", "ipk", "rpm"]).default_value("deb")
+            .hide_short_help(true).help("Make a .deb, an .ipk for OpenWrt's opkg, or an .rpm"))
+        .arg(Arg::new("stage-dir").long("stage-dir").num_args(1).value_name("dir").conflicts_with_all(["install", "dry-run", "emit"])
+            .help("Write the files to be packaged and DEBIAN/ into an empty directory. Makes a .deb only if --output is set too"))
+        .arg(Arg::new("oci-layout").long("oci-layout").num_args(1).value_name("dir").conflicts_with_all(["install", "dry-run", "emit"])
+            .help("Write an OCI container image with the files to be packaged into an empty directory. Makes a .deb only if --output is set too"))
         .arg(Arg::new("install").long("install").action(ArgAction::SetTrue).help("Immediately install the created deb package"))
+        .arg(Arg::new("dry-run").long("dry-run").action(ArgAction::SetTrue).conflicts_with("install")
+            .help("Print the control file, maintainer scripts, and assets instead of making the deb"))
+        .arg(Arg::new("emit").long("emit").num_args(1).value_delimiter(',').action(ArgAction::Append).value_name("control,postinst,assets").conflicts_with("install")
+            .hide_short_help(true).help("Print only the given parts of the package. Implies --dry-run"))
         .arg(Arg::new("no-install-dbgsym").long("no-install-dbgsym").action(ArgAction::SetTrue).requires("install").requires("dbgsym")
             .hide_short_help(true).help("Immediately install the created deb package, but without dbgsym package"))
         .arg(Arg::new("quiet").short('q').long("quiet").action(ArgAction::SetTrue).help("Don't print warnings"))
@@ -56,6 +68,10 @@ fn main() -> ExitCode {
         .arg(Arg::new("maintainer").long("maintainer").num_args(1).value_name("name").help("Override Maintainer field"))
         .arg(Arg::new("section").long("section").num_args(1).value_name("section")
             .hide_short_help(true).help("Set the application category for this package"))
+        .arg(Arg::new("udeb").long("udeb").action(ArgAction::SetTrue)
+            .hide_short_help(true).help("Make a .udeb for debian-installer, without docs or copyright"))
+        .arg(Arg::new("field").long("field").num_args(1).action(ArgAction::Append).value_name("Name=value").value_parser(parse_control_field)
+            .hide_short_help(true).help("Add a custom field to the control file, or override one from extra-fields. Empty value removes it"))
         .next_help_heading("Build overrides")
         .arg(Arg::new("no-build").long("no-build").action(ArgAction::SetTrue)
             .hide_short_help(true).help("Assume the project is already built. Use for complex projects that require non-Cargo build commands"))
@@ -68,12 +84,30 @@ fn main() -> ExitCode {
         .next_help_heading("Deb compression")
         .arg(Arg::new("fast").long("fast").action(ArgAction::SetTrue)
             .help("Use faster compression, which makes a larger deb file"))
-        .arg(Arg::new("compress-type").short('Z').long("compress-type").num_args(1).value_name("gz|xz").value_parser(["xz", "gz", "gzip"]).default_value("xz")
-            .help("Compress with the given compression format").hide_possible_values(true))
+        .arg(Arg::new("compress-type").short('Z').long("compress-type").num_args(1).value_name("gz|xz|zstd|none").value_parser(["xz", "gz", "gzip", "zstd", "zst", "none"])
+            .help("Compress with the given compression format [default: xz]").hide_possible_values(true))
+        .arg(Arg::new("control-compress-type").long("control-compress-type").num_args(1).value_name("gz|xz|zstd|none").value_parser(["xz", "gz", "gzip", "zstd", "zst", "none"])
+            .hide_short_help(true).help("Compress control.tar with a different format than data.tar, e.g. gz for old tools").hide_possible_values(true))
+        .arg(Arg::new("compress-level").long("compress-level").num_args(1).value_name("num").value_parser(clap::value_parser!(u32).range(0..=22))
+            .help("Compression level of the format, e.g. 0-9 for xz. Overrides --fast"))
+        .arg(Arg::new("compress-extreme").long("compress-extreme").action(ArgAction::SetTrue).hide_short_help(true)
+            .help("Use xz's --extreme mode, which is slower, but may compress better"))
+        .arg(Arg::new("compress-threads").long("compress-threads").num_args(1).value_name("num").value_parser(clap::value_parser!(u32)).hide_short_help(true)
+            .help("Number of threads for xz, zstd, and gzip compression [default: all cores]"))
+        .arg(Arg::new("compress-memlimit").long("compress-memlimit").num_args(1).value_name("size").hide_short_help(true)
+            .value_parser(|s: &str| cargo_deb::compress::parse_byte_size(s).ok_or("expected a size like 512MiB"))
+            .help("Memory usage limit for xz compression, reduces the number of threads to fit"))
         .arg(Arg::new("compress-system").long("compress-system").alias("system-xz").action(ArgAction::SetTrue)
             .help("Use the corresponding command-line tool for compression"))
         .arg(Arg::new("rsyncable").long("rsyncable").action(ArgAction::SetTrue).hide_short_help(true)
             .help("Use worse compression, but reduce differences between versions of packages"))
+        .arg(Arg::new("dpkg-deb-compat").long("dpkg-deb-compat").action(ArgAction::SetTrue).hide_short_help(true)
+            .help("Write tarballs with the same entry order and headers as `dpkg-deb --build --root-owner-group`"))
+        .next_help_heading("Existing packages")
+        .arg(Arg::new("inspect").long("inspect").num_args(1).value_name("file.deb").conflicts_with("extract")
+            .help("Print control fields, maintainer scripts, conffiles, and the list of files of a .deb"))
+        .arg(Arg::new("extract").long("extract").num_args(2).value_names(["file.deb", "dir"])
+            .help("Unpack the files of a .deb into a directory, like dpkg-deb --extract"))
         .next_help_heading("Cargo")
         .arg(Arg::new("features").short('F').long("features").num_args(1).value_name("list").help("Can also be set in Cargo.toml `[package.metadata.deb]`"))
         .arg(Arg::new("no-default-features").long("no-default-features").action(ArgAction::SetTrue).help("Can also be set in Cargo.toml `[package.metadata.deb]`"))
@@ -95,11 +129,8 @@ fn main() -> ExitCode {
         logger.init();
     }
 
-    let compress_type = match matches.get_one::<String>("compress-type").map(|s| s.as_str()) {
-        Some("gz" | "gzip") => Format::Gzip,
-        Some("xz") | None => Format::Xz,
-        _ => Format::Xz,
-    };
+    let compress_type = matches.get_one::<String>("compress-type").and_then(|s| Format::from_name(s));
+    let control_compress_type = matches.get_one::<String>("control-compress-type").and_then(|s| Format::from_name(s));
 
     let multiarch = match matches.get_one::<String>("multiarch").map_or("none", |s| s.as_str()) {
         "same" => Multiarch::Same,
@@ -127,6 +158,14 @@ fn main() -> ExitCode {
         verbose, quiet, color,
     };
 
+    if let Some(deb_path) = matches.get_one::<String>("inspect") {
+        return exit_code(inspect(Path::new(deb_path)), listener);
+    }
+    if let Some(mut args) = matches.get_many::<String>("extract") {
+        let (deb_path, dest_dir) = (args.next().unwrap(), args.next().unwrap());
+        return exit_code(DebReader::open(deb_path).and_then(|deb| deb.extract(Path::new(dest_dir))), listener);
+    }
+
     let deb_version = matches.get_one::<String>("deb-version").cloned();
     let deb_revision = matches.get_one::<String>("deb-revision").cloned();
 
@@ -170,13 +209,29 @@ fn main() -> ExitCode {
         verbose,
         verbose_cargo_build,
         install: (install, !matches.get_flag("no-install-dbgsym")),
+        dry_run: matches.get_flag("dry-run") || matches.contains_id("emit"),
+        emit: matches.get_many::<String>("emit").unwrap_or_default().cloned().collect(),
         deb_output,
+        stage_dir: matches.get_one::<String>("stage-dir").map(Path::new),
+        oci_layout: matches.get_one::<String>("oci-layout").map(Path::new),
+        package_format: match matches.get_one::<String>("format").map(String::as_str) {
+            Some("ipk") => PackageFormat::Ipk,
+            Some("rpm") => PackageFormat::Rpm,
+            _ => PackageFormat::Deb,
+        },
         compress_config: CompressConfig {
             // when installing locally it won't be transferred anywhere, so allow faster compression
             fast: install || matches.get_flag("fast"),
             compress_type,
+            control_compress_type,
             compress_system: matches.get_flag("compress-system"),
             rsyncable: matches.get_flag("rsyncable"),
+            options: CompressionOptions {
+                level: matches.get_one::<u32>("compress-level").copied(),
+                extreme: matches.get_flag("compress-extreme").then_some(true),
+                threads: matches.get_one::<u32>("compress-threads").copied(),
+                memlimit: matches.get_one::<u64>("compress-memlimit").copied(),
+            },
         },
         options: BuildOptions {
             config_variant: matches.get_one::<String>("variant").map(|x| x.as_str()),
@@ -204,6 +259,9 @@ fn main() -> ExitCode {
                 tmp.features = matches.get_many::<String>("features").unwrap_or_default().cloned().collect();
                 tmp.no_default_features = matches.get_flag("no-default-features");
                 tmp.all_features = matches.get_flag("all-features");
+                tmp.dpkg_deb_compat = matches.get_flag("dpkg-deb-compat");
+                tmp.udeb = matches.get_flag("udeb");
+                tmp.extra_fields = matches.get_many::<(String, String)>("field").unwrap_or_default().cloned().collect();
                 tmp
             },
             build_profile: BuildProfile {
@@ -225,3 +283,52 @@ fn main() -> ExitCode {
         },
     }
 }
+
+fn exit_code(res: CDResult<()>, listener: &dyn listener::Listener) -> ExitCode {
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            listener.error(&err);
+            ExitCode::FAILURE
+        },
+    }
+}
+
+/// `--field Name=value`, validated later together with `extra-fields`
+fn parse_control_field(arg: &str) -> Result<(String, String), String> {
+    let (name, value) = arg.split_once('=').ok_or_else(|| format!("expected Name=value, got '{arg}'"))?;
+    Ok((name.trim().to_owned(), value.to_owned()))
+}
+
+/// Similar to `dpkg-deb --info` and `--contents` combined
+fn inspect(deb_path: &Path) -> CDResult<()> {
+    let deb = DebReader::open(deb_path)?;
+    let mut out = std::io::stdout().lock();
+    if let Some(control) = deb.control_file("control") {
+        writeln!(out, "{}", String::from_utf8_lossy(&control.data).trim_end())?;
+    }
+    let scripts = deb.maintainer_scripts().map(|s| s.name.as_str()).collect::<Vec<_>>();
+    if !scripts.is_empty() {
+        writeln!(out, "\nMaintainer scripts: {}", scripts.join(", "))?;
+    }
+    let conffiles = deb.conffiles();
+    if !conffiles.is_empty() {
+        writeln!(out, "\nConffiles:")?;
+        for file in conffiles {
+            writeln!(out, " {file}")?;
+        }
+    }
+    writeln!(out, "\nFiles:")?;
+    for file in deb.data_files()? {
+        let owner = file.user.clone().unwrap_or_else(|| file.uid.to_string());
+        let group = file.group.clone().unwrap_or_else(|| file.gid.to_string());
+        let slash = if file.kind == DataFileKind::Dir { "/" } else { "" };
+        write!(out, "{} {owner}/{group} {:>10} ./{}{slash}", file.mode_string(), file.size, file.path.display())?;
+        match &file.kind {
+            DataFileKind::Symlink(target) => writeln!(out, " -> {}", target.display())?,
+            DataFileKind::Hardlink(target) => writeln!(out, " link to ./{}", target.display())?,
+            _ => writeln!(out)?,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,219 @@
+//! [OCI image layout](https://github.com/opencontainers/image-spec/blob/main/image-layout.md)
+//! with the package's files as the only layer
+
+use crate::assets::Asset;
+use crate::config::{BuildEnvironment, PackageConfig};
+use crate::deb::tar::Tarball;
+use crate::error::{CDResult, CargoDebError};
+use crate::listener::Listener;
+use crate::util::compress::{self, CompressConfig, Format};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const MANIFEST_MEDIA_TYPE: &str = "application/vnd.oci.image.manifest.v1+json";
+const CONFIG_MEDIA_TYPE: &str = "application/vnd.oci.image.config.v1+json";
+
+/// The binary to run, if the package has any in `usr/bin`. Assets must not be sorted yet,
+/// so that it's the first one listed in `Cargo.toml`.
+pub(crate) fn entrypoint(assets: &[Asset]) -> Option<PathBuf> {
+    assets.iter()
+        .find(|a| a.c.target_path.parent() == Some(Path::new("usr/bin")) && a.c.is_executable())
+        .map(|a| Path::new("/").join(&a.c.target_path))
+}
+
+/// Writes `oci-layout`, `index.json`, and `blobs/` into `dest_dir`, which must be empty.
+///
+/// The layer is compressed with gzip, unless `none` or `zstd` format is selected.
+pub(crate) fn write_image_layout(config: &BuildEnvironment, dest_dir: &Path, package_deb: &PackageConfig, entrypoint: Option<&Path>, compress_config: &CompressConfig, listener: &dyn Listener) -> CDResult<()> {
+    let (format, media_type) = match compress_config.compress_type.or(package_deb.compress_type).unwrap_or(Format::Gzip) {
+        Format::Gzip => (Format::Gzip, "application/vnd.oci.image.layer.v1.tar+gzip"),
+        Format::Zstd => (Format::Zstd, "application/vnd.oci.image.layer.v1.tar+zstd"),
+        Format::None => (Format::None, "application/vnd.oci.image.layer.v1.tar"),
+        Format::Xz => return Err(CargoDebError::InvalidCompression("OCI image layers can't use xz, only gz, zstd, or none".into())),
+    };
+    if fs::read_dir(dest_dir).is_ok_and(|mut dir| dir.next().is_some()) {
+        return Err(CargoDebError::OutputDirNotEmpty(dest_dir.into()));
+    }
+    let blobs_dir = dest_dir.join("blobs/sha256");
+    fs::create_dir_all(&blobs_dir)
+        .map_err(|e| CargoDebError::IoFile("Can't create the image directory", e, blobs_dir.clone()))?;
+
+    // the config needs a digest of the uncompressed layer, and the manifest of the compressed one
+    let options = compress_config.options.or_manifest(package_deb.compress_options, package_deb.compress_type.unwrap_or(Format::Xz), format, compress_config.fast);
+    let dest = compress::select_compressor(compress_config.fast, format, &options, compress_config.compress_system, false, &config.deb_temp_dir(package_deb))?;
+    let archive = Tarball::new(HashingWriter { inner: dest, hasher: Sha256::new() }, package_deb.default_timestamp);
+    let (layer, _) = archive.archive_files(package_deb, false, listener)?;
+    let diff_id = hex_digest(layer.hasher);
+    let mut layer = layer.inner.finish()?;
+    let layer_size = layer.len();
+    let tmp_path = blobs_dir.join("layer.tmp");
+    let mut hashed = HashingWriter { inner: create(&tmp_path)?, hasher: Sha256::new() };
+    io::copy(&mut layer, &mut hashed).map_err(|e| CargoDebError::IoFile("Can't write the image layer", e, tmp_path.clone()))?;
+    let layer_digest = hex_digest(hashed.hasher);
+    let layer_path = blobs_dir.join(&layer_digest);
+    fs::rename(&tmp_path, &layer_path).map_err(|e| CargoDebError::IoFile("Can't write the image layer", e, layer_path))?;
+
+    let created = rfc3339(package_deb.default_timestamp);
+    let (architecture, variant) = oci_platform(&package_deb.architecture);
+    let mut image_config = json!({
+        "created": created,
+        "architecture": architecture,
+        "os": "linux",
+        "config": {
+            "Labels": {
+                "org.opencontainers.image.title": package_deb.deb_name,
+                "org.opencontainers.image.version": package_deb.deb_version,
+            },
+        },
+        "rootfs": {
+            "type": "layers",
+            "diff_ids": [format!("sha256:{diff_id}")],
+        },
+        "history": [{
+            "created": created,
+            "created_by": concat!("cargo-deb ", env!("CARGO_PKG_VERSION")),
+        }],
+    });
+    if let Some(variant) = variant {
+        image_config["variant"] = variant.into();
+    }
+    if let Some(entrypoint) = entrypoint {
+        image_config["config"]["Entrypoint"] = json!([entrypoint]);
+    }
+    let config_descriptor = write_blob(&blobs_dir, CONFIG_MEDIA_TYPE, &image_config)?;
+
+    let manifest = json!({
+        "schemaVersion": 2,
+        "mediaType": MANIFEST_MEDIA_TYPE,
+        "config": config_descriptor,
+        "layers": [{
+            "mediaType": media_type,
+            "digest": format!("sha256:{layer_digest}"),
+            "size": layer_size,
+        }],
+    });
+    let mut manifest_descriptor = write_blob(&blobs_dir, MANIFEST_MEDIA_TYPE, &manifest)?;
+    manifest_descriptor["platform"] = json!({ "architecture": architecture, "os": "linux" });
+    if let Some(variant) = variant {
+        manifest_descriptor["platform"]["variant"] = variant.into();
+    }
+    manifest_descriptor["annotations"] = json!({ "org.opencontainers.image.ref.name": image_tag(&package_deb.deb_version) });
+
+    let index = json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.index.v1+json",
+        "manifests": [manifest_descriptor],
+    });
+    write_file(&dest_dir.join("index.json"), &serde_json::to_vec(&index)?)?;
+    write_file(&dest_dir.join("oci-layout"), br#"{"imageLayoutVersion":"1.0.0"}"#)?;
+    listener.progress("Image", dest_dir.display().to_string());
+    Ok(())
+}
+
+/// Stores JSON in `blobs/sha256`, and returns its descriptor
+fn write_blob(blobs_dir: &Path, media_type: &str, value: &serde_json::Value) -> CDResult<serde_json::Value> {
+    let data = serde_json::to_vec(value)?;
+    let digest = hex_digest(Sha256::new_with_prefix(&data));
+    write_file(&blobs_dir.join(&digest), &data)?;
+    Ok(json!({
+        "mediaType": media_type,
+        "digest": format!("sha256:{digest}"),
+        "size": data.len(),
+    }))
+}
+
+fn create(path: &Path) -> CDResult<fs::File> {
+    fs::File::create(path).map_err(|e| CargoDebError::IoFile("Can't create file", e, path.into()))
+}
+
+fn write_file(path: &Path, data: &[u8]) -> CDResult<()> {
+    fs::write(path, data).map_err(|e| CargoDebError::IoFile("Can't write file", e, path.into()))
+}
+
+fn hex_digest(hasher: Sha256) -> String {
+    hasher.finalize().iter().fold(String::with_capacity(64), |mut out, b| {
+        let _ = write!(out, "{b:02x}");
+        out
+    })
+}
+
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Debian's architecture names are mostly the same as Go's, which OCI uses
+fn oci_platform(debian_arch: &str) -> (&str, Option<&'static str>) {
+    match debian_arch {
+        "all" => oci_platform(crate::debian_architecture_from_rust_triple(crate::DEFAULT_TARGET)),
+        "i386" => ("386", None),
+        "armhf" => ("arm", Some("v7")),
+        "armel" => ("arm", Some("v5")),
+        "ppc64el" => ("ppc64le", None),
+        "mips64el" => ("mips64le", None),
+        "mipsel" => ("mipsle", None),
+        arch => (arch, None),
+    }
+}
+
+/// Tags can't have `:` or `~` of Debian versions
+fn image_tag(version: &str) -> String {
+    version.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-') { c } else { '_' })
+        .take(128)
+        .collect()
+}
+
+/// UTC date like `2023-11-14T22:13:20Z`
+fn rfc3339(timestamp: u64) -> String {
+    let (days, secs) = (timestamp / 86400, timestamp % 86400);
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates() {
+        assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(rfc3339(1_700_000_000), "2023-11-14T22:13:20Z");
+        assert_eq!(rfc3339(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(rfc3339(4_102_444_799), "2099-12-31T23:59:59Z");
+    }
+
+    #[test]
+    fn platforms() {
+        assert_eq!(oci_platform("amd64"), ("amd64", None));
+        assert_eq!(oci_platform("armhf"), ("arm", Some("v7")));
+        assert_eq!(oci_platform("ppc64el"), ("ppc64le", None));
+        assert_ne!(oci_platform("all").0, "all");
+        assert_eq!(image_tag("1:2.0~rc1+git-3"), "1_2.0_rc1_git-3");
+    }
+}
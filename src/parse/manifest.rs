@@ -8,7 +8,7 @@ use log::debug;
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use std::borrow::Cow;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
@@ -147,6 +147,67 @@ impl DependencyList {
     }
 }
 
+/// `compression = { format = "xz", level = 9, extreme = true, threads = 4, memlimit = "512MiB" }`
+#[derive(Clone, Debug, Deserialize, Default)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) struct CompressionSettings {
+    pub format: Option<String>,
+    /// Format of `control.tar`, defaults to `format`
+    pub control_format: Option<String>,
+    pub level: Option<u32>,
+    pub extreme: Option<bool>,
+    pub threads: Option<u32>,
+    pub memlimit: Option<ByteSize>,
+}
+
+impl CompressionSettings {
+    fn inherit_from(self, parent: Self) -> Self {
+        Self {
+            format: self.format.or(parent.format),
+            control_format: self.control_format.or(parent.control_format),
+            level: self.level.or(parent.level),
+            extreme: self.extreme.or(parent.extreme),
+            threads: self.threads.or(parent.threads),
+            memlimit: self.memlimit.or(parent.memlimit),
+        }
+    }
+}
+
+/// `[package.metadata.deb.rpm]` overrides used only for `--format rpm`
+#[derive(Clone, Debug, Deserialize, Default)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) struct RpmSettings {
+    pub release: Option<String>,
+    pub requires: Option<DependencyList>,
+    pub provides: Option<DependencyList>,
+    pub conflicts: Option<DependencyList>,
+    pub obsoletes: Option<DependencyList>,
+    pub recommends: Option<DependencyList>,
+    pub suggests: Option<DependencyList>,
+}
+
+impl RpmSettings {
+    fn inherit_from(self, parent: Self) -> Self {
+        Self {
+            release: self.release.or(parent.release),
+            requires: self.requires.or(parent.requires),
+            provides: self.provides.or(parent.provides),
+            conflicts: self.conflicts.or(parent.conflicts),
+            obsoletes: self.obsoletes.or(parent.obsoletes),
+            recommends: self.recommends.or(parent.recommends),
+            suggests: self.suggests.or(parent.suggests),
+        }
+    }
+}
+
+/// Number of bytes, or a string with a unit like `"512MiB"`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum ByteSize {
+    Bytes(u64),
+    String(String),
+}
+
 /// Type-alias for list of assets
 pub(crate) type RawAssetList = Vec<RawAssetOrAuto>;
 
@@ -161,6 +222,7 @@ pub(crate) struct MergeMap<'a> {
 pub(crate) enum CargoDebAssetArrayOrTable {
     Table(CargoDebAsset),
     Symlink(CargoDebSymlink),
+    Dir(CargoDebDir),
     Array(Vec<String>),
     Auto(String),
     Invalid(toml::Value),
@@ -172,6 +234,16 @@ pub(crate) struct CargoDebAsset {
     pub dest: String,
     pub mode: Option<String>,
     pub preserve_symlinks: Option<bool>,
+    pub user: Option<CargoDebOwner>,
+    pub group: Option<CargoDebOwner>,
+}
+
+/// User or group name, or a numeric id
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum CargoDebOwner {
+    Id(u32),
+    Name(String),
 }
 
 #[derive(Clone, Debug, Deserialize, Default)]
@@ -180,6 +252,16 @@ pub(crate) struct CargoDebSymlink {
     pub link_name: String,
 }
 
+#[derive(Clone, Debug, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct CargoDebDir {
+    pub dest: String,
+    pub dir: bool,
+    pub mode: Option<String>,
+    pub user: Option<CargoDebOwner>,
+    pub group: Option<CargoDebOwner>,
+}
+
 #[derive(Clone, Debug, Deserialize, Default)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub(crate) struct CargoDeb {
@@ -197,6 +279,18 @@ pub(crate) struct CargoDeb {
     pub breaks: Option<DependencyList>,
     pub replaces: Option<DependencyList>,
     pub provides: Option<DependencyList>,
+    pub built_using: Option<DependencyList>,
+    pub essential: Option<bool>,
+    pub protected: Option<bool>,
+    pub important: Option<bool>,
+    pub bugs: Option<String>,
+    pub origin: Option<String>,
+    pub tag: Option<DependencyList>,
+    /// Empty string disables the default from `repository`
+    pub vcs_git: Option<String>,
+    pub vcs_browser: Option<String>,
+    pub description_md5: Option<bool>,
+    pub cargo_built_using: Option<bool>,
     pub extended_description: Option<String>,
     pub extended_description_file: Option<String>,
     pub section: Option<String>,
@@ -213,7 +307,14 @@ pub(crate) struct CargoDeb {
     pub dbgsym: Option<bool>,
     pub compress_debug_symbols: Option<bool>,
     pub preserve_symlinks: Option<bool>,
+    pub sha256sums: Option<bool>,
+    pub dpkg_deb_compat: Option<bool>,
+    pub udeb: Option<bool>,
     pub systemd_units: Option<SystemUnitsSingleOrMultiple>,
+    pub compression: Option<CompressionSettings>,
+    pub rpm: Option<RpmSettings>,
+    /// Appended to the control file in this order
+    pub extra_fields: Option<toml::Table>,
     pub variants: Option<HashMap<String, Self>>,
 
     /// Cargo build profile, defaults to `release`
@@ -268,10 +369,10 @@ impl MergeByKey {
                     },
                 }
             },
-            RawAsset::Symlink { target_path:dest, .. } => {
+            RawAsset::Symlink { target_path:dest, .. } | RawAsset::Dir { target_path:dest, .. } => {
                 match self {
                     MergeByKey::Src(_) => {
-                        // symlinks defined in the manifest don't have a source path
+                        // symlinks and dirs defined in the manifest don't have a source path
                     },
                     MergeByKey::Dest(_) => {
                         merge_map.by_path.insert(dest, asset);
@@ -361,6 +462,17 @@ impl CargoDeb {
             breaks: self.breaks.or(parent.breaks),
             replaces: self.replaces.or(parent.replaces),
             provides: self.provides.or(parent.provides),
+            built_using: self.built_using.or(parent.built_using),
+            essential: self.essential.or(parent.essential),
+            protected: self.protected.or(parent.protected),
+            important: self.important.or(parent.important),
+            bugs: self.bugs.or(parent.bugs),
+            origin: self.origin.or(parent.origin),
+            tag: self.tag.or(parent.tag),
+            vcs_git: self.vcs_git.or(parent.vcs_git),
+            vcs_browser: self.vcs_browser.or(parent.vcs_browser),
+            description_md5: self.description_md5.or(parent.description_md5),
+            cargo_built_using: self.cargo_built_using.or(parent.cargo_built_using),
             extended_description: self.extended_description.or(parent.extended_description),
             extended_description_file: self.extended_description_file.or(parent.extended_description_file),
             section: self.section.or(parent.section),
@@ -377,7 +489,25 @@ impl CargoDeb {
             separate_debug_symbols: self.separate_debug_symbols.or(parent.separate_debug_symbols),
             compress_debug_symbols: self.compress_debug_symbols.or(parent.compress_debug_symbols),
             preserve_symlinks: self.preserve_symlinks.or(parent.preserve_symlinks),
+            sha256sums: self.sha256sums.or(parent.sha256sums),
+            dpkg_deb_compat: self.dpkg_deb_compat.or(parent.dpkg_deb_compat),
+            udeb: self.udeb.or(parent.udeb),
             systemd_units: self.systemd_units.or(parent.systemd_units),
+            compression: match (self.compression, parent.compression) {
+                (Some(compression), Some(parent)) => Some(compression.inherit_from(parent)),
+                (compression, parent) => compression.or(parent),
+            },
+            rpm: match (self.rpm, parent.rpm) {
+                (Some(rpm), Some(parent)) => Some(rpm.inherit_from(parent)),
+                (rpm, parent) => rpm.or(parent),
+            },
+            extra_fields: match (self.extra_fields, parent.extra_fields) {
+                (Some(fields), Some(mut parent)) => {
+                    parent.extend(fields);
+                    Some(parent)
+                },
+                (fields, parent) => fields.or(parent),
+            },
             variants: self.variants.or(parent.variants),
             profile: self.profile.or(parent.profile),
         }
@@ -476,8 +606,31 @@ fn parse_manifest_only(manifest_path: &Path) -> Result<cargo_toml::Manifest<Carg
     let manifest_bytes = fs::read(manifest_path)
         .map_err(|e| CargoDebError::IoFile("Unable to read manifest", e, manifest_path.to_owned()))?;
 
-    cargo_toml::Manifest::<CargoPackageMetadata>::from_slice_with_metadata(&manifest_bytes)
-            .map_err(|e| CargoDebError::TomlParsing(e, manifest_path.into()))
+    manifest_from_slice(&manifest_bytes)
+        .map_err(|e| CargoDebError::TomlParsing(e, manifest_path.into()))
+}
+
+fn manifest_from_slice(manifest_bytes: &[u8]) -> Result<cargo_toml::Manifest<CargoPackageMetadata>, cargo_toml::Error> {
+    let mut manifest = cargo_toml::Manifest::<CargoPackageMetadata>::from_slice_with_metadata(manifest_bytes)?;
+    if let Some(deb) = manifest.package.as_mut().and_then(|p| p.metadata.as_mut()).and_then(|m| m.deb.as_mut()) {
+        // cargo_toml's tables are sorted by key, but `extra-fields` must keep the order they're written in
+        let table = std::str::from_utf8(manifest_bytes).ok().and_then(|s| toml::from_str::<toml::Table>(s).ok());
+        if let Some(toml::Value::Table(deb_table)) = table.as_ref().and_then(|t| t.get("package")?.get("metadata")?.get("deb")) {
+            restore_extra_fields_order(deb, deb_table);
+        }
+    }
+    Ok(manifest)
+}
+
+fn restore_extra_fields_order(deb: &mut CargoDeb, deb_table: &toml::Table) {
+    if let (Some(fields), Some(toml::Value::Table(ordered))) = (&mut deb.extra_fields, deb_table.get("extra-fields")) {
+        fields.clone_from(ordered);
+    }
+    for (name, variant) in deb.variants.iter_mut().flatten() {
+        if let Some(toml::Value::Table(variant_table)) = deb_table.get("variants").and_then(|v| v.get(name)) {
+            restore_extra_fields_order(variant, variant_table);
+        }
+    }
 }
 
 pub(crate) fn cargo_metadata(initial_manifest_path: Option<&Path>, selected_package_name: Option<&str>, cargo_locking_flags: CargoLockingFlags) -> Result<ManifestFound, CargoDebError> {
@@ -510,6 +663,79 @@ pub(crate) fn cargo_metadata(initial_manifest_path: Option<&Path>, selected_pack
     })
 }
 
+#[derive(Deserialize)]
+struct CargoMetadataResolved {
+    pub packages: Vec<CargoMetadataDependency>,
+    pub resolve: CargoMetadataResolve,
+}
+
+#[derive(Deserialize)]
+struct CargoMetadataDependency {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    /// `None` for local paths
+    pub source: Option<String>,
+    pub targets: Vec<CargoMetadataTarget>,
+}
+
+#[derive(Deserialize)]
+struct CargoMetadataResolve {
+    pub nodes: Vec<CargoMetadataNode>,
+    pub root: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CargoMetadataNode {
+    pub id: String,
+    pub deps: Vec<CargoMetadataNodeDep>,
+}
+
+#[derive(Deserialize)]
+struct CargoMetadataNodeDep {
+    pub pkg: String,
+    pub dep_kinds: Vec<CargoMetadataDepKind>,
+}
+
+#[derive(Deserialize)]
+struct CargoMetadataDepKind {
+    /// `None` for normal deps, or `dev`/`build`
+    pub kind: Option<String>,
+}
+
+/// Names and versions of crates linked into the root package, from `cargo metadata` output
+/// that was already filtered for the target platform and features.
+///
+/// Build-dependencies, dev-dependencies, and proc macros don't end up in the binaries,
+/// and local path dependencies are a part of the package's own source.
+pub(crate) fn linked_crates(cargo_metadata_json: &[u8]) -> CDResult<BTreeSet<(String, String)>> {
+    let metadata: CargoMetadataResolved = serde_json::from_slice(cargo_metadata_json)?;
+    let root = metadata.resolve.root.ok_or(CargoDebError::Str("cargo metadata didn't find the root package"))?;
+    let packages: HashMap<_, _> = metadata.packages.iter().map(|p| (p.id.as_str(), p)).collect();
+    let nodes: HashMap<_, _> = metadata.resolve.nodes.iter().map(|n| (n.id.as_str(), n)).collect();
+
+    let mut crates = BTreeSet::new();
+    let mut seen = HashSet::new();
+    let mut to_visit = vec![root.as_str()];
+    while let Some(id) = to_visit.pop() {
+        let Some(node) = nodes.get(id) else { continue };
+        for dep in node.deps.iter().filter(|d| d.dep_kinds.iter().any(|k| k.kind.is_none())) {
+            if !seen.insert(dep.pkg.as_str()) {
+                continue;
+            }
+            let Some(package) = packages.get(dep.pkg.as_str()) else { continue };
+            if package.targets.iter().any(|t| t.kind.iter().any(|k| k == "proc-macro")) {
+                continue;
+            }
+            if package.source.is_some() {
+                crates.insert((package.name.clone(), package.version.clone()));
+            }
+            to_visit.push(&dep.pkg);
+        }
+    }
+    Ok(crates)
+}
+
 /// Returns the workspace metadata based on the `Cargo.toml` that we want to build,
 /// and directory that paths may be relative to
 fn run_cargo_metadata(manifest_rel_path: Option<&Path>, cargo_locking_flags: CargoLockingFlags) -> CDResult<CargoMetadata> {
@@ -539,7 +765,7 @@ mod tests {
     
     fn create_test_asset(src: impl Into<PathBuf>, target_path: impl Into<PathBuf>, perm: u32) -> RawAsset {
         RawAsset::Asset {
-            source_path: src.into(), target_path: target_path.into(), chmod: Some(perm), preserve_symlinks: None,
+            source_path: src.into(), target_path: target_path.into(), chmod: Some(perm), preserve_symlinks: None, owner: Default::default(),
         }
     }
 
@@ -730,6 +956,61 @@ mod tests {
         assert_eq!("/opt/test/empty.txt", merged_asset.target_path().as_os_str(), "should preserve dest location");
         assert_eq!(Some(0o655), merged_asset.chmod(), "should have merged the chmod");
     }
+
+    #[test]
+    fn linked_crates_skip_unlinked() {
+        let json = br#"{
+            "packages": [
+                {"id": "app", "name": "app", "version": "1.0.0", "source": null, "targets": [{"name": "app", "kind": ["bin"], "crate_types": ["bin"], "src_path": "src/main.rs"}]},
+                {"id": "local", "name": "local", "version": "0.1.0", "source": null, "targets": []},
+                {"id": "serde", "name": "serde", "version": "1.0.200", "source": "registry+https://github.com/rust-lang/crates.io-index", "targets": []},
+                {"id": "derive", "name": "serde_derive", "version": "1.0.200", "source": "registry+x", "targets": [{"name": "serde_derive", "kind": ["proc-macro"], "crate_types": ["proc-macro"], "src_path": "lib.rs"}]},
+                {"id": "syn", "name": "syn", "version": "2.0.0", "source": "registry+x", "targets": []},
+                {"id": "cc", "name": "cc", "version": "1.0.0", "source": "registry+x", "targets": []},
+                {"id": "tempfile", "name": "tempfile", "version": "3.0.0", "source": "registry+x", "targets": []},
+                {"id": "itoa", "name": "itoa", "version": "1.0.0", "source": "git+https://example.com/itoa", "targets": []}
+            ],
+            "resolve": {
+                "root": "app",
+                "nodes": [
+                    {"id": "app", "deps": [
+                        {"pkg": "local", "dep_kinds": [{"kind": null, "target": null}]},
+                        {"pkg": "serde", "dep_kinds": [{"kind": null, "target": null}]},
+                        {"pkg": "derive", "dep_kinds": [{"kind": null, "target": null}]},
+                        {"pkg": "cc", "dep_kinds": [{"kind": "build", "target": null}]},
+                        {"pkg": "tempfile", "dep_kinds": [{"kind": "dev", "target": null}]}
+                    ]},
+                    {"id": "local", "deps": [{"pkg": "itoa", "dep_kinds": [{"kind": null, "target": null}]}]},
+                    {"id": "derive", "deps": [{"pkg": "syn", "dep_kinds": [{"kind": null, "target": null}]}]},
+                    {"id": "serde", "deps": []}, {"id": "syn", "deps": []}, {"id": "cc", "deps": []}, {"id": "tempfile", "deps": []}, {"id": "itoa", "deps": []}
+                ]
+            }
+        }"#;
+        let crates = linked_crates(json).unwrap().into_iter().collect_vec();
+        assert_eq!(crates, [("itoa".into(), "1.0.0".into()), ("serde".into(), "1.0.200".into())]);
+    }
+
+    #[test]
+    fn extra_fields_keep_order() {
+        let manifest = manifest_from_slice(br#"
+            [package]
+            name = "test"
+            version = "1.0.0"
+
+            [package.metadata.deb.extra-fields]
+            X-Zeta = "z"
+            X-Alpha = "a"
+            X-Mid = 1
+
+            [package.metadata.deb.variants.v.extra-fields]
+            X-Later = "l"
+            X-Early = "e"
+        "#).unwrap();
+        let deb = manifest.package.unwrap().metadata.unwrap().deb.unwrap();
+        assert_eq!(deb.extra_fields.as_ref().unwrap().keys().collect_vec(), ["X-Zeta", "X-Alpha", "X-Mid"]);
+        let variant = &deb.variants.as_ref().unwrap()["v"];
+        assert_eq!(variant.extra_fields.as_ref().unwrap().keys().collect_vec(), ["X-Later", "X-Early"]);
+    }
 }
 
 #[test]
@@ -0,0 +1,540 @@
+//! Package relationship fields, like `Depends` and `Conflicts`
+//!
+//! <https://www.debian.org/doc/debian-policy/ch-relationships.html>
+
+use crate::config::check_debian_version;
+use crate::error::{CDResult, CargoDebError};
+use std::fmt;
+use std::ops::Range;
+
+/// Comma-separated relations, each with one or more `|` alternatives
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RelationList(pub Vec<Vec<Relation>>);
+
+/// A single package in a relationship field, e.g. `libc6:any (>= 2.36) [amd64] <!nocheck>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relation {
+    pub name: String,
+    /// `any`, `native`, or an architecture name after the `:`
+    pub arch_qualifier: Option<String>,
+    pub version: Option<(VersionOp, String)>,
+    /// `[amd64 arm64]` or `[!i386]`
+    pub arches: Option<ArchList>,
+    /// Each `<…>` is a list of terms that all have to be true. At least one of the lists has to be true.
+    pub profiles: Vec<Vec<ProfileTerm>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionOp {
+    /// `<<`
+    Earlier,
+    /// `<=`
+    EarlierOrEqual,
+    /// `=`
+    Exactly,
+    /// `>=`
+    LaterOrEqual,
+    /// `>>`
+    Later,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchList {
+    /// `[!a !b]` excludes the architectures. They can't be mixed with plain ones.
+    pub negated: bool,
+    /// Architecture names or wildcards like `linux-any`
+    pub arches: Vec<String>,
+}
+
+/// A build profile like `nocheck`, or `!nocheck`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileTerm {
+    pub negated: bool,
+    pub name: String,
+}
+
+/// An entry of the `depends` setting, which can also have `$auto`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum DependsEntry {
+    Auto,
+    Relations(Vec<Relation>),
+}
+
+/// Restrictions that dpkg has for some fields
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct Rules {
+    /// `$auto` is allowed as an entry
+    pub auto: bool,
+    /// `Conflicts`, `Breaks`, `Replaces`, `Provides`, and `Built-Using` can't have `|`
+    pub no_alternatives: bool,
+    /// `Provides` can only have `=` versions
+    pub exact_versions: bool,
+    /// `Built-Using` must have versions
+    pub version_required: bool,
+}
+
+impl RelationList {
+    /// Parses a relationship field. The field name is only used in error messages.
+    pub fn parse(field: &str, value: &str) -> CDResult<Self> {
+        Self::parse_with(field, value, Rules::default())
+    }
+
+    pub(crate) fn parse_with(field: &str, value: &str, rules: Rules) -> CDResult<Self> {
+        let entries = parse_depends(field, value, Rules { auto: false, ..rules })?;
+        Ok(Self(entries.into_iter().filter_map(|e| match e {
+            DependsEntry::Relations(r) => Some(r),
+            DependsEntry::Auto => None,
+        }).collect()))
+    }
+
+    /// Keeps only alternatives for the given architecture and build profiles, and removes the arch lists and profiles,
+    /// like `dpkg-gencontrol` does for binary packages. Relations without any alternatives left are removed.
+    ///
+    /// `arch_matches` is called with architecture names or wildcards from the `[…]` lists.
+    pub fn for_architecture(&self, mut arch_matches: impl FnMut(&str) -> CDResult<bool>, active_profiles: &[&str]) -> CDResult<Self> {
+        let mut out = Vec::with_capacity(self.0.len());
+        for alternatives in &self.0 {
+            let mut kept = Vec::with_capacity(alternatives.len());
+            for rel in alternatives {
+                if rel.arches_match(&mut arch_matches)? && rel.profiles_match(active_profiles) {
+                    kept.push(Relation { arches: None, profiles: Vec::new(), ..rel.clone() });
+                }
+            }
+            if !kept.is_empty() {
+                out.push(kept);
+            }
+        }
+        Ok(Self(out))
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Relation {
+    /// True if there's no arch list, or the list allows the architecture
+    pub fn arches_match(&self, mut arch_matches: impl FnMut(&str) -> CDResult<bool>) -> CDResult<bool> {
+        let Some(list) = &self.arches else {
+            return Ok(true);
+        };
+        for arch in &list.arches {
+            if arch_matches(arch)? {
+                return Ok(!list.negated);
+            }
+        }
+        Ok(list.negated)
+    }
+
+    /// True if there are no `<…>` restrictions, or any of them is satisfied
+    #[must_use]
+    pub fn profiles_match(&self, active_profiles: &[&str]) -> bool {
+        self.profiles.is_empty() || self.profiles.iter().any(|terms| {
+            terms.iter().all(|t| active_profiles.contains(&t.name.as_str()) != t.negated)
+        })
+    }
+}
+
+impl fmt::Display for RelationList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, alternatives) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            for (j, rel) in alternatives.iter().enumerate() {
+                if j > 0 {
+                    f.write_str(" | ")?;
+                }
+                rel.fmt(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Relation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)?;
+        if let Some(q) = &self.arch_qualifier {
+            write!(f, ":{q}")?;
+        }
+        if let Some((op, version)) = &self.version {
+            write!(f, " ({op} {version})")?;
+        }
+        if let Some(list) = &self.arches {
+            f.write_str(" [")?;
+            for (i, arch) in list.arches.iter().enumerate() {
+                let sep = if i > 0 { " " } else { "" };
+                let neg = if list.negated { "!" } else { "" };
+                write!(f, "{sep}{neg}{arch}")?;
+            }
+            f.write_str("]")?;
+        }
+        for terms in &self.profiles {
+            f.write_str(" <")?;
+            for (i, t) in terms.iter().enumerate() {
+                let sep = if i > 0 { " " } else { "" };
+                let neg = if t.negated { "!" } else { "" };
+                write!(f, "{sep}{neg}{}", t.name)?;
+            }
+            f.write_str(">")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for VersionOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Earlier => "<<",
+            Self::EarlierOrEqual => "<=",
+            Self::Exactly => "=",
+            Self::LaterOrEqual => ">=",
+            Self::Later => ">>",
+        })
+    }
+}
+
+pub(crate) fn parse_depends(field: &str, value: &str, rules: Rules) -> CDResult<Vec<DependsEntry>> {
+    let mut p = Parser { field, input: value, pos: 0, rules };
+    let mut entries = Vec::new();
+    p.skip_ws();
+    if p.peek().is_none() {
+        return Ok(entries);
+    }
+    loop {
+        p.skip_ws();
+        entries.push(p.entry()?);
+        p.skip_ws();
+        match p.peek() {
+            None => return Ok(entries),
+            Some(b',') => p.pos += 1,
+            Some(_) => return Err(p.unexpected()),
+        }
+        let comma = p.pos - 1;
+        p.skip_ws();
+        if matches!(p.peek(), None | Some(b',')) {
+            return Err(p.error(comma..comma + 1, "empty relation, there's an extra ','"));
+        }
+    }
+}
+
+struct Parser<'a> {
+    field: &'a str,
+    input: &'a str,
+    pos: usize,
+    rules: Rules,
+}
+
+impl Parser<'_> {
+    fn entry(&mut self) -> CDResult<DependsEntry> {
+        let start = self.pos;
+        match self.peek() {
+            None | Some(b',') => return Err(self.error(start..start + 1, "empty relation, there's an extra ','")),
+            Some(b'$') => {
+                let span = self.take_while(|c| c != b',' && !c.is_ascii_whitespace() && c != b'|');
+                if !self.rules.auto || &self.input[span.clone()] != "$auto" {
+                    return Err(self.error(span, "unknown variable, only $auto is supported in depends"));
+                }
+                return Ok(DependsEntry::Auto);
+            },
+            _ => {},
+        }
+        let mut alternatives = vec![self.relation()?];
+        loop {
+            self.skip_ws();
+            if self.peek() != Some(b'|') {
+                return Ok(DependsEntry::Relations(alternatives));
+            }
+            if self.rules.no_alternatives {
+                return Err(self.error(self.pos..self.pos + 1, "alternatives with '|' aren't allowed in this field"));
+            }
+            let bar = self.pos;
+            self.pos += 1;
+            self.skip_ws();
+            if matches!(self.peek(), None | Some(b',' | b'|')) {
+                return Err(self.error(bar..bar + 1, "missing package name after '|'"));
+            }
+            alternatives.push(self.relation()?);
+        }
+    }
+
+    fn relation(&mut self) -> CDResult<Relation> {
+        let rest = &self.input[self.pos..];
+        let name = self.pos..self.pos + rest.find(|c: char| c.is_ascii_whitespace() || ",|:()[]<>=!".contains(c)).unwrap_or(rest.len());
+        self.pos = name.end;
+        if name.is_empty() {
+            return Err(self.unexpected());
+        }
+        let name_str = &self.input[name.clone()];
+        if name_str.len() < 2 || !name_str.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit()) ||
+            !name_str.bytes().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, b'+' | b'-' | b'.')) {
+            return Err(self.error(name, "package names must be at least 2 characters of a-z 0-9 + - . starting with a letter or digit"));
+        }
+        let mut rel = Relation {
+            name: name_str.to_owned(),
+            arch_qualifier: None,
+            version: None,
+            arches: None,
+            profiles: Vec::new(),
+        };
+
+        if self.peek() == Some(b':') {
+            self.pos += 1;
+            let q = self.take_while(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'-');
+            if q.is_empty() {
+                return Err(self.error(q.start - 1..q.start, "missing architecture qualifier after ':', like :any"));
+            }
+            rel.arch_qualifier = Some(self.input[q].to_owned());
+        }
+
+        self.skip_ws();
+        if self.peek() == Some(b'(') {
+            rel.version = Some(self.version()?);
+            self.skip_ws();
+        } else if self.rules.version_required {
+            return Err(self.error(name.start..self.pos, "a version is required, like (= 1.0)"));
+        }
+
+        if self.peek() == Some(b'[') {
+            rel.arches = Some(self.arch_list()?);
+            self.skip_ws();
+        }
+
+        while self.peek() == Some(b'<') {
+            rel.profiles.push(self.profiles()?);
+            self.skip_ws();
+        }
+
+        match self.peek() {
+            None | Some(b',' | b'|') => Ok(rel),
+            Some(b'>' | b'=') => Err(self.error(self.pos..self.pos + 1, "version constraints must be in parentheses, like foo (>= 1.0)")),
+            Some(b'(') => Err(self.error(self.pos..self.pos + 1, "the version must come right after the package name")),
+            Some(b'[') => Err(self.error(self.pos..self.pos + 1, "the architecture list must come before build profiles")),
+            Some(_) => Err(self.error(self.pos..self.pos + 1, "unexpected text, missing ',' between relations?")),
+        }
+    }
+
+    /// `(>= 1.0)`
+    fn version(&mut self) -> CDResult<(VersionOp, String)> {
+        let open = self.pos;
+        self.pos += 1;
+        self.skip_ws();
+        let op_span = self.take_while(|c| b"<>=".contains(&c));
+        let op = match &self.input[op_span.clone()] {
+            "<<" => VersionOp::Earlier,
+            "<=" => VersionOp::EarlierOrEqual,
+            "=" => VersionOp::Exactly,
+            ">=" => VersionOp::LaterOrEqual,
+            ">>" => VersionOp::Later,
+            "" => return Err(self.error(open..self.pos + 1, "missing version operator, like >=")),
+            _ => return Err(self.error(op_span, "unknown version operator, expected <<, <=, =, >=, or >>")),
+        };
+        if self.rules.exact_versions && op != VersionOp::Exactly {
+            return Err(self.error(op_span, "only exact '=' versions are allowed in this field"));
+        }
+        self.skip_ws();
+        let ver = self.take_while(|c| c != b')' && c != b',' && !c.is_ascii_whitespace());
+        if ver.is_empty() {
+            return Err(self.error(open..self.pos, "missing version"));
+        }
+        if let Err(reason) = check_debian_version(&self.input[ver.clone()]) {
+            return Err(self.error(ver, reason));
+        }
+        self.skip_ws();
+        if self.peek() != Some(b')') {
+            return Err(self.error(open..self.pos, "missing ')'"));
+        }
+        self.pos += 1;
+        Ok((op, self.input[ver].to_owned()))
+    }
+
+    /// `[amd64 !i386]`
+    fn arch_list(&mut self) -> CDResult<ArchList> {
+        let open = self.pos;
+        self.pos += 1;
+        let mut negated = None;
+        let mut arches = Vec::new();
+        loop {
+            self.skip_ws();
+            match self.peek() {
+                Some(b']') => break,
+                None | Some(b',' | b'|' | b'<') => return Err(self.error(open..self.pos, "missing ']'")),
+                _ => {},
+            }
+            let start = self.pos;
+            let neg = self.peek() == Some(b'!');
+            if neg {
+                self.pos += 1;
+            }
+            let arch = self.take_while(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'-');
+            if arch.is_empty() {
+                return Err(self.unexpected());
+            }
+            if *negated.get_or_insert(neg) != neg {
+                return Err(self.error(start..arch.end, "negated and plain architectures can't be mixed"));
+            }
+            arches.push(self.input[arch].to_owned());
+        }
+        self.pos += 1;
+        if arches.is_empty() {
+            return Err(self.error(open..self.pos, "empty architecture list"));
+        }
+        Ok(ArchList { negated: negated.unwrap_or(false), arches })
+    }
+
+    /// `<!nocheck cross>`
+    fn profiles(&mut self) -> CDResult<Vec<ProfileTerm>> {
+        let open = self.pos;
+        self.pos += 1;
+        if matches!(self.peek(), Some(b'<' | b'=')) {
+            return Err(self.error(open..self.pos + 1, "version constraints must be in parentheses, like foo (<= 1.0)"));
+        }
+        let mut terms = Vec::new();
+        loop {
+            self.skip_ws();
+            match self.peek() {
+                Some(b'>') => break,
+                None | Some(b',' | b'|' | b'[') => return Err(self.error(open..self.pos, "missing '>'")),
+                _ => {},
+            }
+            let negated = self.peek() == Some(b'!');
+            if negated {
+                self.pos += 1;
+            }
+            let name = self.take_while(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, b'+' | b'-' | b'.'));
+            if name.is_empty() {
+                return Err(self.unexpected());
+            }
+            terms.push(ProfileTerm { negated, name: self.input[name].to_owned() });
+        }
+        self.pos += 1;
+        if terms.is_empty() {
+            return Err(self.error(open..self.pos, "empty build profile list"));
+        }
+        Ok(terms)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_ws(&mut self) {
+        self.take_while(|c| c.is_ascii_whitespace());
+    }
+
+    /// Stops at non-ASCII chars too, so that the spans are always on char boundaries
+    fn take_while(&mut self, f: impl Fn(u8) -> bool) -> Range<usize> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii() && f(c)) {
+            self.pos += 1;
+        }
+        start..self.pos
+    }
+
+    fn unexpected(&self) -> CargoDebError {
+        let len = self.input[self.pos..].chars().next().map_or(1, char::len_utf8);
+        self.error(self.pos..self.pos + len, "unexpected character")
+    }
+
+    /// The span is widened to whole chars, because the error message slices the input with it
+    fn error(&self, span: Range<usize>, reason: &'static str) -> CargoDebError {
+        let mut start = span.start.min(self.input.len());
+        while !self.input.is_char_boundary(start) {
+            start -= 1;
+        }
+        let mut end = span.end.clamp(start, self.input.len());
+        while !self.input.is_char_boundary(end) {
+            end += 1;
+        }
+        let span = start..end;
+        CargoDebError::InvalidRelation(self.field.into(), self.input.into(), span, reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_span(field_value: &str, rules: Rules) -> (Range<usize>, &'static str) {
+        match parse_depends("Depends", field_value, rules) {
+            Err(CargoDebError::InvalidRelation(_, _, span, reason)) => (span, reason),
+            other => panic!("{field_value}: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_and_print() {
+        let list = RelationList::parse("Depends", "libc6:any (>=2.36) [amd64  arm64] <!nocheck> <stage1 cross>,\n  foo | bar-utils (<< 2:1.0~rc1-1) [!i386], baz:native").unwrap();
+        assert_eq!(list.0.len(), 3);
+        assert_eq!(list.0[1].len(), 2);
+        let libc = &list.0[0][0];
+        assert_eq!(libc.name, "libc6");
+        assert_eq!(libc.arch_qualifier.as_deref(), Some("any"));
+        assert_eq!(libc.version, Some((VersionOp::LaterOrEqual, "2.36".into())));
+        assert_eq!(libc.arches, Some(ArchList { negated: false, arches: vec!["amd64".into(), "arm64".into()] }));
+        assert_eq!(libc.profiles.len(), 2);
+        assert_eq!(list.to_string(), "libc6:any (>= 2.36) [amd64 arm64] <!nocheck> <stage1 cross>, foo | bar-utils (<< 2:1.0~rc1-1) [!i386], baz:native");
+        assert!(RelationList::parse("Depends", "  ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn auto() {
+        let entries = parse_depends("Depends", "$auto, foo", Rules { auto: true, ..Rules::default() }).unwrap();
+        assert_eq!(entries[0], DependsEntry::Auto);
+        assert_eq!(error_span("$auto", Rules::default()).0, 0..5);
+        assert_eq!(error_span("$shlibs, foo", Rules { auto: true, ..Rules::default() }).0, 0..7);
+    }
+
+    #[test]
+    fn errors() {
+        let r = Rules::default();
+        assert_eq!(error_span("libssl3 (>= 3.0", r), (8..15, "missing ')'"));
+        assert_eq!(error_span("foo >= 1", r).0, 4..5);
+        assert_eq!(error_span("foo <= 1", r).0, 4..6);
+        assert_eq!(error_span("foo (=> 1)", r).0, 5..7);
+        assert_eq!(error_span("foo (1.0)", r).1, "missing version operator, like >=");
+        assert_eq!(error_span("foo (>= v1)", r).0, 8..10);
+        assert_eq!(error_span("foo, , bar", r).0, 3..4);
+        assert_eq!(error_span("foo,", r).0, 3..4);
+        assert_eq!(error_span("Foo", r).0, 0..3);
+        assert_eq!(error_span("foo bar", r).0, 4..5);
+        assert_eq!(error_span("foo | ", r).0, 4..5);
+        assert_eq!(error_span("foo [amd64 !i386]", r).0, 11..16);
+        assert_eq!(error_span("foo [amd64", r).0, 4..10);
+        assert_eq!(error_span("foo <nocheck", r).0, 4..12);
+        assert_eq!(error_span("foo <!nocheck> [amd64]", r).0, 15..16);
+        assert_eq!(error_span("foo:", r).0, 3..4);
+        assert_eq!(error_span("fóo", r).0, 0..4);
+        assert_eq!(error_span("a1 | b1", Rules { no_alternatives: true, ..r }).0, 3..4);
+        assert_eq!(error_span("a1 (>= 1)", Rules { exact_versions: true, ..r }).0, 4..6);
+        assert_eq!(error_span("gcc-12", Rules { version_required: true, ..r }).0, 0..6);
+    }
+
+    #[test]
+    fn non_ascii_errors() {
+        let r = Rules::default();
+        assert_eq!(error_span("libfoo é", r).0, 7..9);
+        assert_eq!(error_span("libfoo (é)", r).0, 7..10);
+        assert_eq!(error_span("libfoo (>= 1é)", r), (7..12, "missing ')'"));
+        let err = RelationList::parse("Recommends", "libfoo é").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid Recommends: unexpected text, missing ',' between relations?\n    libfoo é\n           ^");
+        let err = RelationList::parse("Recommends", "libfoo (é)").unwrap_err();
+        assert!(err.to_string().ends_with("\n    libfoo (é)\n           ^^"), "{err}");
+    }
+
+    #[test]
+    fn error_message() {
+        let err = RelationList::parse("Depends", "foo, libssl3 (>= 3.0").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid Depends: missing ')'\n    foo, libssl3 (>= 3.0\n                 ^^^^^^^");
+    }
+
+    #[test]
+    fn filter_for_architecture() {
+        let list = RelationList::parse("Depends", "a1 [amd64], b1 [!amd64], c1 [linux-any] | d1, e1 <!nocheck>, f1 <nocheck> | g1, h1 [i386]").unwrap();
+        let arch_matches = |arch: &str| Ok(arch == "amd64" || arch == "linux-any");
+        assert_eq!(list.for_architecture(arch_matches, &[]).unwrap().to_string(), "a1, c1 | d1, e1, g1");
+        assert_eq!(list.for_architecture(arch_matches, &["nocheck"]).unwrap().to_string(), "a1, c1 | d1, f1 | g1");
+    }
+}
@@ -0,0 +1,769 @@
+//! [RPM v4](https://rpm-software-management.github.io/rpm/manual/format_v4.html) packages,
+//! made from the same `PackageConfig` and assets as the .deb
+//!
+//! The file is a legacy lead, a signature header with digests, the main header with the metadata
+//! and the list of files, and a compressed cpio archive with the files.
+
+use crate::assets::{Asset, AssetSource, OwnerId};
+use crate::config::{BuildEnvironment, PackageConfig};
+use crate::deb::tar::symlink_link_name;
+use crate::dh::dh_installsystemd;
+use crate::error::{CDResult, CargoDebError};
+use crate::listener::Listener;
+use crate::util::compress::{self, CompressConfig, Compressor, Format};
+use crate::util::{is_path_file, read_file_to_string};
+use crate::OutputPath;
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+const HEADER_MAGIC: [u8; 8] = [0x8e, 0xad, 0xe8, 0x01, 0, 0, 0, 0];
+
+const RPMTAG_HEADERSIGNATURES: u32 = 62;
+const RPMTAG_HEADERIMMUTABLE: u32 = 63;
+const RPMTAG_HEADERI18NTABLE: u32 = 100;
+
+const RPMSIGTAG_SHA256: u32 = 273;
+const RPMSIGTAG_SIZE: u32 = 1000;
+const RPMSIGTAG_MD5: u32 = 1004;
+const RPMSIGTAG_PAYLOADSIZE: u32 = 1007;
+
+const RPMTAG_NAME: u32 = 1000;
+const RPMTAG_VERSION: u32 = 1001;
+const RPMTAG_RELEASE: u32 = 1002;
+const RPMTAG_EPOCH: u32 = 1003;
+const RPMTAG_SUMMARY: u32 = 1004;
+const RPMTAG_DESCRIPTION: u32 = 1005;
+const RPMTAG_BUILDTIME: u32 = 1006;
+const RPMTAG_BUILDHOST: u32 = 1007;
+const RPMTAG_SIZE: u32 = 1009;
+const RPMTAG_LICENSE: u32 = 1014;
+const RPMTAG_PACKAGER: u32 = 1015;
+const RPMTAG_GROUP: u32 = 1016;
+const RPMTAG_URL: u32 = 1020;
+const RPMTAG_OS: u32 = 1021;
+const RPMTAG_ARCH: u32 = 1022;
+const RPMTAG_PREIN: u32 = 1023;
+const RPMTAG_POSTIN: u32 = 1024;
+const RPMTAG_PREUN: u32 = 1025;
+const RPMTAG_POSTUN: u32 = 1026;
+const RPMTAG_FILESIZES: u32 = 1028;
+const RPMTAG_FILEMODES: u32 = 1030;
+const RPMTAG_FILERDEVS: u32 = 1033;
+const RPMTAG_FILEMTIMES: u32 = 1034;
+const RPMTAG_FILEDIGESTS: u32 = 1035;
+const RPMTAG_FILELINKTOS: u32 = 1036;
+const RPMTAG_FILEFLAGS: u32 = 1037;
+const RPMTAG_FILEUSERNAME: u32 = 1039;
+const RPMTAG_FILEGROUPNAME: u32 = 1040;
+const RPMTAG_SOURCERPM: u32 = 1044;
+const RPMTAG_PROVIDENAME: u32 = 1047;
+const RPMTAG_REQUIREFLAGS: u32 = 1048;
+const RPMTAG_REQUIRENAME: u32 = 1049;
+const RPMTAG_REQUIREVERSION: u32 = 1050;
+const RPMTAG_CONFLICTFLAGS: u32 = 1053;
+const RPMTAG_CONFLICTNAME: u32 = 1054;
+const RPMTAG_CONFLICTVERSION: u32 = 1055;
+const RPMTAG_PREINPROG: u32 = 1085;
+const RPMTAG_POSTINPROG: u32 = 1086;
+const RPMTAG_PREUNPROG: u32 = 1087;
+const RPMTAG_POSTUNPROG: u32 = 1088;
+const RPMTAG_OBSOLETENAME: u32 = 1090;
+const RPMTAG_FILEDEVICES: u32 = 1095;
+const RPMTAG_FILEINODES: u32 = 1096;
+const RPMTAG_FILELANGS: u32 = 1097;
+const RPMTAG_PROVIDEFLAGS: u32 = 1112;
+const RPMTAG_PROVIDEVERSION: u32 = 1113;
+const RPMTAG_OBSOLETEFLAGS: u32 = 1114;
+const RPMTAG_OBSOLETEVERSION: u32 = 1115;
+const RPMTAG_DIRINDEXES: u32 = 1116;
+const RPMTAG_BASENAMES: u32 = 1117;
+const RPMTAG_DIRNAMES: u32 = 1118;
+const RPMTAG_PAYLOADFORMAT: u32 = 1124;
+const RPMTAG_PAYLOADCOMPRESSOR: u32 = 1125;
+const RPMTAG_PAYLOADFLAGS: u32 = 1126;
+const RPMTAG_FILEDIGESTALGO: u32 = 5011;
+const RPMTAG_RECOMMENDNAME: u32 = 5046;
+const RPMTAG_RECOMMENDVERSION: u32 = 5047;
+const RPMTAG_RECOMMENDFLAGS: u32 = 5048;
+const RPMTAG_SUGGESTNAME: u32 = 5049;
+const RPMTAG_SUGGESTVERSION: u32 = 5050;
+const RPMTAG_SUGGESTFLAGS: u32 = 5051;
+const RPMTAG_PAYLOADDIGEST: u32 = 5092;
+const RPMTAG_PAYLOADDIGESTALGO: u32 = 5093;
+
+const PGPHASHALGO_SHA256: u32 = 8;
+
+const RPMSENSE_LESS: u32 = 1 << 1;
+const RPMSENSE_GREATER: u32 = 1 << 2;
+const RPMSENSE_EQUAL: u32 = 1 << 3;
+const RPMSENSE_RPMLIB: u32 = 1 << 24;
+
+const RPMFILE_CONFIG: u32 = 1 << 0;
+const RPMFILE_DOC: u32 = 1 << 1;
+const RPMFILE_NOREPLACE: u32 = 1 << 4;
+
+/// The file name is `{name}-{version}-{release}.{arch}.rpm` when writing to a directory
+pub(crate) fn rpm_output_path(package_deb: &PackageConfig, path: &OutputPath<'_>) -> PathBuf {
+    if path.is_dir {
+        let (_, version, release) = rpm_version(package_deb);
+        path.path.join(format!("{}-{version}-{release}.{}.rpm", package_deb.deb_name, architecture(package_deb)))
+    } else {
+        path.path.to_owned()
+    }
+}
+
+/// Writes an `.rpm` package. The payload is compressed with xz, unless `gz` or `zstd` is selected.
+pub(crate) fn write_rpm(config: &BuildEnvironment, rpm_output_path: PathBuf, package_deb: &PackageConfig, &CompressConfig { fast, compress_type, compress_system, options, .. }: &CompressConfig, listener: &dyn Listener) -> CDResult<PathBuf> {
+    let format = compress_type.or(package_deb.compress_type).unwrap_or(Format::Xz);
+    let (compressor_name, rpmlib_payload) = match format {
+        Format::Xz => ("xz", Some(("rpmlib(PayloadIsXz)", "5.2-1"))),
+        Format::Zstd => ("zstd", Some(("rpmlib(PayloadIsZstd)", "5.4.18-1"))),
+        Format::Gzip => ("gzip", None),
+        Format::None => return Err(CargoDebError::InvalidCompression("RPM payloads can't be uncompressed, use gz, xz, or zstd".into())),
+    };
+    let options = options.or_manifest(package_deb.compress_options, package_deb.compress_type.unwrap_or(Format::Xz), format, fast);
+    let rpm_temp_dir = config.deb_temp_dir(package_deb);
+
+    // Files are sorted, because rpm looks them up with a binary search
+    let mut assets = package_deb.assets.resolved.iter().collect::<Vec<_>>();
+    assets.sort_by(|a, b| file_order(&a.c.target_path, &b.c.target_path));
+    let payload = compress::select_compressor(fast, format, &options, compress_system, false, &rpm_temp_dir)?;
+    let (payload, files) = write_payload(payload, &assets, package_deb, listener)?;
+    let payload_size = payload.uncompressed_size as u64;
+    let mut payload = payload.finish()?;
+    let mut payload_digest = HashingWriter { inner: io::sink(), hasher: Sha256::new() };
+    io::copy(&mut payload, &mut payload_digest).map_err(|e| CargoDebError::Io(e).context("can't read the payload"))?;
+    payload.rewind()?;
+
+    let (epoch, version, release) = rpm_version(package_deb);
+    let evr = match epoch {
+        Some(epoch) => format!("{epoch}:{version}-{release}"),
+        None => format!("{version}-{release}"),
+    };
+    let arch = architecture(package_deb);
+    let mut header = Header::default();
+    header.set(RPMTAG_HEADERI18NTABLE, Value::StringArray(vec!["C".into()]));
+    header.set(RPMTAG_NAME, Value::String(package_deb.deb_name.clone()));
+    header.set(RPMTAG_VERSION, Value::String(version.clone()));
+    header.set(RPMTAG_RELEASE, Value::String(release.clone()));
+    if let Some(epoch) = epoch {
+        header.set(RPMTAG_EPOCH, Value::Int32(vec![epoch]));
+    }
+    header.set(RPMTAG_SUMMARY, Value::I18nString(package_deb.description.clone()));
+    let description = package_deb.extended_description(config)?;
+    header.set(RPMTAG_DESCRIPTION, Value::I18nString(description.as_deref().unwrap_or(&package_deb.description).into()));
+    header.set(RPMTAG_BUILDTIME, Value::Int32(vec![u32_size(package_deb.default_timestamp)?]));
+    header.set(RPMTAG_BUILDHOST, Value::String("localhost".into()));
+    header.set(RPMTAG_SIZE, Value::Int32(vec![u32_size(files.iter().map(|f| u64::from(f.size)).sum())?]));
+    header.set(RPMTAG_LICENSE, Value::String(package_deb.license_identifier.as_deref().unwrap_or("Unknown").into()));
+    if let Some(maintainer) = &package_deb.maintainer {
+        header.set(RPMTAG_PACKAGER, Value::String(maintainer.clone()));
+    }
+    header.set(RPMTAG_GROUP, Value::I18nString("Unspecified".into()));
+    if let Some(url) = package_deb.homepage.as_deref().or(package_deb.documentation.as_deref()).or(package_deb.repository.as_deref()) {
+        header.set(RPMTAG_URL, Value::String(url.into()));
+    }
+    header.set(RPMTAG_OS, Value::String("linux".into()));
+    header.set(RPMTAG_ARCH, Value::String(arch.into()));
+    header.set(RPMTAG_SOURCERPM, Value::String(format!("{}-{version}-{release}.src.rpm", package_deb.deb_name)));
+
+    for (script, tag, prog_tag, interpreter) in scriptlets(config, package_deb, listener)? {
+        header.set(tag, Value::String(script));
+        header.set(prog_tag, Value::String(interpreter));
+    }
+
+    header.add_file_list(&files, package_deb.default_timestamp);
+    header.set(RPMTAG_FILEDIGESTALGO, Value::Int32(vec![PGPHASHALGO_SHA256]));
+
+    let mut requires = dependencies(package_deb.rpm.requires.as_deref().unwrap_or(&package_deb.wildcard_depends));
+    if package_deb.rpm.requires.is_none() {
+        requires.extend(dependencies(package_deb.pre_depends.as_deref().unwrap_or_default()));
+    }
+    let rich = requires.iter().any(|d| d.name.starts_with('('));
+    let rpmlib = [
+        Some(("rpmlib(CompressedFileNames)", "3.0.4-1")),
+        Some(("rpmlib(FileDigests)", "4.6.0-1")),
+        Some(("rpmlib(PayloadFilesHavePrefix)", "4.0-1")),
+        rpmlib_payload,
+        rich.then_some(("rpmlib(RichDependencies)", "4.12.0-1")),
+    ];
+    requires.extend(rpmlib.into_iter().flatten().map(|(name, version)| Dependency {
+        name: name.into(),
+        flags: RPMSENSE_RPMLIB | RPMSENSE_LESS | RPMSENSE_EQUAL,
+        version: version.into(),
+    }));
+    header.add_dependencies(&requires, RPMTAG_REQUIRENAME, RPMTAG_REQUIREFLAGS, RPMTAG_REQUIREVERSION);
+
+    let mut provides = dependencies(package_deb.rpm.provides.as_deref().or(package_deb.provides.as_deref()).unwrap_or_default());
+    provides.push(Dependency { name: package_deb.deb_name.clone(), flags: RPMSENSE_EQUAL, version: evr });
+    header.add_dependencies(&provides, RPMTAG_PROVIDENAME, RPMTAG_PROVIDEFLAGS, RPMTAG_PROVIDEVERSION);
+    let conflicts = package_deb.rpm.conflicts.as_deref().or(package_deb.conflicts.as_deref()).unwrap_or_default();
+    header.add_dependencies(&dependencies(conflicts), RPMTAG_CONFLICTNAME, RPMTAG_CONFLICTFLAGS, RPMTAG_CONFLICTVERSION);
+    let obsoletes = package_deb.rpm.obsoletes.as_deref().unwrap_or_default();
+    header.add_dependencies(&dependencies(obsoletes), RPMTAG_OBSOLETENAME, RPMTAG_OBSOLETEFLAGS, RPMTAG_OBSOLETEVERSION);
+    let recommends = package_deb.rpm.recommends.as_deref().or(package_deb.recommends.as_deref()).unwrap_or_default();
+    header.add_dependencies(&dependencies(recommends), RPMTAG_RECOMMENDNAME, RPMTAG_RECOMMENDFLAGS, RPMTAG_RECOMMENDVERSION);
+    let suggests = package_deb.rpm.suggests.as_deref().or(package_deb.suggests.as_deref()).unwrap_or_default();
+    header.add_dependencies(&dependencies(suggests), RPMTAG_SUGGESTNAME, RPMTAG_SUGGESTFLAGS, RPMTAG_SUGGESTVERSION);
+
+    header.set(RPMTAG_PAYLOADFORMAT, Value::String("cpio".into()));
+    header.set(RPMTAG_PAYLOADCOMPRESSOR, Value::String(compressor_name.into()));
+    header.set(RPMTAG_PAYLOADFLAGS, Value::String(options.level.unwrap_or(format.level(fast)).to_string()));
+    header.set(RPMTAG_PAYLOADDIGEST, Value::StringArray(vec![hex(&payload_digest.hasher.finalize())]));
+    header.set(RPMTAG_PAYLOADDIGESTALGO, Value::Int32(vec![PGPHASHALGO_SHA256]));
+    let header = header.to_bytes(RPMTAG_HEADERIMMUTABLE);
+
+    let mut header_and_payload_md5 = HashingWriter { inner: io::sink(), hasher: Md5::new_with_prefix(&header) };
+    io::copy(&mut payload, &mut header_and_payload_md5).map_err(|e| CargoDebError::Io(e).context("can't read the payload"))?;
+    payload.rewind()?;
+
+    let mut signature = Header::default();
+    signature.set(RPMSIGTAG_SHA256, Value::String(hex(&Sha256::digest(&header))));
+    signature.set(RPMSIGTAG_SIZE, Value::Int32(vec![u32_size(header.len() as u64 + payload.len())?]));
+    signature.set(RPMSIGTAG_MD5, Value::Bin(header_and_payload_md5.hasher.finalize().to_vec()));
+    signature.set(RPMSIGTAG_PAYLOADSIZE, Value::Int32(vec![u32_size(payload_size)?]));
+    let mut signature = signature.to_bytes(RPMTAG_HEADERSIGNATURES);
+    // the main header is 8-byte aligned
+    signature.resize(signature.len().next_multiple_of(8), 0);
+
+    let _ = fs::create_dir_all(rpm_output_path.parent().ok_or("invalid output path")?);
+    let file = fs::File::create(&rpm_output_path)
+        .map_err(|e| CargoDebError::IoFile("can't create file for the archive", e, rpm_output_path.clone()))?;
+    let mut out = BufWriter::new(file);
+    out.write_all(&lead(&format!("{}-{version}-{release}", package_deb.deb_name), arch))
+        .and_then(|()| out.write_all(&signature))
+        .and_then(|()| out.write_all(&header))
+        .and_then(|()| io::copy(&mut payload, &mut out).map(drop))
+        .and_then(|()| out.flush())
+        .map_err(|e| CargoDebError::IoFile("can't write the archive", e, rpm_output_path.clone()))?;
+
+    let _ = fs::remove_dir(&rpm_temp_dir);
+    Ok(rpm_output_path)
+}
+
+/// Metadata of a file in the payload, for the header
+struct RpmFile {
+    /// Absolute
+    path: String,
+    mode: u16,
+    size: u32,
+    digest: String,
+    link_to: String,
+    flags: u32,
+    user: String,
+    group: String,
+}
+
+/// Same as rpmbuild's `strcmp` of absolute paths, which differs from `Path::cmp`
+/// when a name has a byte below `/`, e.g. `foo-bar` comes before `foo/a`
+fn file_order(a: &Path, b: &Path) -> Ordering {
+    let a = Path::new("/").join(a);
+    let b = Path::new("/").join(b);
+    a.as_os_str().as_encoded_bytes().cmp(b.as_os_str().as_encoded_bytes())
+}
+
+/// Writes files in the cpio "newc" format, with `./` prefix that rpm expects. Hardlinks are stored as copies.
+fn write_payload(mut out: Compressor, assets: &[&Asset], package_deb: &PackageConfig, listener: &dyn Listener) -> CDResult<(Compressor, Vec<RpmFile>)> {
+    let mtime = u32_size(package_deb.default_timestamp)?;
+    let mut files = Vec::with_capacity(assets.len());
+    for (ino, asset) in (1..).zip(assets) {
+        let target_path = &asset.c.target_path;
+        let path = Path::new("/").join(target_path);
+        let path = path.to_str().ok_or_else(|| CargoDebError::InvalidPackage(target_path.clone(), "RPM paths must be UTF-8".into()))?;
+        let name = format!(".{path}");
+        let perms = asset.archive_mode() & 0o7777;
+        listener.progress("Adding", path.into());
+
+        let mut file = RpmFile {
+            path: path.into(),
+            mode: 0,
+            size: 0,
+            digest: String::new(),
+            link_to: String::new(),
+            flags: file_flags(target_path, package_deb),
+            user: owner_name(asset.c.owner.user.as_ref()),
+            group: owner_name(asset.c.owner.group.as_ref()),
+        };
+        let mut write = |mode: u32, data: &mut dyn Read, size: u64| -> io::Result<[u8; 32]> {
+            let size = u32::try_from(size).map_err(|_| io::Error::other("files in RPM packages must be smaller than 4GB"))?;
+            let header = format!("070701{ino:08x}{mode:08x}{:08x}{:08x}{:08x}{mtime:08x}{size:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}", 0, 0, 1, 0, 0, 0, 0, name.len() + 1, 0);
+            out.write_all(header.as_bytes())?;
+            out.write_all(name.as_bytes())?;
+            out.write_all(&[0; 4][..1 + pad4(header.len() + name.len() + 1)])?;
+            let mut hashed = HashingWriter { inner: &mut out, hasher: Sha256::new() };
+            let copied = io::copy(&mut data.take(size.into()), &mut hashed)?;
+            if copied != u64::from(size) {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let digest = hashed.hasher.finalize().into();
+            out.write_all(&[0; 3][..pad4(size as usize)])?;
+            Ok(digest)
+        };
+        let res = match &asset.source {
+            AssetSource::Dir => {
+                file.mode = 0o040000 | perms as u16;
+                write(file.mode.into(), &mut io::empty(), 0).map(drop)
+            },
+            AssetSource::Symlink(symlink_kind) => {
+                let link_to = symlink_link_name(target_path, symlink_kind)?;
+                file.link_to = link_to.to_str().ok_or_else(|| CargoDebError::InvalidPackage(link_to.clone(), "RPM paths must be UTF-8".into()))?.into();
+                file.mode = 0o120777;
+                file.size = file.link_to.len() as u32;
+                write(file.mode.into(), &mut file.link_to.as_bytes(), file.size.into()).map(drop)
+            },
+            source => {
+                let (size, mut reader) = source.reader()?;
+                file.mode = 0o100000 | perms as u16;
+                write(file.mode.into(), &mut reader, size).map(|digest| {
+                    file.size = size as u32;
+                    file.digest = hex(&digest);
+                })
+            },
+        };
+        res.map_err(|e| CargoDebError::IoFile("Can't add file to the RPM payload", e, target_path.clone()))?;
+        files.push(file);
+    }
+    let trailer = format!("070701{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}TRAILER!!!\0", 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 11, 0);
+    out.write_all(trailer.as_bytes())
+        .and_then(|()| out.write_all(&[0; 3][..pad4(trailer.len())]))
+        .map_err(|e| CargoDebError::Io(e).context("error while writing the RPM payload"))?;
+    Ok((out, files))
+}
+
+/// Bytes needed to align `len` to 4
+fn pad4(len: usize) -> usize {
+    len.next_multiple_of(4) - len
+}
+
+/// Conffiles are `%config(noreplace)`, and docs are `%doc`, so that they can be excluded
+fn file_flags(target_path: &Path, package_deb: &PackageConfig) -> u32 {
+    if package_deb.conf_files.iter().any(|c| Path::new(c.trim_start_matches('/')) == target_path) {
+        RPMFILE_CONFIG | RPMFILE_NOREPLACE
+    } else if target_path.starts_with("usr/share/doc") || target_path.starts_with("usr/share/man") {
+        RPMFILE_DOC
+    } else {
+        0
+    }
+}
+
+/// RPM uses only names. Unknown ones are replaced with root by rpm.
+fn owner_name(owner: Option<&OwnerId>) -> String {
+    owner.map_or_else(|| "root".into(), |o| o.to_string())
+}
+
+/// Debian maintainer scripts become `%pre`, `%post`, `%preun`, and `%postun`.
+/// Their arguments are translated from rpm's number of installed versions to dpkg's actions.
+/// Systemd units get RPM's equivalent of `dh_installsystemd` scripts in place of `#DEBHELPER#`.
+fn scriptlets(config: &BuildEnvironment, package_deb: &PackageConfig, listener: &dyn Listener) -> CDResult<Vec<(String, u32, u32, String)>> {
+    const SCRIPTS: [(&str, u32, u32, &str); 4] = [
+        ("preinst", RPMTAG_PREIN, RPMTAG_PREINPROG, r#"if [ "$rpm_arg" -gt 1 ]; then set -- upgrade; else set -- install; fi"#),
+        ("postinst", RPMTAG_POSTIN, RPMTAG_POSTINPROG, "set -- configure"),
+        ("prerm", RPMTAG_PREUN, RPMTAG_PREUNPROG, r#"if [ "$rpm_arg" -eq 0 ]; then set -- remove; else set -- upgrade; fi"#),
+        ("postrm", RPMTAG_POSTUN, RPMTAG_POSTUNPROG, r#"if [ "$rpm_arg" -eq 0 ]; then set -- remove; else set -- upgrade; fi"#),
+    ];
+    let maintainer_scripts_dir = package_deb.maintainer_scripts_rel_path.as_ref()
+        .map(|dir| config.path_in_cargo_crate(dir));
+    let mut fragments = systemd_fragments(package_deb, listener)?;
+
+    let mut scriptlets = Vec::new();
+    for (name, tag, prog_tag, dpkg_args) in SCRIPTS {
+        let fragment = fragments.remove(name).unwrap_or_default();
+        let script_path = maintainer_scripts_dir.as_ref().map(|dir| dir.join(name)).filter(|p| is_path_file(p));
+        let (script, interpreter) = if let Some(script_path) = script_path {
+            let script = read_file_to_string(&script_path)
+                .map_err(|e| CargoDebError::IoFile("Can't read script", e, script_path.clone()))?;
+            let (shebang, body) = match script.strip_prefix("#!") {
+                Some(rest) => rest.split_once('\n').unwrap_or((rest, "")),
+                None => ("/bin/sh", script.as_str()),
+            };
+            let mut shebang = shebang.split_ascii_whitespace();
+            let interpreter = shebang.next().unwrap_or("/bin/sh").to_owned();
+            let set_e = if shebang.any(|arg| arg == "-e") { "set -e\n" } else { "" };
+            if !fragment.is_empty() && !body.contains("#DEBHELPER#") {
+                listener.warning(format!("{} doesn't have #DEBHELPER# for the systemd units", script_path.display()));
+            }
+            (format!("rpm_arg=\"$1\"\n{dpkg_args}\n{set_e}{}", body.replace("#DEBHELPER#", fragment.trim_end())), interpreter)
+        } else if !fragment.is_empty() {
+            (format!("rpm_arg=\"$1\"\n{fragment}"), "/bin/sh".into())
+        } else {
+            continue;
+        };
+        scriptlets.push((script, tag, prog_tag, interpreter));
+    }
+    Ok(scriptlets)
+}
+
+/// Same decisions as `dh_installsystemd`, but using `systemctl` directly, since `deb-systemd-helper` is Debian-only
+fn systemd_fragments(package_deb: &PackageConfig, listener: &dyn Listener) -> CDResult<BTreeMap<&'static str, String>> {
+    let mut fragments = BTreeMap::new();
+    let Some(units_config) = package_deb.systemd_units.as_deref().and_then(|u| u.first()) else {
+        return Ok(fragments);
+    };
+    let options = dh_installsystemd::Options::from(units_config);
+    let assets = &package_deb.assets.resolved;
+    let (enable_units, start_units) = dh_installsystemd::units_to_enable_and_start(assets, listener)?;
+    let tmpfiles = assets.iter()
+        .filter(|a| a.c.target_path.parent() == Some(Path::new("usr/lib/tmpfiles.d")))
+        .filter_map(|a| a.c.target_path.file_name()?.to_str())
+        .collect::<Vec<_>>();
+
+    let mut postinst = String::new();
+    if !tmpfiles.is_empty() {
+        let _ = writeln!(postinst, "systemd-tmpfiles --create {} >/dev/null 2>&1 || :", tmpfiles.join(" "));
+    }
+    if !start_units.is_empty() {
+        let start_units = start_units.iter().map(String::as_str).collect::<Vec<_>>().join(" ");
+        postinst.push_str("if [ \"$rpm_arg\" -eq 1 ]; then\n    systemctl daemon-reload >/dev/null 2>&1 || :\n");
+        if !options.no_enable && !enable_units.is_empty() {
+            let enable_units = enable_units.iter().map(String::as_str).collect::<Vec<_>>().join(" ");
+            let _ = writeln!(postinst, "    systemctl enable {enable_units} >/dev/null 2>&1 || :");
+        }
+        if !options.no_start {
+            let _ = writeln!(postinst, "    systemctl start {start_units} >/dev/null 2>&1 || :");
+        }
+        postinst.push_str("fi\n");
+        fragments.insert("prerm", format!("if [ \"$rpm_arg\" -eq 0 ]; then\n    systemctl --no-reload disable --now {start_units} >/dev/null 2>&1 || :\nfi\n"));
+
+        let mut postrm = String::from("systemctl daemon-reload >/dev/null 2>&1 || :\n");
+        if options.restart_after_upgrade || !options.no_stop_on_upgrade {
+            let _ = writeln!(postrm, "if [ \"$rpm_arg\" -ge 1 ]; then\n    systemctl try-restart {start_units} >/dev/null 2>&1 || :\nfi");
+        }
+        fragments.insert("postrm", postrm);
+    }
+    if !postinst.is_empty() {
+        fragments.insert("postinst", postinst);
+    }
+    Ok(fragments)
+}
+
+#[derive(Debug, PartialEq)]
+struct Dependency {
+    name: String,
+    flags: u32,
+    version: String,
+}
+
+/// Converts Debian's `name (>= 1.0), a | b` syntax, and also accepts RPM's `name >= 1.0`.
+/// Alternatives become rich dependencies. `$auto` and architecture-specific deps are skipped,
+/// because they're names of Debian packages.
+fn dependencies(list: &str) -> Vec<Dependency> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|dep| !dep.is_empty() && !dep.starts_with('$') && !dep.contains('['))
+        .map(|dep| {
+            let mut alternatives = dep.split('|').map(dependency).collect::<Vec<_>>();
+            if alternatives.len() == 1 {
+                return alternatives.remove(0);
+            }
+            let alternatives = alternatives.iter().map(|d| {
+                let op = match d.flags & (RPMSENSE_LESS | RPMSENSE_GREATER | RPMSENSE_EQUAL) {
+                    0 => return d.name.clone(),
+                    f if f == RPMSENSE_LESS => "<",
+                    f if f == RPMSENSE_GREATER => ">",
+                    f if f == RPMSENSE_EQUAL => "=",
+                    f if f == RPMSENSE_LESS | RPMSENSE_EQUAL => "<=",
+                    _ => ">=",
+                };
+                format!("{} {op} {}", d.name, d.version)
+            }).collect::<Vec<_>>();
+            Dependency { name: format!("({})", alternatives.join(" or ")), flags: 0, version: String::new() }
+        })
+        .collect()
+}
+
+fn dependency(dep: &str) -> Dependency {
+    let dep = dep.trim();
+    let (name, constraint) = match dep.split_once('(') {
+        Some((name, constraint)) => (name, constraint.trim_end_matches(')')),
+        None => dep.split_once(char::is_whitespace).unwrap_or((dep, "")),
+    };
+    // multiarch qualifiers
+    let name = name.trim().trim_end_matches(":any").trim_end_matches(":native");
+    let constraint = constraint.trim();
+    let op_len = constraint.find(|c| !matches!(c, '<' | '>' | '=')).unwrap_or(constraint.len());
+    let flags = match &constraint[..op_len] {
+        ">=" => RPMSENSE_GREATER | RPMSENSE_EQUAL,
+        "<=" => RPMSENSE_LESS | RPMSENSE_EQUAL,
+        "=" | "==" => RPMSENSE_EQUAL,
+        ">>" | ">" => RPMSENSE_GREATER,
+        "<<" | "<" => RPMSENSE_LESS,
+        _ => 0,
+    };
+    Dependency {
+        name: name.into(),
+        flags,
+        version: if flags != 0 { constraint[op_len..].trim().into() } else { String::new() },
+    }
+}
+
+/// Epoch, version and release from the Debian version.
+/// The Debian revision becomes the release, unless it's set in `[package.metadata.deb.rpm]`.
+fn rpm_version(package_deb: &PackageConfig) -> (Option<u32>, String, String) {
+    let (epoch, version) = match package_deb.deb_version.split_once(':') {
+        Some((epoch, version)) => (epoch.parse().ok(), version),
+        None => (None, package_deb.deb_version.as_str()),
+    };
+    let (version, revision) = version.rsplit_once('-').unwrap_or((version, "1"));
+    let release = package_deb.rpm.release.as_deref().unwrap_or(revision);
+    // RPM versions can't have `-`
+    (epoch, version.replace('-', "_"), release.replace('-', "_"))
+}
+
+fn architecture(package_deb: &PackageConfig) -> &str {
+    if package_deb.architecture == "all" {
+        return "noarch";
+    }
+    rpm_architecture_from_rust_triple(package_deb.rust_target_triple.as_deref().unwrap_or(crate::DEFAULT_TARGET))
+}
+
+fn rpm_architecture_from_rust_triple(rust_target_triple: &str) -> &str {
+    let mut p = rust_target_triple.split('-');
+    let arch = p.next().unwrap();
+    let abi = p.next_back().unwrap_or("");
+
+    match (arch, abi) {
+        ("i586" | "i686" | "x86_64" | "aarch64" | "s390x" | "loongarch64" | "mips" | "mipsel" | "mips64" | "mips64el", _) => arch,
+        (arm, abi) if arm.starts_with("armv7") || arm.starts_with("thumbv7") => {
+            if abi.ends_with("hf") { "armv7hl" } else { "armv7l" }
+        },
+        (arm, abi) if arm.starts_with("arm") => if abi.ends_with("hf") { "armv6hl" } else { "armv5tel" },
+        ("powerpc64le", _) => "ppc64le",
+        ("powerpc64", _) => "ppc64",
+        ("powerpc", _) => "ppc",
+        (risc, _) if risc.starts_with("riscv64") => "riscv64",
+        (arch, _) => arch,
+    }
+}
+
+/// The legacy header at the start of the file. Only the magic and the type are still used.
+fn lead(name: &str, arch: &str) -> [u8; 96] {
+    let archnum: u16 = match arch {
+        "i586" | "i686" | "x86_64" => 1,
+        "ppc" => 5,
+        "armv5tel" | "armv6hl" | "armv7l" | "armv7hl" => 12,
+        "s390x" => 15,
+        "ppc64" | "ppc64le" => 16,
+        "aarch64" => 19,
+        "riscv64" => 22,
+        _ => 0,
+    };
+    let mut lead = [0; 96];
+    lead[..4].copy_from_slice(&[0xed, 0xab, 0xee, 0xdb]);
+    lead[4] = 3; // major version
+    // binary package type is 0
+    lead[8..10].copy_from_slice(&archnum.to_be_bytes());
+    let name = &name.as_bytes()[..name.len().min(65)];
+    lead[10..10 + name.len()].copy_from_slice(name);
+    lead[76..78].copy_from_slice(&1u16.to_be_bytes()); // Linux
+    lead[78..80].copy_from_slice(&5u16.to_be_bytes()); // signature in a header
+    lead
+}
+
+enum Value {
+    Int16(Vec<u16>),
+    Int32(Vec<u32>),
+    String(String),
+    Bin(Vec<u8>),
+    StringArray(Vec<String>),
+    I18nString(String),
+}
+
+#[derive(Default)]
+struct Header {
+    entries: BTreeMap<u32, Value>,
+}
+
+impl Header {
+    /// Empty arrays aren't allowed in the header, so they're left out
+    fn set(&mut self, tag: u32, value: Value) {
+        let is_empty = match &value {
+            Value::Int16(v) => v.is_empty(),
+            Value::Int32(v) => v.is_empty(),
+            Value::StringArray(v) => v.is_empty(),
+            Value::String(_) | Value::Bin(_) | Value::I18nString(_) => false,
+        };
+        if !is_empty {
+            self.entries.insert(tag, value);
+        }
+    }
+
+    /// Paths are split into `DIRNAMES` and `BASENAMES` (`rpmlib(CompressedFileNames)`)
+    fn add_file_list(&mut self, files: &[RpmFile], mtime: u64) {
+        let mut dir_names = Vec::<String>::new();
+        let mut dir_indexes = Vec::with_capacity(files.len());
+        let mut base_names = Vec::with_capacity(files.len());
+        for file in files {
+            let (dir, base) = file.path.rsplit_once('/').unwrap_or(("", &file.path));
+            let dir = format!("{dir}/");
+            let index = dir_names.iter().position(|d| *d == dir).unwrap_or_else(|| {
+                dir_names.push(dir);
+                dir_names.len() - 1
+            });
+            dir_indexes.push(index as u32);
+            base_names.push(base.to_owned());
+        }
+        let strings = |f: fn(&RpmFile) -> &String| Value::StringArray(files.iter().map(|file| f(file).clone()).collect());
+        self.set(RPMTAG_FILESIZES, Value::Int32(files.iter().map(|f| f.size).collect()));
+        self.set(RPMTAG_FILEMODES, Value::Int16(files.iter().map(|f| f.mode).collect()));
+        self.set(RPMTAG_FILERDEVS, Value::Int16(vec![0; files.len()]));
+        self.set(RPMTAG_FILEMTIMES, Value::Int32(vec![mtime as u32; files.len()]));
+        self.set(RPMTAG_FILEDIGESTS, strings(|f| &f.digest));
+        self.set(RPMTAG_FILELINKTOS, strings(|f| &f.link_to));
+        self.set(RPMTAG_FILEFLAGS, Value::Int32(files.iter().map(|f| f.flags).collect()));
+        self.set(RPMTAG_FILEUSERNAME, strings(|f| &f.user));
+        self.set(RPMTAG_FILEGROUPNAME, strings(|f| &f.group));
+        self.set(RPMTAG_FILEDEVICES, Value::Int32(vec![1; files.len()]));
+        self.set(RPMTAG_FILEINODES, Value::Int32((1..=files.len() as u32).collect()));
+        self.set(RPMTAG_FILELANGS, Value::StringArray(vec![String::new(); files.len()]));
+        self.set(RPMTAG_DIRINDEXES, Value::Int32(dir_indexes));
+        self.set(RPMTAG_BASENAMES, Value::StringArray(base_names));
+        self.set(RPMTAG_DIRNAMES, Value::StringArray(dir_names));
+    }
+
+    fn add_dependencies(&mut self, deps: &[Dependency], name_tag: u32, flags_tag: u32, version_tag: u32) {
+        self.set(name_tag, Value::StringArray(deps.iter().map(|d| d.name.clone()).collect()));
+        self.set(flags_tag, Value::Int32(deps.iter().map(|d| d.flags).collect()));
+        self.set(version_tag, Value::StringArray(deps.iter().map(|d| d.version.clone()).collect()));
+    }
+
+    /// The header is an index of `(tag, type, offset, count)` entries sorted by tag, followed by their data.
+    /// All of it is inside a region, which is marked by a first entry pointing to a trailer at the end of the data.
+    fn to_bytes(&self, region_tag: u32) -> Vec<u8> {
+        let entry = |tag: u32, ty: u32, offset: u32, count: u32| {
+            [tag, ty, offset, count].into_iter().flat_map(u32::to_be_bytes)
+        };
+        let index_len = self.entries.len() as u32 + 1;
+        let mut index = Vec::new();
+        let mut data = Vec::new();
+        for (&tag, value) in &self.entries {
+            let (ty, align, count) = match value {
+                Value::Int16(v) => (3, 2, v.len()),
+                Value::Int32(v) => (4, 4, v.len()),
+                Value::String(_) => (6, 1, 1),
+                Value::Bin(v) => (7, 1, v.len()),
+                Value::StringArray(v) => (8, 1, v.len()),
+                Value::I18nString(_) => (9, 1, 1),
+            };
+            data.resize(data.len().next_multiple_of(align), 0);
+            index.extend(entry(tag, ty, data.len() as u32, count as u32));
+            match value {
+                Value::Int16(v) => data.extend(v.iter().flat_map(|n| n.to_be_bytes())),
+                Value::Int32(v) => data.extend(v.iter().flat_map(|n| n.to_be_bytes())),
+                Value::String(s) | Value::I18nString(s) => {
+                    data.extend_from_slice(s.as_bytes());
+                    data.push(0);
+                },
+                Value::Bin(v) => data.extend_from_slice(v),
+                Value::StringArray(v) => for s in v {
+                    data.extend_from_slice(s.as_bytes());
+                    data.push(0);
+                },
+            }
+        }
+        let trailer_offset = data.len() as u32;
+        data.extend(entry(region_tag, 7, (-(index_len as i32 * 16)) as u32, 16));
+
+        let mut out = Vec::with_capacity(16 + index.len() + 16 + data.len());
+        out.extend_from_slice(&HEADER_MAGIC);
+        out.extend_from_slice(&index_len.to_be_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        out.extend(entry(region_tag, 7, trailer_offset, 16));
+        out.extend_from_slice(&index);
+        out.extend_from_slice(&data);
+        out
+    }
+}
+
+fn u32_size(size: u64) -> CDResult<u32> {
+    u32::try_from(size).map_err(|_| CargoDebError::Str("RPM packages larger than 4GB are not supported"))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::with_capacity(bytes.len() * 2), |mut out, b| {
+        let _ = write!(out, "{b:02x}");
+        out
+    })
+}
+
+struct HashingWriter<W, D> {
+    inner: W,
+    hasher: D,
+}
+
+impl<W: Write, D: Digest> Write for HashingWriter<W, D> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debian_dependencies() {
+        assert_eq!(dependencies("$auto, libfoo1 (>= 1.2-3), bar, baz:any (<< 2), arm-only [armhf]"), [
+            Dependency { name: "libfoo1".into(), flags: RPMSENSE_GREATER | RPMSENSE_EQUAL, version: "1.2-3".into() },
+            Dependency { name: "bar".into(), flags: 0, version: String::new() },
+            Dependency { name: "baz".into(), flags: RPMSENSE_LESS, version: "2".into() },
+        ]);
+        assert_eq!(dependencies("openssl-libs >= 1:3.0, a | b (= 1)"), [
+            Dependency { name: "openssl-libs".into(), flags: RPMSENSE_GREATER | RPMSENSE_EQUAL, version: "1:3.0".into() },
+            Dependency { name: "(a or b = 1)".into(), flags: 0, version: String::new() },
+        ]);
+    }
+
+    #[test]
+    fn files_sorted_like_strcmp() {
+        let mut paths = ["usr/share/foo/a", "usr/share/foo.d/x", "usr/share/foo-bar", "usr/share/foo"].map(Path::new);
+        paths.sort_by(|a, b| file_order(a, b));
+        assert_eq!(paths, ["usr/share/foo", "usr/share/foo-bar", "usr/share/foo.d/x", "usr/share/foo/a"].map(Path::new));
+    }
+
+    #[test]
+    fn architectures() {
+        assert_eq!(rpm_architecture_from_rust_triple("x86_64-unknown-linux-gnu"), "x86_64");
+        assert_eq!(rpm_architecture_from_rust_triple("armv7-unknown-linux-gnueabihf"), "armv7hl");
+        assert_eq!(rpm_architecture_from_rust_triple("powerpc64le-unknown-linux-gnu"), "ppc64le");
+        assert_eq!(rpm_architecture_from_rust_triple("riscv64gc-unknown-linux-gnu"), "riscv64");
+    }
+
+    #[test]
+    fn header_layout() {
+        let mut header = Header::default();
+        header.set(RPMTAG_NAME, Value::String("foo".into()));
+        header.set(RPMTAG_FILEMODES, Value::Int16(vec![0o100644]));
+        header.set(RPMTAG_FILESIZES, Value::Int32(vec![]));
+        header.set(RPMTAG_FILEINODES, Value::Int32(vec![7]));
+        let bytes = header.to_bytes(RPMTAG_HEADERIMMUTABLE);
+        let be = |pos: usize| u32::from_be_bytes(bytes[pos..pos + 4].try_into().unwrap());
+
+        assert_eq!(bytes[..8], HEADER_MAGIC);
+        assert_eq!(be(8), 4); // region + 3 tags, without the empty one
+        let data_start = 16 + 4 * 16;
+        assert_eq!(be(12) as usize, bytes.len() - data_start);
+        // region entry points to the trailer, and the trailer points back to the start of the index
+        assert_eq!([be(16), be(20), be(28)], [RPMTAG_HEADERIMMUTABLE, 7, 16]);
+        let trailer = data_start + be(24) as usize;
+        assert_eq!(be(trailer), RPMTAG_HEADERIMMUTABLE);
+        assert_eq!(be(trailer + 8) as i32, -4 * 16);
+        // sorted by tag, with aligned data
+        assert_eq!([be(32), be(48), be(64)], [RPMTAG_NAME, RPMTAG_FILEMODES, RPMTAG_FILEINODES]);
+        assert_eq!(&bytes[data_start..data_start + 4], b"foo\0");
+        assert_eq!(be(56), 4);
+        assert_eq!(be(72), 8);
+        assert_eq!(be(data_start + 8), 7);
+    }
+}
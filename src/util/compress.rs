@@ -1,23 +1,116 @@
 use crate::error::{CDResult, CargoDebError};
-use std::io::{BufWriter, Read};
-#[cfg(feature = "lzma")]
-use std::num::NonZeroUsize;
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek};
+use std::path::Path;
 use std::process::{Child, ChildStdin, Command, Stdio};
-use std::{io, ops};
+use std::io;
 use zopfli::{BlockType, GzipEncoder, Options};
 
 pub struct CompressConfig {
     /// Don't compress heavily
     pub fast: bool,
-    pub compress_type: Format,
+    /// Overrides the format set in `Cargo.toml`. Defaults to xz if neither is set.
+    pub compress_type: Option<Format>,
+    /// Format of `control.tar`, if it should differ from `data.tar`
+    pub control_compress_type: Option<Format>,
     pub compress_system: bool,
     pub rsyncable: bool,
+    /// Overrides `compression` options set in `Cargo.toml`
+    pub options: CompressionOptions,
+}
+
+/// Tuning of the compressor. Unset options use the defaults of the format.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompressionOptions {
+    /// Format-specific level, e.g. 0-9 for xz. Takes precedence over `fast`.
+    pub level: Option<u32>,
+    /// xz's `--extreme` mode
+    pub extreme: Option<bool>,
+    /// Number of threads for xz, zstd, and gzip (except zopfli). 0 or unset uses all available cores.
+    pub threads: Option<u32>,
+    /// xz's memory usage limit for compression, in bytes. Reduces the number of threads to fit.
+    pub memlimit: Option<u64>,
+}
+
+impl CompressionOptions {
+    /// Options set in `self` take precedence
+    #[must_use]
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            level: self.level.or(fallback.level),
+            extreme: self.extreme.or(fallback.extreme),
+            threads: self.threads.or(fallback.threads),
+            memlimit: self.memlimit.or(fallback.memlimit),
+        }
+    }
+
+    /// Merges command-line options with the ones from `Cargo.toml`, which were meant for `manifest_format`.
+    /// If the format is changed, the level, `extreme`, and `memlimit` from `Cargo.toml` don't apply.
+    /// The level from `Cargo.toml` doesn't apply in `fast` mode either.
+    #[must_use]
+    pub fn or_manifest(self, manifest: Self, manifest_format: Format, format: Format, fast: bool) -> Self {
+        let same_format = manifest_format == format;
+        Self {
+            level: self.level.or(manifest.level.filter(|_| same_format && !fast)),
+            extreme: self.extreme.or(manifest.extreme.filter(|_| same_format)),
+            threads: self.threads.or(manifest.threads),
+            memlimit: self.memlimit.or(manifest.memlimit.filter(|_| same_format)),
+        }
+    }
+
+    fn threads(&self) -> u32 {
+        self.threads.filter(|&t| t > 0)
+            .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get() as u32))
+    }
+
+    fn check(&self, compress_format: Format) -> CDResult<()> {
+        let Some(program) = compress_format.program() else {
+            // there's nothing to tune, but it's handy for overriding the format set in Cargo.toml
+            return Ok(());
+        };
+        if let Some(level) = self.level {
+            if !compress_format.levels().contains(&level) {
+                let range = compress_format.levels();
+                return Err(CargoDebError::InvalidCompression(format!("level {level} is out of range for {program} ({}-{})", range.start(), range.end())));
+            }
+        }
+        if !matches!(compress_format, Format::Xz) {
+            if self.extreme == Some(true) {
+                return Err(CargoDebError::InvalidCompression("extreme is only supported by xz".into()));
+            }
+            if self.memlimit.is_some() {
+                return Err(CargoDebError::InvalidCompression("memlimit is only supported by xz".into()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses sizes like `512MiB`, `1G` or `1000000`.
+/// Like in xz, the suffixes are multiples of 1024.
+#[must_use]
+pub fn parse_byte_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let digits_end = size.find(|c: char| !c.is_ascii_digit()).unwrap_or(size.len());
+    let (num, suffix) = size.split_at(digits_end);
+    let num: u64 = num.parse().ok()?;
+    let shift = match suffix.trim_start().to_ascii_lowercase().as_str() {
+        "" | "b" => 0,
+        "k" | "kb" | "kib" => 10,
+        "m" | "mb" | "mib" => 20,
+        "g" | "gb" | "gib" => 30,
+        _ => return None,
+    };
+    num.checked_mul(1 << shift)
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Format {
     Xz,
     Gzip,
+    Zstd,
+    /// Plain `.tar`, for when compression is a waste of time
+    None,
 }
 
 impl Format {
@@ -26,34 +119,68 @@ impl Format {
         match self {
             Self::Xz => "xz",
             Self::Gzip => "gz",
+            Self::Zstd => "zst",
+            Self::None => "",
         }
     }
 
-    fn program(self) -> &'static str {
+    /// Accepts names used by `-Z` and file extensions
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "xz" => Self::Xz,
+            "gz" | "gzip" => Self::Gzip,
+            "zstd" | "zst" => Self::Zstd,
+            "none" => Self::None,
+            _ => return None,
+        })
+    }
+
+    /// Command-line tool for the format. The tar is written as-is without one.
+    fn program(self) -> Option<&'static str> {
         match self {
-            Self::Xz => "xz",
-            Self::Gzip => "gzip",
+            Self::Xz => Some("xz"),
+            Self::Gzip => Some("gzip"),
+            Self::Zstd => Some("zstd"),
+            Self::None => None,
         }
     }
 
-    const fn level(self, fast: bool) -> u32 {
+    pub(crate) const fn level(self, fast: bool) -> u32 {
         match self {
             Self::Xz => if fast { 1 } else { 6 },
             Self::Gzip => if fast { 1 } else { 9 },
+            // 3 is zstd's (and dpkg's) default, 19 is the highest level that doesn't need --ultra
+            Self::Zstd => if fast { 3 } else { 19 },
+            Self::None => 0,
+        }
+    }
+
+    const fn levels(self) -> std::ops::RangeInclusive<u32> {
+        match self {
+            Self::Xz => 0..=9,
+            Self::Gzip => 1..=9,
+            Self::Zstd => 1..=22,
+            Self::None => 0..=0,
         }
     }
 }
 
 enum Writer {
     #[cfg(feature = "lzma")]
-    Xz(xz2::write::XzEncoder<Vec<u8>>),
-    #[cfg(feature = "gzip")]
-    Gz(flate2::write::GzEncoder<Vec<u8>>),
-    ZopfliGz(BufWriter<GzipEncoder<Vec<u8>>>),
+    Xz(xz2::write::XzEncoder<File>),
+    Gz(flate2::write::GzEncoder<File>, flate2::Compression),
+    ParallelGz(ParallelGzEncoder),
+    /// Not started until it's known whether the input is large enough for `ParallelGz`
+    PendingGz(PendingGz),
+    ZopfliGz(BufWriter<GzipEncoder<File>>, Options),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::write::Encoder<'static, File>),
+    None(BufWriter<File>),
     StdIn {
         compress_format: Format,
         child: Child,
-        handle: std::thread::JoinHandle<io::Result<Vec<u8>>>,
+        handle: std::thread::JoinHandle<io::Result<File>>,
         stdin: BufWriter<ChildStdin>,
     },
 }
@@ -62,7 +189,7 @@ impl Writer {
     fn finish(self) -> io::Result<Compressed> {
         match self {
             #[cfg(feature = "lzma")]
-            Self::Xz(w) => w.finish().map(|data| Compressed { compress_format: Format::Xz, data }),
+            Self::Xz(w) => Compressed::new(Format::Xz, w.finish()?),
             Self::StdIn {
                 compress_format,
                 mut child,
@@ -71,62 +198,399 @@ impl Writer {
             } => {
                 drop(stdin);
                 child.wait()?;
-                handle.join().unwrap().map(|data| Compressed { compress_format, data })
+                Compressed::new(compress_format, handle.join().unwrap()?)
             },
-            #[cfg(feature = "gzip")]
-            Self::Gz(w) => w.finish().map(|data| Compressed { compress_format: Format::Gzip, data }),
-            Self::ZopfliGz(w) => w.into_inner()?.finish().map(|data| Compressed { compress_format: Format::Gzip, data }),
+            Self::Gz(w, _) => Compressed::new(Format::Gzip, w.finish()?),
+            Self::ParallelGz(w) => Compressed::new(Format::Gzip, w.finish()?),
+            Self::PendingGz(w) => w.into_writer(false)?.finish(),
+            Self::ZopfliGz(w, _) => Compressed::new(Format::Gzip, w.into_inner()?.finish()?),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(w) => Compressed::new(Format::Zstd, w.finish()?),
+            Self::None(w) => Compressed::new(Format::None, w.into_inner()?),
         }
     }
-}
 
-pub struct Compressor {
-    writer: Writer,
-    pub uncompressed_size: usize,
+    /// Makes the data that follows compress independently of the data before,
+    /// so that a change in the input doesn't change the rest of the output
+    fn resync(&mut self) -> io::Result<()> {
+        let dest = match self {
+            // LZMA_FULL_FLUSH ends the xz block and resets the encoder state
+            #[cfg(feature = "lzma")]
+            Self::Xz(w) => return io::Write::flush(w),
+            Self::Gz(w, _) => w.get_ref(),
+            // blocks are compressed separately anyway
+            Self::ParallelGz(w) => return w.resync(),
+            Self::PendingGz(w) => {
+                w.resyncs.push(w.data.len());
+                return Ok(());
+            },
+            Self::ZopfliGz(w, _) => w.get_ref().get_ref(),
+            // zstd has its own rsyncable mode, and the command-line tools get `--rsyncable`
+            #[cfg(feature = "zstd")]
+            Self::Zstd(_) => return Ok(()),
+            Self::None(_) | Self::StdIn { .. } => return Ok(()),
+        };
+        // Deflate can't reset its state mid-stream, but a new gzip member can be started instead.
+        // Decompressors read concatenated members as one stream.
+        // The placeholder is never written to, it's only there while the old member is finished.
+        let placeholder = Self::None(BufWriter::new(dest.try_clone()?));
+        *self = match std::mem::replace(self, placeholder) {
+            Self::Gz(w, level) => Self::Gz(flate2::write::GzEncoder::new(w.finish()?, level), level),
+            Self::ZopfliGz(w, options) => {
+                let dest = w.into_inner()?.finish()?;
+                Self::ZopfliGz(GzipEncoder::new_buffered(options, BlockType::Dynamic, dest)?, options)
+            },
+            _ => unreachable!(),
+        };
+        Ok(())
+    }
+
+    /// Switches to the parallel encoder once there's enough input for it
+    fn start_parallel_gz(&mut self) -> io::Result<()> {
+        let Self::PendingGz(w) = self else {
+            return Ok(());
+        };
+        if w.data.len() < PendingGz::PARALLEL_MIN_LEN {
+            return Ok(());
+        }
+        // The placeholder is never written to, it's only there while the pending data is moved
+        let placeholder = Self::None(BufWriter::new(w.dest.try_clone()?));
+        if let Self::PendingGz(w) = std::mem::replace(self, placeholder) {
+            *self = w.into_writer(true)?;
+        }
+        Ok(())
+    }
 }
 
-impl io::Write for Compressor {
+impl io::Write for Writer {
     fn flush(&mut self) -> io::Result<()> {
-        match &mut self.writer {
+        match self {
             #[cfg(feature = "lzma")]
-            Writer::Xz(w) => w.flush(),
-            #[cfg(feature = "gzip")]
-            Writer::Gz(w) => w.flush(),
-            Writer::ZopfliGz(w) => w.flush(),
-            Writer::StdIn { stdin, .. } => stdin.flush(),
+            Self::Xz(w) => w.flush(),
+            Self::Gz(w, _) => w.flush(),
+            Self::ParallelGz(w) => w.flush(),
+            Self::PendingGz(_) => Ok(()),
+            Self::ZopfliGz(w, _) => w.flush(),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(w) => w.flush(),
+            Self::None(w) => w.flush(),
+            Self::StdIn { stdin, .. } => stdin.flush(),
         }
     }
 
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        let len = match &mut self.writer {
+        match self {
             #[cfg(feature = "lzma")]
-            Writer::Xz(w) => w.write(buf),
-            #[cfg(feature = "gzip")]
-            Writer::Gz(w) => w.write(buf),
-            Writer::ZopfliGz(w) => w.write(buf),
-            Writer::StdIn { stdin, .. } => stdin.write(buf),
-        }?;
-        self.uncompressed_size += len;
-        Ok(len)
+            Self::Xz(w) => w.write(buf),
+            Self::Gz(w, _) => w.write(buf),
+            Self::ParallelGz(w) => w.write(buf),
+            Self::PendingGz(w) => {
+                w.data.extend_from_slice(buf);
+                self.start_parallel_gz()?;
+                Ok(buf.len())
+            },
+            Self::ZopfliGz(w, _) => w.write(buf),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(w) => w.write(buf),
+            Self::None(w) => w.write(buf),
+            Self::StdIn { stdin, .. } => stdin.write(buf),
+        }
     }
 
     fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
-        match &mut self.writer {
+        match self {
             #[cfg(feature = "lzma")]
-            Writer::Xz(w) => w.write_all(buf),
-            #[cfg(feature = "gzip")]
-            Writer::Gz(w) => w.write_all(buf),
-            Writer::ZopfliGz(w) => w.write_all(buf),
-            Writer::StdIn { stdin, .. } => stdin.write_all(buf),
-        }?;
-        self.uncompressed_size += buf.len();
+            Self::Xz(w) => w.write_all(buf),
+            Self::Gz(w, _) => w.write_all(buf),
+            Self::ParallelGz(w) => w.write_all(buf),
+            Self::PendingGz(w) => {
+                w.data.extend_from_slice(buf);
+                self.start_parallel_gz()
+            },
+            Self::ZopfliGz(w, _) => w.write_all(buf),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(w) => w.write_all(buf),
+            Self::None(w) => w.write_all(buf),
+            Self::StdIn { stdin, .. } => stdin.write_all(buf),
+        }
+    }
+}
+
+/// Finds content-defined chunk boundaries using a gear rolling hash (as in FastCDC).
+/// The boundaries depend only on the last 64 bytes of input, so after an insertion or deletion
+/// the boundaries, and the compressed output that follows them, line up again.
+struct Chunker {
+    hash: u64,
+    chunk_len: usize,
+    min_chunk_len: usize,
+    mask: u64,
+}
+
+impl Chunker {
+    fn new(avg_chunk_len: usize) -> Self {
+        let bits = avg_chunk_len.next_power_of_two().trailing_zeros();
+        Self {
+            hash: 0,
+            chunk_len: 0,
+            min_chunk_len: avg_chunk_len / 4,
+            // the top bits of the hash are mixed from the most bytes
+            mask: !(u64::MAX >> bits),
+        }
+    }
+
+    /// Length of `data` until the end of the current chunk, if it ends within `data`
+    fn next_boundary(&mut self, data: &[u8]) -> Option<usize> {
+        for (i, &byte) in data.iter().enumerate() {
+            self.hash = (self.hash << 1).wrapping_add(GEAR[byte as usize]);
+            self.chunk_len += 1;
+            if self.hash & self.mask == 0 && self.chunk_len >= self.min_chunk_len {
+                self.chunk_len = 0;
+                return Some(i + 1);
+            }
+        }
+        None
+    }
+}
+
+/// Random values for the gear hash, from splitmix64
+static GEAR: [u64; 256] = {
+    let mut table = [0; 256];
+    let mut state = 0x9E37_79B9_7F4A_7C15_u64;
+    let mut i = 0;
+    while i < table.len() {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
+
+/// Gzip input is held in memory until it's large enough to be worth compressing on all cores.
+/// Smaller inputs are compressed at the end, with Zopfli at the highest level.
+struct PendingGz {
+    dest: File,
+    data: Vec<u8>,
+    /// Offsets in `data` where the output has to resync
+    resyncs: Vec<usize>,
+    level: u32,
+    threads: usize,
+}
+
+impl PendingGz {
+    /// Smaller inputs take little time with Zopfli anyway
+    const PARALLEL_MIN_LEN: usize = 1 << 20;
+
+    fn into_writer(self, parallel: bool) -> io::Result<Writer> {
+        let mut writer = if parallel {
+            Writer::ParallelGz(ParallelGzEncoder::new(self.dest, flate2::Compression::new(self.level), self.threads)?)
+        } else if self.level < 9 {
+            let level = flate2::Compression::new(self.level);
+            Writer::Gz(flate2::write::GzEncoder::new(self.dest, level), level)
+        } else {
+            let options = Options { iteration_count: 7.try_into().unwrap(), ..Options::default() };
+            Writer::ZopfliGz(GzipEncoder::new_buffered(options, BlockType::Dynamic, self.dest)?, options)
+        };
+        let mut start = 0;
+        for end in self.resyncs {
+            io::Write::write_all(&mut writer, &self.data[start..end])?;
+            writer.resync()?;
+            start = end;
+        }
+        io::Write::write_all(&mut writer, &self.data[start..])?;
+        Ok(writer)
+    }
+}
+
+/// Compresses blocks of input on all cores, like pigz, and stitches them into a single gzip stream.
+///
+/// Each block is primed with the last 32KB of the input before it, so the ratio is nearly the same as
+/// of a single-threaded deflate. The blocks end with a sync flush on a byte boundary, so their
+/// deflate streams can be concatenated.
+struct ParallelGzEncoder {
+    dest: BufWriter<File>,
+    level: flate2::Compression,
+    threads: usize,
+    /// Full blocks waiting to be compressed in one batch
+    blocks: Vec<GzBlock>,
+    current: GzBlock,
+    /// Up to 32KB of input preceding the next batch
+    dictionary: Vec<u8>,
+    crc: flate2::Crc,
+}
+
+#[derive(Default)]
+struct GzBlock {
+    data: Vec<u8>,
+    /// Doesn't refer to any data before it, for rsyncable output
+    independent: bool,
+}
+
+impl ParallelGzEncoder {
+    /// Same as pigz
+    const BLOCK_LEN: usize = 128 << 10;
+    /// Maximum distance of a deflate back-reference
+    const WINDOW_LEN: usize = 32 << 10;
+
+    fn new(mut dest: File, level: flate2::Compression, threads: usize) -> io::Result<Self> {
+        // mtime is 0 for reproducibility, OS is Unix
+        io::Write::write_all(&mut dest, &[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 3])?;
+        Ok(Self {
+            dest: BufWriter::with_capacity(1 << 16, dest),
+            level,
+            threads: threads.max(1),
+            blocks: Vec::with_capacity(threads),
+            current: GzBlock { data: Vec::with_capacity(Self::BLOCK_LEN), independent: true },
+            dictionary: Vec::new(),
+            crc: flate2::Crc::new(),
+        })
+    }
+
+    /// Ends the current block early, and doesn't let the next one refer to it
+    fn resync(&mut self) -> io::Result<()> {
+        if !self.current.data.is_empty() {
+            self.end_block()?;
+        }
+        self.current.independent = true;
+        Ok(())
+    }
+
+    fn end_block(&mut self) -> io::Result<()> {
+        let next = GzBlock { data: Vec::with_capacity(Self::BLOCK_LEN), independent: false };
+        self.blocks.push(std::mem::replace(&mut self.current, next));
+        if self.blocks.len() >= self.threads {
+            self.compress_blocks(false)?;
+        }
         Ok(())
     }
+
+    /// Small inputs end up as a single block, and aren't sent to other threads
+    fn compress_blocks(&mut self, last: bool) -> io::Result<()> {
+        use rayon::prelude::*;
+
+        let blocks = std::mem::take(&mut self.blocks);
+        let mut dictionaries = Vec::with_capacity(blocks.len());
+        for block in &blocks {
+            if block.independent {
+                self.dictionary.clear();
+            }
+            dictionaries.push(self.dictionary.clone());
+            self.dictionary.extend_from_slice(&block.data);
+            let excess = self.dictionary.len().saturating_sub(Self::WINDOW_LEN);
+            self.dictionary.drain(..excess);
+        }
+
+        let level = self.level;
+        let last_index = blocks.len().wrapping_sub(1);
+        let compress = |(i, (block, dictionary)): (usize, (&GzBlock, &Vec<u8>))| {
+            let mut crc = flate2::Crc::new();
+            crc.update(&block.data);
+            deflate_block(level, dictionary, &block.data, last && i == last_index).map(|out| (out, crc))
+        };
+        let compressed: Vec<_> = if blocks.len() > 1 {
+            blocks.par_iter().zip(&dictionaries).enumerate().map(compress).collect::<io::Result<_>>()?
+        } else {
+            blocks.iter().zip(&dictionaries).enumerate().map(compress).collect::<io::Result<_>>()?
+        };
+        for (out, crc) in compressed {
+            io::Write::write_all(&mut self.dest, &out)?;
+            self.crc.combine(&crc);
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<File> {
+        let current = std::mem::take(&mut self.current);
+        // always has at least one block to end the deflate stream, even if it's empty
+        self.blocks.push(current);
+        self.compress_blocks(true)?;
+        let mut trailer = [0; 8];
+        trailer[..4].copy_from_slice(&self.crc.sum().to_le_bytes());
+        trailer[4..].copy_from_slice(&self.crc.amount().to_le_bytes());
+        io::Write::write_all(&mut self.dest, &trailer)?;
+        self.dest.into_inner().map_err(|e| e.into_error())
+    }
+}
+
+impl io::Write for ParallelGzEncoder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(Self::BLOCK_LEN - self.current.data.len());
+        self.current.data.extend_from_slice(&buf[..len]);
+        if self.current.data.len() >= Self::BLOCK_LEN {
+            self.end_block()?;
+        }
+        Ok(len)
+    }
+
+    /// Only complete blocks can be compressed
+    fn flush(&mut self) -> io::Result<()> {
+        self.dest.flush()
+    }
+}
+
+/// Raw deflate of one block. It's byte-aligned at the end, and has the final bit set only if `last`.
+fn deflate_block(level: flate2::Compression, dictionary: &[u8], data: &[u8], last: bool) -> io::Result<Vec<u8>> {
+    use flate2::{Compress, FlushCompress, Status};
+
+    let mut deflate = Compress::new(level, false);
+    if !dictionary.is_empty() {
+        deflate.set_dictionary(dictionary).map_err(io::Error::other)?;
+    }
+    let flush = if last { FlushCompress::Finish } else { FlushCompress::Sync };
+    let mut out = Vec::with_capacity(data.len() / 2 + 64);
+    loop {
+        let consumed = deflate.total_in() as usize;
+        let status = deflate.compress_vec(&data[consumed..], &mut out, flush).map_err(io::Error::other)?;
+        // the flush is complete when it hasn't used all of the available space
+        let done = if last { status == Status::StreamEnd } else { out.len() < out.capacity() };
+        if done && deflate.total_in() as usize == data.len() {
+            return Ok(out);
+        }
+        out.reserve(out.capacity().max(1 << 12));
+    }
+}
+
+pub struct Compressor {
+    writer: Writer,
+    /// Set when rsyncable
+    chunker: Option<Chunker>,
+    pub uncompressed_size: usize,
+}
+
+impl io::Write for Compressor {
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Some(chunker) = &mut self.chunker else {
+            let len = self.writer.write(buf)?;
+            self.uncompressed_size += len;
+            return Ok(len);
+        };
+        // the chunker has to see every byte exactly once, so this can't be a partial write
+        let boundary = chunker.next_boundary(buf);
+        let len = boundary.unwrap_or(buf.len());
+        self.writer.write_all(&buf[..len])?;
+        self.uncompressed_size += len;
+        if boundary.is_some() {
+            self.writer.resync()?;
+        }
+        Ok(len)
+    }
 }
 
 impl Compressor {
     const fn new(writer: Writer) -> Self {
-        Self { writer, uncompressed_size: 0 }
+        Self { writer, chunker: None, uncompressed_size: 0 }
+    }
+
+    /// Adds resync points to the compressed stream, roughly every `avg_chunk_len` bytes of input
+    fn rsyncable(mut self, avg_chunk_len: usize) -> Self {
+        self.chunker = Some(Chunker::new(avg_chunk_len));
+        self
     }
 
     pub fn finish(self) -> CDResult<Compressed> {
@@ -134,78 +598,179 @@ impl Compressor {
     }
 }
 
+/// Compressed data spooled to a temporary file, so that large packages don't need to fit in memory
 pub struct Compressed {
     compress_format: Format,
-    data: Vec<u8>,
+    file: File,
+    len: u64,
 }
 
 impl Compressed {
+    fn new(compress_format: Format, mut file: File) -> io::Result<Self> {
+        let len = file.stream_position()?;
+        file.rewind()?;
+        Ok(Self { compress_format, file, len })
+    }
+
     #[must_use]
     pub fn extension(&self) -> &'static str {
         self.compress_format.extension()
     }
-}
 
-impl ops::Deref for Compressed {
-    type Target = Vec<u8>;
+    /// Reads the data from the start again, e.g. after hashing it
+    pub fn rewind(&mut self) -> io::Result<()> {
+        self.file.rewind()
+    }
+
+    /// Size of the compressed data in bytes
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
 
-    fn deref(&self) -> &Self::Target {
-        &self.data
+impl Read for Compressed {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
     }
 }
 
-fn system_compressor(compress_format: Format, fast: bool) -> CDResult<Compressor> {
-    let mut child = Command::new(compress_format.program())
-        .arg(format!("-{}", compress_format.level(fast)))
+/// From liblzma's `lzma/container.h`, not exported by xz2
+#[cfg(feature = "lzma")]
+const LZMA_PRESET_EXTREME: u32 = 1 << 31;
+
+/// The file is deleted as soon as it's closed. Falls back to the system temp dir,
+/// because the `temp_dir` may be removed by a parallel build of another package.
+fn temp_file(temp_dir: &Path) -> CDResult<File> {
+    let _ = std::fs::create_dir_all(temp_dir);
+    tempfile::tempfile_in(temp_dir)
+        .or_else(|_| tempfile::tempfile())
+        .map_err(|e| CargoDebError::IoFile("Unable to create a temporary file for compression", e, temp_dir.into()))
+}
+
+fn system_compressor(compress_format: Format, fast: bool, options: &CompressionOptions, rsyncable: bool, mut dest: File) -> CDResult<Compressor> {
+    let Some(program) = compress_format.program() else {
+        return Err(CargoDebError::InvalidCompression("there's no compressor program for uncompressed tarballs".into()));
+    };
+    let level = options.level.unwrap_or(compress_format.level(fast));
+    let mut cmd = Command::new(program);
+    cmd.arg(format!("-{level}"));
+    match compress_format {
+        Format::Xz => {
+            if options.extreme == Some(true) {
+                cmd.arg("--extreme");
+            }
+            if let Some(threads) = options.threads {
+                cmd.arg(format!("--threads={threads}"));
+            }
+            if let Some(memlimit) = options.memlimit {
+                cmd.arg(format!("--memlimit-compress={memlimit}"));
+            }
+        },
+        Format::Zstd => {
+            // zstd prints progress to the terminal otherwise
+            cmd.arg("-q");
+            if level > 19 {
+                cmd.arg("--ultra");
+            }
+            if let Some(threads) = options.threads {
+                cmd.arg(format!("--threads={threads}"));
+            }
+            if rsyncable {
+                cmd.arg("--rsyncable");
+            }
+        },
+        Format::Gzip => {
+            if rsyncable {
+                cmd.arg("--rsyncable");
+            }
+        },
+        Format::None => {},
+    }
+    let mut child = cmd
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::inherit())
         .spawn()
-        .map_err(|e| CargoDebError::CommandFailed(e, compress_format.program().into()))?;
+        .map_err(|e| CargoDebError::CommandFailed(e, program.into()))?;
     let mut stdout = child.stdout.take().unwrap();
 
     let handle = std::thread::spawn(move || {
-        let mut buf = Vec::new();
-        stdout.read_to_end(&mut buf).map(|_| buf)
+        io::copy(&mut stdout, &mut dest).map(|_| dest)
     });
 
     let stdin = BufWriter::with_capacity(1<<16, child.stdin.take().unwrap());
     Ok(Compressor::new(Writer::StdIn { compress_format, child, handle, stdin }))
 }
 
-pub fn select_compressor(fast: bool, compress_format: Format, use_system: bool) -> CDResult<Compressor> {
-    if use_system {
-        return system_compressor(compress_format, fast);
-    }
+/// The compressed output is written to an anonymous file in `temp_dir`.
+///
+/// `rsyncable` adds content-defined resync points, so that a small change in the input
+/// causes only a small change in the output, at a cost of slightly worse compression.
+pub fn select_compressor(fast: bool, compress_format: Format, options: &CompressionOptions, use_system: bool, rsyncable: bool, temp_dir: &Path) -> CDResult<Compressor> {
+    options.check(compress_format)?;
+    let dest = temp_file(temp_dir)?;
+    let level = options.level.unwrap_or(compress_format.level(fast));
 
     match compress_format {
+        Format::None => Ok(Compressor::new(Writer::None(BufWriter::with_capacity(1<<16, dest)))),
+        _ if use_system => system_compressor(compress_format, fast, options, rsyncable, dest),
         #[cfg(feature = "lzma")]
         Format::Xz => {
             // Compression level 6 is a good trade off between size and [ridiculously] long compression time
-            let encoder = xz2::stream::MtStreamBuilder::new()
-                .threads(std::thread::available_parallelism().unwrap_or(NonZeroUsize::new(1).unwrap()).get() as u32)
-                .preset(compress_format.level(fast))
-                .encoder()
-                .map_err(CargoDebError::LzmaCompressionError)?;
-
-            let writer = xz2::write::XzEncoder::new_stream(Vec::new(), encoder);
-            Ok(Compressor::new(Writer::Xz(writer)))
+            let mut builder = xz2::stream::MtStreamBuilder::new();
+            builder.preset(if options.extreme == Some(true) { level | LZMA_PRESET_EXTREME } else { level });
+            let mut threads = options.threads();
+            builder.threads(threads);
+            if let Some(memlimit) = options.memlimit {
+                // same as xz's --memlimit-compress, which scales down threads first
+                while threads > 1 && builder.memusage() > memlimit {
+                    threads -= 1;
+                    builder.threads(threads);
+                }
+                if builder.memusage() > memlimit {
+                    return Err(CargoDebError::InvalidCompression(format!("memlimit {memlimit} is too low for xz level {level} (needs {})", builder.memusage())));
+                }
+            }
+            let encoder = builder.encoder().map_err(CargoDebError::LzmaCompressionError)?;
+
+            let writer = Compressor::new(Writer::Xz(xz2::write::XzEncoder::new_stream(dest, encoder)));
+            // xz blocks are large, and resetting the dictionary too often hurts compression a lot
+            Ok(if rsyncable { writer.rsyncable(1 << 20) } else { writer })
         },
         #[cfg(not(feature = "lzma"))]
-        Format::Xz => system_compressor(compress_format, fast),
+        Format::Xz => system_compressor(compress_format, fast, options, rsyncable, dest),
+        #[cfg(feature = "zstd")]
+        Format::Zstd => {
+            let mut encoder = zstd::stream::write::Encoder::new(dest, level as i32)?;
+            encoder.multithread(options.threads())?;
+            encoder.include_checksum(true)?;
+            if rsyncable {
+                encoder.set_parameter(zstd::zstd_safe::CParameter::RSyncable(true))?;
+            }
+            Ok(Compressor::new(Writer::Zstd(encoder)))
+        },
+        #[cfg(not(feature = "zstd"))]
+        Format::Zstd => system_compressor(compress_format, fast, options, rsyncable, dest),
         Format::Gzip => {
-            #[cfg(feature = "gzip")]
-            if fast {
-                let level = flate2::Compression::new(compress_format.level(fast));
-                let inner_writer = flate2::write::GzEncoder::new(Vec::new(), level);
-                return Ok(Compressor::new(Writer::Gz(inner_writer)));
-            }
-
-            let inner_writer = GzipEncoder::new_buffered(Options {
-                iteration_count: (if fast { 1 } else { 7 }).try_into().unwrap(),
-                ..Options::default()
-            }, BlockType::Dynamic, Vec::new()).unwrap();
-            Ok(Compressor::new(Writer::ZopfliGz(inner_writer)))
+            // same as gzip --rsyncable, the deflate window is only 32KB anyway
+            let compressor = |writer| {
+                let writer = Compressor::new(writer);
+                if rsyncable { writer.rsyncable(1 << 16) } else { writer }
+            };
+
+            Ok(compressor(Writer::PendingGz(PendingGz {
+                dest,
+                data: Vec::new(),
+                resyncs: Vec::new(),
+                level,
+                threads: options.threads() as usize,
+            })))
         },
     }
 }
@@ -226,3 +791,209 @@ pub(crate) fn gzipped(mut content: &[u8]) -> io::Result<Vec<u8>> {
     encoder.finish()?;
     Ok(compressed)
 }
+
+/// Reads the output of `xz -dc`, etc., and fails if the command does
+struct SystemDecompressor {
+    program: &'static str,
+    child: Child,
+    stdout: std::process::ChildStdout,
+}
+
+impl Read for SystemDecompressor {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.stdout.read(buf)?;
+        if n == 0 && !buf.is_empty() {
+            let status = self.child.wait()?;
+            if !status.success() {
+                return Err(io::Error::other(format!("{} -d failed with {status}", self.program)));
+            }
+        }
+        Ok(n)
+    }
+}
+
+/// Decompresses with a built-in library if it's enabled, or the command-line tool otherwise.
+///
+/// All formats accept concatenated streams, like the ones made for `--rsyncable`.
+pub fn decompressor<'a>(compress_format: Format, input: impl Read + 'a) -> CDResult<Box<dyn Read + 'a>> {
+    Ok(match compress_format {
+        Format::None => Box::new(input),
+        #[cfg(feature = "lzma")]
+        Format::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(input)),
+        #[cfg(feature = "zstd")]
+        Format::Zstd => Box::new(zstd::stream::read::Decoder::new(input)?),
+        Format::Gzip => Box::new(flate2::read::MultiGzDecoder::new(input)),
+        #[allow(unreachable_patterns)]
+        _ => Box::new(system_decompressor(compress_format, input)?),
+    })
+}
+
+fn system_decompressor(compress_format: Format, mut input: impl Read) -> CDResult<SystemDecompressor> {
+    let Some(program) = compress_format.program() else {
+        return Err(CargoDebError::InvalidCompression("there's no decompressor program for uncompressed tarballs".into()));
+    };
+
+    // the input is spooled, so that there's no need for a thread feeding the stdin
+    let mut spooled = temp_file(&std::env::temp_dir())?;
+    io::copy(&mut input, &mut spooled)?;
+    spooled.rewind()?;
+    let mut child = Command::new(program)
+        .arg("-dc")
+        .stdin(spooled)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|e| CargoDebError::CommandFailed(e, program.into()))?;
+    let stdout = child.stdout.take().unwrap();
+    Ok(SystemDecompressor { program, child, stdout })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn byte_sizes() {
+        assert_eq!(parse_byte_size("1000"), Some(1000));
+        assert_eq!(parse_byte_size("512MiB"), Some(512 << 20));
+        assert_eq!(parse_byte_size("2 G"), Some(2 << 30));
+        assert_eq!(parse_byte_size("64k"), Some(64 << 10));
+        assert_eq!(parse_byte_size("MiB"), None);
+        assert_eq!(parse_byte_size("1.5G"), None);
+        assert_eq!(parse_byte_size("99999999999G"), None);
+    }
+
+    #[test]
+    fn options_precedence_and_checks() {
+        let cli = CompressionOptions { level: Some(1), ..Default::default() };
+        let manifest = CompressionOptions { level: Some(9), extreme: Some(true), ..Default::default() };
+        assert_eq!(cli.or(manifest), CompressionOptions { level: Some(1), extreme: Some(true), ..Default::default() });
+
+        assert!(manifest.check(Format::Xz).is_ok());
+        assert!(manifest.check(Format::Zstd).is_err());
+        assert!(CompressionOptions { level: Some(22), ..Default::default() }.check(Format::Zstd).is_ok());
+        assert!(CompressionOptions { level: Some(10), ..Default::default() }.check(Format::Gzip).is_err());
+        assert!(CompressionOptions { memlimit: Some(1 << 30), ..Default::default() }.check(Format::Gzip).is_err());
+        assert!(manifest.check(Format::None).is_ok());
+    }
+
+    #[test]
+    fn manifest_options_dropped_for_another_format() {
+        let manifest = CompressionOptions { level: Some(9), extreme: Some(true), threads: Some(2), memlimit: Some(1 << 30) };
+        let merged = CompressionOptions::default().or_manifest(manifest, Format::Xz, Format::Gzip, false);
+        assert_eq!(merged, CompressionOptions { threads: Some(2), ..Default::default() });
+        assert!(merged.check(Format::Gzip).is_ok());
+        assert_eq!(CompressionOptions::default().or_manifest(manifest, Format::Xz, Format::Xz, false), manifest);
+        let cli = CompressionOptions { level: Some(3), ..Default::default() };
+        assert_eq!(cli.or_manifest(manifest, Format::Xz, Format::Zstd, false).level, Some(3));
+    }
+
+    #[test]
+    fn fast_ignores_manifest_level() {
+        let manifest = CompressionOptions { level: Some(9), extreme: Some(true), ..Default::default() };
+        let merged = CompressionOptions::default().or_manifest(manifest, Format::Xz, Format::Xz, true);
+        assert_eq!(merged, CompressionOptions { extreme: Some(true), ..Default::default() });
+        // --compress-level still overrides --fast
+        let cli = CompressionOptions { level: Some(4), ..Default::default() };
+        assert_eq!(cli.or_manifest(manifest, Format::Xz, Format::Xz, true).level, Some(4));
+    }
+
+    /// Compressible, but not repetitive
+    fn sample_text(len: usize) -> Vec<u8> {
+        let words = ["deb", "package ", "cargo", "tar ", "control", "\n", "rsync ", "data"];
+        let mut state = 12345_u32;
+        let mut out = Vec::with_capacity(len + 10);
+        while out.len() < len {
+            state ^= state << 13; state ^= state >> 17; state ^= state << 5;
+            out.extend_from_slice(words[state as usize % words.len()].as_bytes());
+        }
+        out
+    }
+
+    fn compress(data: &[u8], format: Format, rsyncable: bool) -> Vec<u8> {
+        let mut c = select_compressor(true, format, &CompressionOptions::default(), false, rsyncable, &std::env::temp_dir()).unwrap();
+        c.write_all(data).unwrap();
+        let mut out = Vec::new();
+        c.finish().unwrap().read_to_end(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn chunker_resyncs_after_insertion() {
+        let data = sample_text(1 << 20);
+        let boundaries = |data: &[u8]| {
+            let mut chunker = Chunker::new(1 << 14);
+            let mut pos = 0;
+            let mut all = Vec::new();
+            while let Some(len) = chunker.next_boundary(&data[pos..]) {
+                pos += len;
+                all.push(pos);
+            }
+            all
+        };
+        let orig = boundaries(&data);
+        assert!(orig.len() > 20 && orig.len() < 200, "{}", orig.len());
+
+        let mut edited = b"inserted".to_vec();
+        edited.extend_from_slice(&data);
+        let edited = boundaries(&edited);
+        let shifted: Vec<_> = edited.iter().map(|&b| b - 8).collect();
+        assert!(orig[2..].iter().all(|b| shifted.contains(b)));
+    }
+
+    #[test]
+    fn rsyncable_gzip_output_resyncs() {
+        let data = sample_text(1 << 18);
+        let mut edited = data.clone();
+        edited[100..110].copy_from_slice(b"0123456789");
+
+        let plain = compress(&data, Format::Gzip, true);
+        let changed = compress(&edited, Format::Gzip, true);
+        let common_suffix = plain.iter().rev().zip(changed.iter().rev()).take_while(|(a, b)| a == b).count();
+        assert!(common_suffix > plain.len() / 2, "{common_suffix}/{}", plain.len());
+    }
+
+    #[test]
+    fn small_gzip_stays_single_threaded() {
+        let data = sample_text(100_000);
+        let mut c = select_compressor(false, Format::Gzip, &CompressionOptions::default(), false, false, &std::env::temp_dir()).unwrap();
+        c.write_all(&data).unwrap();
+        assert!(matches!(c.writer, Writer::PendingGz(_)));
+        let mut compressed = Vec::new();
+        c.finish().unwrap().read_to_end(&mut compressed).unwrap();
+        let mut decompressed = Vec::new();
+        flate2::read::GzDecoder::new(&compressed[..]).read_to_end(&mut decompressed).unwrap();
+        assert!(decompressed == data);
+    }
+
+    #[test]
+    fn parallel_gzip_is_a_single_stream() {
+        let data = sample_text(ParallelGzEncoder::BLOCK_LEN * 9 + 123);
+        assert!(data.len() > PendingGz::PARALLEL_MIN_LEN);
+        // the default level, which is otherwise Zopfli's
+        let options = CompressionOptions { threads: Some(4), ..Default::default() };
+        let mut c = select_compressor(false, Format::Gzip, &options, false, false, &std::env::temp_dir()).unwrap();
+        assert!(matches!(c.writer, Writer::PendingGz(_)));
+        c.write_all(&data).unwrap();
+        assert!(matches!(c.writer, Writer::ParallelGz(_)));
+        let mut compressed = Vec::new();
+        c.finish().unwrap().read_to_end(&mut compressed).unwrap();
+        assert!(compressed.len() < data.len() / 2);
+
+        // unlike MultiGzDecoder, this stops after the first gzip member
+        let mut decompressed = Vec::new();
+        flate2::read::GzDecoder::new(&compressed[..]).read_to_end(&mut decompressed).unwrap();
+        assert!(decompressed == data);
+    }
+
+    #[test]
+    #[cfg(feature = "lzma")]
+    fn rsyncable_xz_round_trips() {
+        let data = sample_text(3 << 20);
+        let compressed = compress(&data, Format::Xz, true);
+        let mut decompressed = Vec::new();
+        xz2::read::XzDecoder::new(&compressed[..]).read_to_end(&mut decompressed).unwrap();
+        assert!(decompressed == data);
+    }
+}
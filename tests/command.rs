@@ -68,6 +68,33 @@ fn build_with_explicit_compress_type_xz() {
     assert!(ddir.path().join("usr/bin/renamed2").exists());
 }
 
+#[test]
+fn build_with_explicit_compress_type_zstd() {
+    let (cdir, ddir) = extract_built_package_from_manifest("tests/test-workspace/test-ws1/Cargo.toml", "zst", &["--no-strip", "--fast", "--compress-type", "zstd"]);
+    assert!(cdir.path().join("control").exists());
+    assert!(ddir.path().join("usr/local/bin/decoy").exists());
+}
+
+#[test]
+fn build_with_command_line_compress_zstd() {
+    let (_, ddir) = extract_built_package_from_manifest("tests/test-workspace/test-ws2/Cargo.toml", "zst", &["--no-strip", "--compress-system", "--compress-type", "zstd"]);
+    assert!(ddir.path().join("usr/bin/renamed2").exists());
+}
+
+#[test]
+fn build_rsyncable_gz() {
+    // rsyncable gzip is made of many concatenated gzip members
+    let (_, ddir) = extract_built_package_from_manifest("tests/test-workspace/test-ws1/Cargo.toml", "gz", &["--no-strip", "--fast", "--rsyncable", "--compress-type", "gz"]);
+    assert!(ddir.path().join("usr/local/bin/decoy").exists());
+}
+
+#[test]
+fn build_with_explicit_compress_type_none() {
+    let (cdir, ddir) = extract_built_package_from_manifest("tests/test-workspace/test-ws1/Cargo.toml", "", &["--no-strip", "--compress-type", "none"]);
+    assert!(cdir.path().join("control").exists());
+    assert!(ddir.path().join("usr/local/bin/decoy").exists());
+}
+
 #[test]
 fn build_with_command_line_compress_xz() {
     // ws1 with system xz
@@ -75,6 +102,313 @@ fn build_with_command_line_compress_xz() {
     assert!(ddir.path().join("usr/local/bin/decoy").exists());
 }
 
+#[test]
+fn build_with_separate_control_compress_type() {
+    let (_tmpdir, deb_path, _) = cargo_deb("tests/test-workspace/test-ws1/Cargo.toml", &["--no-strip", "--fast", "-Z", "zstd", "--control-compress-type", "gz"]);
+    let (cdir, ddir) = extract_package_with_control_ext(&deb_path, "gz", "zst");
+    assert!(cdir.path().join("control").exists());
+    assert!(ddir.path().join("usr/local/bin/decoy").exists());
+}
+
+#[test]
+fn build_with_compression_from_manifest() {
+    let (_, ddir) = extract_built_package_from_manifest("example/Cargo.toml", "zst", &["--no-strip", "--variant=zstd"]);
+    assert!(ddir.path().join("usr/bin/example").exists());
+
+    // command-line options override Cargo.toml
+    let (_, ddir) = extract_built_package_from_manifest("example/Cargo.toml", "xz", &["--no-strip", "--variant=zstd", "-Z", "xz", "--compress-level=0", "--compress-extreme"]);
+    assert!(ddir.path().join("usr/bin/example").exists());
+}
+
+#[test]
+fn dry_run() {
+    let (cargo_dir, stdout) = cargo_deb_stdout("tests/test-workspace/test-ws1/Cargo.toml", &["--no-strip", "--dry-run"]);
+    assert!(stdout.starts_with("==> control <==\nPackage: test1-crate-name\n"), "{stdout}");
+    assert!(stdout.contains("==> assets <==\nSOURCE "), "{stdout}");
+    assert!(stdout.contains(" /usr/local/bin/decoy "), "{stdout}");
+    assert!(!stdout.contains("==> md5sums <=="), "{stdout}");
+    assert!(!cargo_dir.path().join("debian").exists());
+
+    let (_, stdout) = cargo_deb_stdout("tests/test-workspace/test-ws1/Cargo.toml", &["--no-strip", "--emit=control"]);
+    assert!(stdout.starts_with("Package: test1-crate-name\n"), "{stdout}");
+    assert!(!stdout.contains("==>"));
+}
+
+#[test]
+fn extra_fields() {
+    let (_, stdout) = cargo_deb_stdout("example/Cargo.toml", &["--variant=fields", "--field", "Origin=", "--field=X-Git-Commit=abc123", "--emit=control"]);
+    let fields = stdout.split_once("\nDescription:").unwrap().1.split_once("\n").unwrap().1;
+    assert!(fields.ends_with("X-Vendor-Build: 42\nX-Notes: first\n second\nX-Git-Commit: abc123\n\n"), "{stdout}");
+    assert!(!stdout.contains("Origin"), "{stdout}");
+}
+
+#[test]
+fn inspect_and_extract() {
+    let (_tmpdir, deb_path, _) = cargo_deb("tests/test-workspace/test-ws1/Cargo.toml", &["--no-strip", "--fast", "-Z", "gz", "--rsyncable"]);
+
+    let deb = cargo_deb::deb::reader::DebReader::open(&deb_path).unwrap();
+    assert_eq!(deb.field("Package"), Some("test1-crate-name"));
+    let files = deb.data_files().unwrap();
+    assert!(files.iter().any(|f| f.path == Path::new("usr/local/bin/decoy")));
+    let copyright = files.iter().find(|f| f.path == Path::new("usr/share/doc/test1-crate-name/copyright")).unwrap();
+    assert_eq!(copyright.mode, 0o644);
+    assert!(copyright.size > 0);
+
+    let cmd_path = env!("CARGO_BIN_EXE_cargo-deb");
+    let output = Command::new(cmd_path).arg("--inspect").arg(&deb_path).output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Package: test1-crate-name\n"), "{stdout}");
+    assert!(stdout.contains(" ./usr/local/bin/decoy\n"), "{stdout}");
+
+    let ddir = tempfile::tempdir().unwrap();
+    let output = Command::new(cmd_path).arg("--extract").arg(&deb_path).arg(ddir.path()).output().unwrap();
+    assert!(output.status.success());
+    assert!(ddir.path().join("usr/local/bin/decoy").exists());
+}
+
+#[test]
+fn asset_owners() {
+    let (_tmpdir, deb_path, _) = cargo_deb("example/Cargo.toml", &["--variant=owned", "--no-strip", "--fast"]);
+
+    let deb = cargo_deb::deb::reader::DebReader::open(&deb_path).unwrap();
+    let files = deb.data_files().unwrap();
+    let named = files.iter().find(|f| f.path == Path::new("var/lib/example/owned/4.txt")).unwrap();
+    assert_eq!((named.uid, named.gid), (0, 0));
+    assert_eq!((named.user.as_deref(), named.group.as_deref()), (Some("example"), Some("adm")));
+    let numeric = files.iter().find(|f| f.path == Path::new("var/lib/example/owned/3.txt")).unwrap();
+    assert_eq!((numeric.uid, numeric.gid), (1000, 1000));
+
+    let postinst = String::from_utf8(deb.control_file("postinst").unwrap().data.clone()).unwrap();
+    assert!(postinst.contains("for f in '/var/lib/example/owned/4.txt' ; do"), "{postinst}");
+    assert!(postinst.contains("chown 'example:adm' \"$f\""), "{postinst}");
+    assert!(!postinst.contains("3.txt"), "{postinst}");
+}
+
+#[test]
+fn dir_assets() {
+    let (_tmpdir, deb_path, _) = cargo_deb("example/Cargo.toml", &["--variant=dirs", "--no-strip", "--fast"]);
+
+    let deb = cargo_deb::deb::reader::DebReader::open(&deb_path).unwrap();
+    let files = deb.data_files().unwrap();
+    let file = |path: &str| files.iter().find(|f| f.path == Path::new(path)).unwrap_or_else(|| panic!("{path} in {files:#?}"));
+    let log = file("var/log/example");
+    assert_eq!((log.kind.clone(), log.mode, log.user.as_deref()), (cargo_deb::deb::reader::DataFileKind::Dir, 0o750, Some("example")));
+    assert_eq!(file("var/lib/example/private").mode, 0o700);
+    assert_eq!(file("var/lib/example/private/4.txt").mode, 0o600);
+    assert_eq!(file("var/lib/example").mode, 0o755);
+    assert_eq!(files.iter().filter(|f| f.path == Path::new("var/lib/example/private")).count(), 1);
+
+    let md5sums = String::from_utf8(deb.control_file("md5sums").unwrap().data.clone()).unwrap();
+    assert!(!md5sums.contains("var/log/example"), "{md5sums}");
+    let postinst = String::from_utf8(deb.control_file("postinst").unwrap().data.clone()).unwrap();
+    assert!(postinst.contains("for f in '/var/log/example' ; do"), "{postinst}");
+    assert!(postinst.contains("chmod 0750 \"$f\""), "{postinst}");
+}
+
+#[test]
+#[cfg(unix)]
+fn stage_dir() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let stage = tempfile::tempdir().unwrap();
+    let stage_dir = stage.path().join("stage");
+    let stage_arg = format!("--stage-dir={}", stage_dir.display());
+    let (cargo_dir, _) = cargo_deb_stdout("example/Cargo.toml", &["--variant=dirs", "--no-strip", "--fast", &stage_arg]);
+    assert!(!cargo_dir.path().join("debian").exists());
+
+    let mode = |path: &str| fs::symlink_metadata(stage_dir.join(path)).unwrap().permissions().mode() & 0o7777;
+    assert_eq!(mode("usr/bin/example"), 0o755);
+    assert_eq!(mode("var/log/example"), 0o750);
+    assert_eq!(mode("var/lib/example/private/4.txt"), 0o600);
+    assert_eq!(mode("DEBIAN"), 0o755);
+    assert_eq!(mode("DEBIAN/postinst"), 0o755);
+    let control = fs::read_to_string(stage_dir.join("DEBIAN/control")).unwrap();
+    assert!(control.starts_with("Package: example-dirs\n"), "{control}");
+    let md5sums = fs::read_to_string(stage_dir.join("DEBIAN/md5sums")).unwrap();
+    assert!(md5sums.contains("  var/lib/example/private/4.txt\n"), "{md5sums}");
+    assert!(fs::read(stage_dir.join("usr/share/doc/example-dirs/changelog.Debian.gz")).unwrap().starts_with(&[0x1f, 0x8b]));
+
+    // never mixes with leftovers from an earlier build
+    let output = Command::new(env!("CARGO_BIN_EXE_cargo-deb"))
+        .env("CARGO_TARGET_DIR", cargo_dir.path())
+        .args(["--no-build", "--no-strip", "--variant=dirs", &stage_arg])
+        .current_dir("example")
+        .output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("is not empty"));
+}
+
+#[test]
+fn oci_layout() {
+    use sha2::{Digest, Sha256};
+
+    let image = tempfile::tempdir().unwrap();
+    let image_dir = image.path().join("image");
+    let (_cargo_dir, _) = cargo_deb_stdout("example/Cargo.toml", &["--no-strip", "--fast", &format!("--oci-layout={}", image_dir.display())]);
+
+    assert_eq!(fs::read_to_string(image_dir.join("oci-layout")).unwrap(), r#"{"imageLayoutVersion":"1.0.0"}"#);
+    // every blob is named after its hash, and the descriptors must agree
+    let blob = |descriptor: &serde_json::Value| {
+        let digest = descriptor["digest"].as_str().unwrap().strip_prefix("sha256:").unwrap();
+        let data = fs::read(image_dir.join("blobs/sha256").join(digest)).unwrap();
+        assert_eq!(format!("{:x}", Sha256::digest(&data)), digest);
+        assert_eq!(descriptor["size"].as_u64().unwrap(), data.len() as u64);
+        data
+    };
+    let index: serde_json::Value = serde_json::from_slice(&fs::read(image_dir.join("index.json")).unwrap()).unwrap();
+    let manifest_descriptor = &index["manifests"][0];
+    assert_eq!(manifest_descriptor["annotations"]["org.opencontainers.image.ref.name"], "0.1.0-1");
+    let manifest: serde_json::Value = serde_json::from_slice(&blob(manifest_descriptor)).unwrap();
+    let config: serde_json::Value = serde_json::from_slice(&blob(&manifest["config"])).unwrap();
+    assert_eq!(config["config"]["Entrypoint"], serde_json::json!(["/usr/bin/example"]));
+    assert_eq!(config["os"], "linux");
+    assert_eq!(manifest["layers"][0]["mediaType"], "application/vnd.oci.image.layer.v1.tar+gzip");
+
+    let layer_path = image.path().join("layer.tar.gz");
+    fs::write(&layer_path, blob(&manifest["layers"][0])).unwrap();
+    let tar = Command::new("gzip").arg("-dc").arg(&layer_path).output().unwrap().stdout;
+    assert_eq!(config["rootfs"]["diff_ids"][0].as_str().unwrap(), format!("sha256:{:x}", Sha256::digest(&tar)));
+    let paths = tar::Archive::new(&tar[..]).entries().unwrap()
+        .map(|e| e.unwrap().path().unwrap().into_owned())
+        .collect::<Vec<_>>();
+    assert!(paths.contains(&PathBuf::from("./usr/bin/example")), "{paths:?}");
+}
+
+#[test]
+fn udeb() {
+    let (_tmpdir, udeb_path, _) = cargo_deb("example/Cargo.toml", &["--variant=udeb", "--no-strip", "--fast"]);
+    assert_eq!(udeb_path.file_name().unwrap().to_str().unwrap().split('_').next(), Some("example-udeb"));
+    assert_eq!(udeb_path.extension().unwrap(), "udeb");
+
+    let (cdir, ddir) = extract_package_with_control_ext(&udeb_path, DEFAULT_COMPRESSION_EXT, DEFAULT_COMPRESSION_EXT);
+    let control = fs::read_to_string(cdir.path().join("control")).unwrap();
+    assert!(control.contains("\nPackage-Type: udeb\n"), "{control}");
+    assert!(control.contains("\nSection: debian-installer\n"), "{control}");
+    assert!(!cdir.path().join("md5sums").exists());
+    assert!(ddir.path().join("usr/bin/example").exists());
+    assert!(!ddir.path().join("usr/share/doc").exists());
+}
+
+#[test]
+fn ipk_format() {
+    let (_cargo_dir, ipk_path, _) = cargo_deb("example/Cargo.toml", &["--no-strip", "--fast", "--format=ipk"]);
+    assert_eq!(ipk_path.extension().unwrap(), "ipk");
+    let ipk_dir = tempfile::tempdir().unwrap();
+
+    let gunzip = |path: &Path| Command::new("gzip").arg("-dc").arg(path).output().unwrap().stdout;
+    let outer = gunzip(&ipk_path);
+    let mut outer = tar::Archive::new(&outer[..]);
+    let mut members = Vec::new();
+    for entry in outer.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let path = entry.path().unwrap().into_owned();
+        entry.unpack(ipk_dir.path().join(path.file_name().unwrap())).unwrap();
+        members.push(path);
+    }
+    assert_eq!(members, [Path::new("./debian-binary"), Path::new("./control.tar.gz"), Path::new("./data.tar.gz")]);
+    assert_eq!(fs::read(ipk_dir.path().join("debian-binary")).unwrap(), b"2.0\n");
+
+    let control_tar = gunzip(&ipk_dir.path().join("control.tar.gz"));
+    let mut control = String::new();
+    for entry in tar::Archive::new(&control_tar[..]).entries().unwrap() {
+        let mut entry = entry.unwrap();
+        if entry.path().unwrap() == Path::new("./control") {
+            entry.read_to_string(&mut control).unwrap();
+        }
+    }
+    assert!(control.starts_with("Package: example\nVersion: 0.1.0-1\nArchitecture: "), "{control}");
+    assert!(control.contains("\nLicense: MIT\n"), "{control}");
+    assert!(!control.contains("Homepage") && !control.contains("Multi-Arch"), "{control}");
+
+    let data_tar = gunzip(&ipk_dir.path().join("data.tar.gz"));
+    let paths = tar::Archive::new(&data_tar[..]).entries().unwrap()
+        .map(|e| e.unwrap().path().unwrap().into_owned())
+        .collect::<Vec<_>>();
+    assert!(paths.contains(&PathBuf::from("./usr/bin/example")), "{paths:?}");
+}
+
+#[test]
+fn rpm_format() {
+    use md5::{Digest, Md5};
+    use sha2::Sha256;
+
+    let (_cargo_dir, rpm_path, _) = cargo_deb("example/Cargo.toml", &["--no-strip", "--fast", "--format=rpm", "-Z", "gz"]);
+    let file_name = rpm_path.file_name().unwrap().to_str().unwrap();
+    assert!(file_name.starts_with("example-0.1.0-1.") && file_name.ends_with(".rpm"), "{file_name}");
+    let rpm = fs::read(&rpm_path).unwrap();
+    assert_eq!(rpm[..4], [0xed, 0xab, 0xee, 0xdb]);
+    assert_eq!(&rpm[10..26], b"example-0.1.0-1\0");
+
+    let be = |pos: usize| u32::from_be_bytes(rpm[pos..pos + 4].try_into().unwrap()) as usize;
+    // returns strings of tags and the end of the header
+    let parse_header = |start: usize| {
+        assert_eq!(rpm[start..start + 8], [0x8e, 0xad, 0xe8, 0x01, 0, 0, 0, 0]);
+        let (index_len, data_len) = (be(start + 8), be(start + 12));
+        let data_start = start + 16 + index_len * 16;
+        let mut tags = std::collections::HashMap::new();
+        for entry in (start + 16..data_start).step_by(16) {
+            let (offset, count) = (data_start + be(entry + 8), be(entry + 12));
+            let value = match be(entry + 4) {
+                6 | 8 | 9 => rpm[offset..].split(|&b| b == 0).take(count).map(|s| String::from_utf8(s.to_vec()).unwrap()).collect(),
+                7 => vec![String::from_utf8_lossy(&rpm[offset..offset + count]).into_owned()],
+                _ => vec![],
+            };
+            tags.insert(be(entry), (value, offset));
+        }
+        (tags, data_start + data_len)
+    };
+    let (signature, signature_end) = parse_header(96);
+    let header_start = signature_end.next_multiple_of(8);
+    let (header, payload_start) = parse_header(header_start);
+
+    let sha256 = Sha256::digest(&rpm[header_start..payload_start]).iter().map(|b| format!("{b:02x}")).collect::<String>();
+    assert_eq!(signature[&273].0, [sha256]);
+    let md5_offset = signature[&1004].1;
+    assert_eq!(rpm[md5_offset..md5_offset + 16], Md5::digest(&rpm[header_start..])[..]);
+
+    assert_eq!(header[&1000].0, ["example"]);
+    assert_eq!(header[&1001].0, ["0.1.0"]);
+    assert_eq!(header[&1002].0, ["1"]);
+    assert_eq!(header[&1125].0, ["gzip"]);
+    assert!(header[&1117].0.contains(&"example".to_string()), "{:?}", header[&1117]);
+    assert!(header[&1118].0.contains(&"/usr/bin/".to_string()), "{:?}", header[&1118]);
+
+    let mut gunzip = Command::new("gzip").arg("-dc").stdin(std::process::Stdio::piped()).stdout(std::process::Stdio::piped()).spawn().unwrap();
+    let mut stdin = gunzip.stdin.take().unwrap();
+    let payload = rpm[payload_start..].to_vec();
+    let writer = std::thread::spawn(move || std::io::Write::write_all(&mut stdin, &payload).unwrap());
+    let cpio = gunzip.wait_with_output().unwrap().stdout;
+    writer.join().unwrap();
+    assert!(cpio.starts_with(b"070701"));
+    assert!(cpio.windows(17).any(|w| w == b"./usr/bin/example"));
+    assert!(cpio.windows(10).any(|w| w == b"TRAILER!!!"));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn dpkg_deb_compat() {
+    let (_tmpdir, deb_path, _) = cargo_deb("example/Cargo.toml", &["--dpkg-deb-compat", "-Z", "none", "--no-strip", "--fast"]);
+
+    // repacking the same tree with dpkg-deb must give the same bytes
+    let tree = tempfile::tempdir().unwrap();
+    let Ok(status) = Command::new("dpkg-deb").arg("--raw-extract").arg(&deb_path).arg(tree.path().join("t")).status() else {
+        eprintln!("dpkg-deb is not installed");
+        return;
+    };
+    assert!(status.success());
+    let deb = fs::read(&deb_path).unwrap();
+    let mtime = std::str::from_utf8(&deb[8 + 16..8 + 28]).unwrap().trim();
+    let repacked = tree.path().join("repacked.deb");
+    assert!(Command::new("dpkg-deb")
+        .env("SOURCE_DATE_EPOCH", mtime)
+        .args(["--root-owner-group", "-Znone", "--build"])
+        .arg(tree.path().join("t"))
+        .arg(&repacked)
+        .status().unwrap().success());
+    assert!(deb == fs::read(&repacked).unwrap(), "{} differs from {}", deb_path.display(), repacked.display());
+}
+
 #[test]
 #[cfg_attr(all(feature = "default_enable_separate_debug_symbols", target_os = "macos"), ignore = "no objcopy")]
 fn build_with_target() {
@@ -143,11 +477,11 @@ fn check_ar(deb_path: &Path) {
             data: Some(b"2.0\n"),
         },
         Expected {
-            name_prefix: "control.tar.",
+            name_prefix: "control.tar",
             data: None,
         },
         Expected {
-            name_prefix: "data.tar.",
+            name_prefix: "data.tar",
             data: None,
         },
     ];
@@ -193,6 +527,11 @@ fn check_ar(deb_path: &Path) {
 
 #[track_caller]
 fn extract_package(deb_path: &Path, ext: &str) -> (TempDir, TempDir) {
+    extract_package_with_control_ext(deb_path, ext, ext)
+}
+
+#[track_caller]
+fn extract_package_with_control_ext(deb_path: &Path, control_ext: &str, ext: &str) -> (TempDir, TempDir) {
     check_ar(deb_path);
     let ardir = tempfile::tempdir().expect("testdir");
     assert!(ardir.path().exists());
@@ -204,21 +543,23 @@ fn extract_package(deb_path: &Path, ext: &str) -> (TempDir, TempDir) {
 
     assert_eq!("2.0\n", fs::read_to_string(ardir.path().join("debian-binary")).unwrap());
 
-    assert!(ardir.path().join(format!("data.tar.{ext}")).exists());
-    assert!(ardir.path().join(format!("control.tar.{ext}")).exists());
+    // uncompressed tarballs don't have an extension
+    let tar_name = |base, ext: &str| if ext.is_empty() { format!("{base}.tar") } else { format!("{base}.tar.{ext}") };
+    assert!(ardir.path().join(tar_name("data", ext)).exists());
+    assert!(ardir.path().join(tar_name("control", control_ext)).exists());
 
     let cdir = tempfile::tempdir().unwrap();
     assert!(Command::new("tar")
         .arg("xf")
         .current_dir(cdir.path())
-        .arg(ardir.path().join(format!("control.tar.{ext}")))
+        .arg(ardir.path().join(tar_name("control", control_ext)))
         .status().unwrap().success());
 
     let ddir = tempfile::tempdir().unwrap();
     assert!(Command::new("tar")
         .arg("xf")
         .current_dir(ddir.path())
-        .arg(ardir.path().join(format!("data.tar.{ext}")))
+        .arg(ardir.path().join(tar_name("data", ext)))
         .status().unwrap().success());
 
     (cdir, ddir)
@@ -230,6 +571,21 @@ fn extract_package(deb_path: &Path, ext: &str) -> (TempDir, TempDir) {
 /// The `--manifest-path` and `--output` args are automatically set.
 #[track_caller]
 fn cargo_deb(manifest_path: &str, args: &[&str]) -> (TempDir, PathBuf, Option<PathBuf>) {
+    let (cargo_dir, stdout) = cargo_deb_stdout(manifest_path, args);
+
+    // prints deb path on the last line
+    let mut lines = stdout.lines();
+    let deb_path = PathBuf::from(lines.next_back().unwrap());
+    assert!(deb_path.exists());
+    let before_last_line = lines.next_back().unwrap_or_default();
+    let maybe_ddeb_path = if before_last_line.ends_with(".ddeb") { Some(PathBuf::from(before_last_line)) } else { None };
+
+    assert!(deb_path.starts_with(&cargo_dir));
+    (cargo_dir, deb_path, maybe_ddeb_path)
+}
+
+/// Returns the isolated target dir, and everything printed to stdout
+fn cargo_deb_stdout(manifest_path: &str, args: &[&str]) -> (TempDir, String) {
     let _ = env_logger::builder().is_test(true).try_init();
 
     let cargo_dir = tempfile::tempdir().unwrap();
@@ -249,7 +605,7 @@ fn cargo_deb(manifest_path: &str, args: &[&str]) -> (TempDir, PathBuf, Option<Pa
         .output()
         .unwrap();
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
     let stderr = String::from_utf8_lossy(&output.stderr);
     println!("{manifest_path} {args:?}: {stdout}");
     eprintln!("{manifest_path} {args:?}: {stderr}");
@@ -261,16 +617,7 @@ fn cargo_deb(manifest_path: &str, args: &[&str]) -> (TempDir, PathBuf, Option<Pa
             cargo_dir.keep().display(),
         );
     }
-
-    // prints deb path on the last line
-    let mut lines = stdout.lines();
-    let deb_path = PathBuf::from(lines.next_back().unwrap());
-    assert!(deb_path.exists());
-    let before_last_line = lines.next_back().unwrap_or_default();
-    let maybe_ddeb_path = if before_last_line.ends_with(".ddeb") { Some(PathBuf::from(before_last_line)) } else { None };
-
-    assert!(deb_path.starts_with(&cargo_dir));
-    (cargo_dir, deb_path, maybe_ddeb_path)
+    (cargo_dir, stdout)
 }
 
 #[test]
@@ -296,6 +643,17 @@ fn run_cargo_deb_command_on_example_dir() {
         &[0x1F, 0x8B],
         &fs::read(ddir.path().join("usr/share/doc/example/changelog.Debian.gz")).unwrap()[..2]
     );
+
+    // merged.txt is a copy of 3.txt
+    use std::os::unix::fs::MetadataExt;
+    assert_eq!(2, fs::metadata(ddir.path().join("var/lib/example/merged.txt")).unwrap().nlink());
+
+    let md5sums = fs::read_to_string(cdir.path().join("md5sums")).unwrap();
+    assert!(md5sums.contains("  usr/bin/example\n"), "{md5sums}");
+    assert!(md5sums.contains("  var/lib/example/merged.txt\n"), "{md5sums}");
+    let status = Command::new("md5sum").arg("--check").arg("--quiet").arg(cdir.path().join("md5sums"))
+        .current_dir(ddir.path()).status().unwrap();
+    assert!(status.success());
 }
 
 #[test]